@@ -0,0 +1,27 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use s8disasm::parser::{decode_instruction, encode_instruction};
+
+fuzz_target!(|data: &[u8]| {
+    for chunk in data.chunks_exact(2) {
+        let word = u16::from_le_bytes([chunk[0], chunk[1]]);
+        let instruction = decode_instruction(word);
+        assert_eq!(
+            instruction.operands().count(),
+            instruction.operand_kinds().len(),
+            "{:#06x} decodes to {}",
+            word,
+            instruction
+        );
+        let op = encode_instruction(&instruction);
+        let decoded = decode_instruction(op);
+        assert_eq!(
+            encode_instruction(&decoded),
+            op,
+            "{:#06x} decodes to {}",
+            op,
+            decoded
+        );
+        assert_eq!(decoded.to_string(), instruction.to_string());
+    }
+});
@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use s8disasm::parser::{self, MAGIC};
+
+fuzz_target!(|data: &[u8]| {
+    let input = [MAGIC, data].concat();
+    let program = parser::program(&input).expect("the input has a header");
+    let lines = parser::disassemble(&input, 0).expect("the input has a header");
+    assert_eq!(lines.len(), program.len() / 2);
+    for (i, line) in lines.iter().enumerate() {
+        assert_eq!(line.pos, 2 * i);
+        assert_eq!(
+            line.op,
+            u16::from_le_bytes([program[2 * i], program[2 * i + 1]])
+        );
+    }
+    let recursive = parser::disassemble_recursive(&input, 0).expect("the input has a header");
+    assert!(recursive.len() >= lines.len());
+    for line in recursive {
+        let _ = line.instruction.to_string();
+    }
+});
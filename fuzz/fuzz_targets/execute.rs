@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use s8disasm::parser::{memory_image, MEMORY_SIZE};
+use s8disasm::{quick_run, Program, State};
+
+const MAX_CYCLES: usize = 10_000;
+
+fuzz_target!(|data: &[u8]| {
+    let (program, stdin) = data.split_at(data.len().min(MEMORY_SIZE));
+    let memory = memory_image(program);
+    let stepped = State::new(memory, stdin.to_vec()).run_result(MAX_CYCLES);
+    let quick = quick_run(&Program::from_image(memory), stdin, MAX_CYCLES);
+    assert!(stepped.cycles <= MAX_CYCLES);
+    assert!(stepped.input_read <= stdin.len());
+    assert_eq!(stepped.halt, quick.halt);
+    assert_eq!(stepped.cycles, quick.cycles);
+    assert_eq!(stepped.input_read, quick.input_read);
+    assert_eq!(stepped.output, quick.output);
+});
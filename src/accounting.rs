@@ -0,0 +1,126 @@
+use crate::emulator::{HaltReason, State};
+use crate::parser::Instruction;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Default)]
+pub struct FunctionCycles {
+    pub calls: usize,
+    pub inclusive: usize,
+    pub exclusive: usize,
+}
+
+pub fn track(
+    stack: &mut Vec<usize>,
+    instruction: &Instruction,
+    pc: usize,
+    functions: &BTreeSet<usize>,
+) -> Option<usize> {
+    let top = stack.len() - 1;
+    match instruction {
+        Instruction::Tur(target) => {
+            stack.push(usize::from(*target));
+            Some(usize::from(*target))
+        }
+        Instruction::Retur if stack.len() > 1 => {
+            stack.pop();
+            None
+        }
+        Instruction::Hopp(_) | Instruction::BHopp(_)
+            if functions.contains(&pc) && stack[top] != pc =>
+        {
+            stack[top] = pc;
+            Some(pc)
+        }
+        _ => None,
+    }
+}
+
+pub fn function_cycles(
+    state: &mut State,
+    functions: &BTreeSet<usize>,
+    max_cycles: usize,
+) -> BTreeMap<usize, FunctionCycles> {
+    let mut table: BTreeMap<usize, FunctionCycles> = BTreeMap::new();
+    let mut stack = vec![state.pc()];
+    table.entry(state.pc()).or_default().calls += 1;
+
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        let instruction = state.next_instruction();
+        let before = state.cycles();
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+        let cost = state.cycles() - before;
+        let active: BTreeSet<usize> = stack.iter().copied().collect();
+        for entry in active {
+            table.entry(entry).or_default().inclusive += cost;
+        }
+        let top = stack.len() - 1;
+        table.entry(stack[top]).or_default().exclusive += cost;
+
+        if let Some(entered) = track(&mut stack, &instruction, state.pc(), functions) {
+            table.entry(entered).or_default().calls += 1;
+        }
+    }
+    if state.halted().is_none() && state.cycles() >= max_cycles {
+        state.halt(HaltReason::TickLimit(max_cycles));
+    }
+    table
+}
+
+pub struct InputSpan {
+    pub bytes: Option<(usize, usize)>,
+    pub cycles: (usize, usize),
+    pub function: usize,
+}
+
+pub fn input_timeline(
+    state: &mut State,
+    functions: &BTreeSet<usize>,
+    max_cycles: usize,
+) -> Vec<InputSpan> {
+    let mut segments: Vec<(usize, BTreeMap<usize, usize>)> = vec![(0, BTreeMap::new())];
+    let mut stack = vec![state.pc()];
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        let instruction = state.next_instruction();
+        let before = state.cycles();
+        let read = state.input_read();
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+        if state.input_read() > read {
+            segments.push((before, BTreeMap::new()));
+        }
+        let top = stack[stack.len() - 1];
+        if let Some((_, cycles)) = segments.last_mut() {
+            *cycles.entry(top).or_default() += state.cycles() - before;
+        }
+        track(&mut stack, &instruction, state.pc(), functions);
+    }
+
+    let end = state.cycles();
+    let mut spans: Vec<InputSpan> = Vec::new();
+    for (i, (start, cycles)) in segments.iter().enumerate() {
+        let function = cycles
+            .iter()
+            .max_by_key(|(&entry, &count)| (count, std::cmp::Reverse(entry)))
+            .map_or(stack[0], |(&entry, _)| entry);
+        let stop = segments.get(i + 1).map_or(end, |(next, _)| *next);
+        let bytes = i.checked_sub(1).map(|byte| (byte, byte));
+        match spans.last_mut() {
+            Some(span) if span.function == function && span.bytes.is_some() && bytes.is_some() => {
+                span.bytes = span
+                    .bytes
+                    .zip(bytes)
+                    .map(|((first, _), (_, last))| (first, last));
+                span.cycles.1 = stop;
+            }
+            _ => spans.push(InputSpan {
+                bytes,
+                cycles: (*start, stop),
+                function,
+            }),
+        }
+    }
+    spans
+}
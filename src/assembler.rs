@@ -0,0 +1,240 @@
+use crate::parser::{encode_instruction, ALEOp, CMPOp, Instruction, Operand, ALE_OPS, CMP_OPS};
+use crate::source::{parse, parse_number, parse_register, SourceLine};
+use std::collections::HashMap;
+use std::fmt;
+
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+pub struct Relocation {
+    pub offset: usize,
+    pub symbol: String,
+    pub line: usize,
+}
+
+pub struct Fragment {
+    pub code: Vec<u8>,
+    pub symbols: HashMap<String, usize>,
+    pub relocations: Vec<Relocation>,
+}
+
+enum Target {
+    Address(u16),
+    Label(String),
+}
+
+fn expect_operands(line: &SourceLine, count: usize) -> Result<(), String> {
+    if line.operands.len() != count {
+        Err(format!(
+            "{} takes {} operand(s), got {}",
+            line.mnemonic.as_deref().unwrap_or(""),
+            count,
+            line.operands.len()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn register(operand: &str) -> Result<Operand, String> {
+    parse_register(operand)
+        .map(|r| Operand::Reg((r & 0xf) as u8))
+        .ok_or_else(|| format!("expected register, got '{}'", operand))
+}
+
+fn target(operand: &str) -> Result<Target, String> {
+    if let Some(address) = parse_number(operand) {
+        Ok(Target::Address((address & 0xfff) as u16))
+    } else if parse_register(operand).is_some() || operand.is_empty() {
+        Err(format!("expected address or label, got '{}'", operand))
+    } else {
+        Ok(Target::Label(operand.to_string()))
+    }
+}
+
+fn ale_op(mnemonic: &str) -> Option<ALEOp> {
+    ALE_OPS.iter().copied().find(|op| op.mnemonic() == mnemonic)
+}
+
+fn cmp_op(mnemonic: &str) -> Option<CMPOp> {
+    CMP_OPS.iter().copied().find(|op| op.mnemonic() == mnemonic)
+}
+
+fn assemble_line(line: &SourceLine) -> Result<(Instruction, Option<String>), String> {
+    let mnemonic = line.mnemonic.as_deref().unwrap_or("");
+    let operands = &line.operands;
+
+    if let Some(op) = ale_op(mnemonic) {
+        expect_operands(line, 2)?;
+        return Ok((
+            Instruction::ALE(op, register(&operands[0])?, register(&operands[1])?),
+            None,
+        ));
+    }
+    if let Some(op) = cmp_op(mnemonic) {
+        expect_operands(line, 2)?;
+        return Ok((
+            Instruction::Sammenligne(op, register(&operands[0])?, register(&operands[1])?),
+            None,
+        ));
+    }
+
+    let instruction = match mnemonic {
+        "SETT" => {
+            expect_operands(line, 2)?;
+            let ra = register(&operands[0])?;
+            if let Some(rb) = parse_register(&operands[1]) {
+                Instruction::Sett(ra, Operand::Reg((rb & 0xf) as u8))
+            } else if let Some(value) = parse_number(&operands[1]) {
+                Instruction::SettImmediate(ra, Operand::Imm(value as u8))
+            } else {
+                return Err(format!(
+                    "expected register or immediate, got '{}'",
+                    operands[1]
+                ));
+            }
+        }
+        "LES" | "SKRIV" | "LAST" | "LAGR" => {
+            expect_operands(line, 1)?;
+            let r = register(&operands[0])?;
+            match mnemonic {
+                "LES" => Instruction::Les(r),
+                "SKRIV" => Instruction::Skriv(r),
+                "LAST" => Instruction::Last(r),
+                _ => Instruction::Lagr(r),
+            }
+        }
+        "HOPP" | "BHOPP" | "TUR" | "FINN" => {
+            expect_operands(line, 1)?;
+            let (address, label) = match target(&operands[0])? {
+                Target::Address(address) => (address, None),
+                Target::Label(label) => (0, Some(label)),
+            };
+            let address = Operand::Addr(address);
+            let instruction = match mnemonic {
+                "HOPP" => Instruction::Hopp(address),
+                "BHOPP" => Instruction::BHopp(address),
+                "TUR" => Instruction::Tur(address),
+                _ => Instruction::Finn(address),
+            };
+            return Ok((instruction, label));
+        }
+        "RETUR" | "STOPP" | "NOPE" => {
+            expect_operands(line, 0)?;
+            match mnemonic {
+                "RETUR" => Instruction::Retur,
+                "STOPP" => Instruction::Stopp,
+                _ => Instruction::Nope,
+            }
+        }
+        _ => return Err(format!("unknown mnemonic '{}'", mnemonic)),
+    };
+    Ok((instruction, None))
+}
+
+pub fn assemble_fragment(source: &str) -> Result<Fragment, Vec<AsmError>> {
+    let mut fragment = Fragment {
+        code: Vec::new(),
+        symbols: HashMap::new(),
+        relocations: Vec::new(),
+    };
+    let mut errors = Vec::new();
+
+    for (i, line) in parse(source).iter().enumerate() {
+        let number = i + 1;
+        let data = line.mnemonic.as_deref() == Some(".DATA");
+        if !data
+            && (line.label.is_some() || line.mnemonic.is_some())
+            && !fragment.code.len().is_multiple_of(2)
+        {
+            fragment.code.push(0);
+        }
+        if let Some(label) = &line.label {
+            if fragment
+                .symbols
+                .insert(label.clone(), fragment.code.len())
+                .is_some()
+            {
+                errors.push(AsmError {
+                    line: number,
+                    message: format!("label '{}' defined more than once", label),
+                });
+            }
+        }
+        match line.mnemonic.as_deref() {
+            None => (),
+            Some(".DATA") => {
+                for operand in &line.operands {
+                    match parse_number(operand) {
+                        Some(value) if value <= 0xff => fragment.code.push(value as u8),
+                        Some(_) => errors.push(AsmError {
+                            line: number,
+                            message: format!("data byte {} does not fit in 8 bits", operand),
+                        }),
+                        None => errors.push(AsmError {
+                            line: number,
+                            message: format!("expected data byte, got '{}'", operand),
+                        }),
+                    }
+                }
+            }
+            Some(_) => match assemble_line(line) {
+                Ok((instruction, label)) => {
+                    if let Some(symbol) = label {
+                        fragment.relocations.push(Relocation {
+                            offset: fragment.code.len(),
+                            symbol,
+                            line: number,
+                        });
+                    }
+                    let op = encode_instruction(&instruction);
+                    fragment.code.extend_from_slice(&op.to_le_bytes());
+                }
+                Err(message) => errors.push(AsmError {
+                    line: number,
+                    message,
+                }),
+            },
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(fragment)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instructions_after_odd_data_are_aligned() {
+        let fragment = assemble_fragment("HOPP next\n.DATA 1, 2, 3\nnext: STOPP\n.DATA 4\nend:")
+            .unwrap_or_else(|_| panic!("bad test source"));
+        assert_eq!(fragment.code, vec![0x08, 0x00, 1, 2, 3, 0, 0, 0, 4, 0]);
+        assert_eq!(fragment.symbols["next"], 6);
+        assert_eq!(fragment.symbols["end"], 10);
+    }
+
+    #[test]
+    fn data_bytes_must_fit() {
+        let errors = assemble_fragment(".DATA 0xff, 0x1234")
+            .err()
+            .unwrap_or_default();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "line 1: data byte 0x1234 does not fit in 8 bits"
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use crate::checkpoint::{visit, Visited};
+use crate::emulator::HaltReason;
+use crate::error::S8Error;
+use crate::parser::{decode_instruction, ALEOp, CMPOp, Instruction, MEMORY_SIZE};
+
+pub const LANES: usize = 64;
+
+pub struct Outcome {
+    pub halt: HaltReason,
+    pub pc: usize,
+    pub cycles: usize,
+}
+
+struct Batch<'a> {
+    memory: Vec<[u8; LANES]>,
+    registers: [[u8; LANES]; 16],
+    flagg: [bool; LANES],
+    pc: [usize; LANES],
+    cycles: [usize; LANES],
+    stacks: Vec<Vec<usize>>,
+    read: [usize; LANES],
+    inputs: &'a [Vec<u8>],
+    halt: [Option<HaltReason>; LANES],
+    running: u64,
+}
+
+fn lanes(mask: u64) -> impl Iterator<Item = usize> {
+    (0..LANES).filter(move |l| mask & 1 << l != 0)
+}
+
+fn select(mask: u64, target: &mut [u8; LANES], values: [u8; LANES]) {
+    for l in 0..LANES {
+        if mask & 1 << l != 0 {
+            target[l] = values[l];
+        }
+    }
+}
+
+impl Batch<'_> {
+    fn word(&self, lane: usize, address: usize) -> u16 {
+        u16::from_le_bytes([self.memory[address][lane], self.memory[address + 1][lane]])
+    }
+
+    fn stop(&mut self, lane: usize, reason: HaltReason) {
+        self.halt[lane] = Some(reason);
+        self.running &= !(1 << lane);
+    }
+
+    fn check(&mut self, lane: usize, max_cycles: usize) {
+        let pc = self.pc[lane];
+        let reason = if self.cycles[lane] >= max_cycles {
+            Some(HaltReason::TickLimit(max_cycles))
+        } else if pc + 1 >= MEMORY_SIZE {
+            Some(HaltReason::from(S8Error::PcOutOfRange(pc)))
+        } else {
+            match decode_instruction(self.word(lane, pc)) {
+                Instruction::Les(_) if self.read[lane] >= self.inputs[lane].len() => {
+                    Some(HaltReason::from(S8Error::InputExhausted(pc)))
+                }
+                Instruction::Retur if self.stacks[lane].is_empty() => {
+                    Some(HaltReason::from(S8Error::EmptyReturnStack(pc)))
+                }
+                _ => None,
+            }
+        };
+        if let Some(reason) = reason {
+            self.stop(lane, reason);
+        }
+    }
+
+    fn group(&self) -> (usize, u16, u64) {
+        let first = lanes(self.running)
+            .min_by_key(|&l| self.pc[l])
+            .expect("some lane is running");
+        let (pc, word) = (self.pc[first], self.word(first, self.pc[first]));
+        let mask = lanes(self.running)
+            .filter(|&l| self.pc[l] == pc && self.word(l, pc) == word)
+            .fold(0, |m, l| m | 1 << l);
+        (pc, word, mask)
+    }
+
+    fn pointer(&self, lane: usize) -> usize {
+        (usize::from(self.registers[1][lane]) << 8 | usize::from(self.registers[0][lane])) & 0xfff
+    }
+
+    fn execute(&mut self, pc: usize, instruction: Instruction, mask: u64) {
+        for l in lanes(mask) {
+            self.pc[l] = pc + 2;
+            self.cycles[l] += 1;
+        }
+        match instruction {
+            Instruction::Sett(ra, rb) => {
+                let b = self.registers[usize::from(rb)];
+                select(mask, &mut self.registers[usize::from(ra)], b);
+            }
+            Instruction::SettImmediate(ra, value) => {
+                select(
+                    mask,
+                    &mut self.registers[usize::from(ra)],
+                    [value.value() as u8; LANES],
+                );
+            }
+            Instruction::ALE(op, ra, rb) => {
+                let (a, b) = (
+                    self.registers[usize::from(ra)],
+                    self.registers[usize::from(rb)],
+                );
+                let mut values = [0; LANES];
+                for l in 0..LANES {
+                    values[l] = match op {
+                        ALEOp::Pluss => a[l].wrapping_add(b[l]),
+                        ALEOp::Minus => a[l].wrapping_sub(b[l]),
+                        ALEOp::Eller => a[l] | b[l],
+                        ALEOp::XEller => a[l] ^ b[l],
+                        ALEOp::Og => a[l] & b[l],
+                        ALEOp::Vskift => a[l].checked_shl(u32::from(b[l])).unwrap_or(0),
+                        ALEOp::Hskift => a[l].checked_shr(u32::from(b[l])).unwrap_or(0),
+                    };
+                }
+                select(mask, &mut self.registers[usize::from(ra)], values);
+            }
+            Instruction::Sammenligne(op, ra, rb) => {
+                let (a, b) = (
+                    self.registers[usize::from(ra)],
+                    self.registers[usize::from(rb)],
+                );
+                for l in lanes(mask) {
+                    self.flagg[l] = match op {
+                        CMPOp::Lik => a[l] == b[l],
+                        CMPOp::Me => a[l] < b[l],
+                        CMPOp::Mel => a[l] <= b[l],
+                        CMPOp::Se => a[l] > b[l],
+                        CMPOp::Sel => a[l] >= b[l],
+                        CMPOp::Ulik => a[l] != b[l],
+                    };
+                }
+            }
+            Instruction::Stopp => {
+                for l in lanes(mask) {
+                    self.pc[l] = pc;
+                    self.stop(l, HaltReason::Stopp);
+                }
+            }
+            Instruction::Hopp(target) => {
+                for l in lanes(mask) {
+                    self.pc[l] = usize::from(target);
+                }
+            }
+            Instruction::BHopp(target) => {
+                let taken = lanes(mask)
+                    .filter(|&l| self.flagg[l])
+                    .fold(0u64, |m, l| m | 1 << l);
+                for l in lanes(taken) {
+                    self.pc[l] = usize::from(target);
+                }
+            }
+            Instruction::Les(r) => {
+                for l in lanes(mask) {
+                    self.registers[usize::from(r)][l] = self.inputs[l][self.read[l]];
+                    self.read[l] += 1;
+                }
+            }
+            Instruction::Tur(target) => {
+                for l in lanes(mask) {
+                    self.stacks[l].push(pc + 2);
+                    self.pc[l] = usize::from(target);
+                }
+            }
+            Instruction::Retur => {
+                for l in lanes(mask) {
+                    self.pc[l] = self.stacks[l].pop().expect("checked before executing");
+                }
+            }
+            Instruction::Finn(address) => {
+                select(mask, &mut self.registers[0], [address.value() as u8; LANES]);
+                select(
+                    mask,
+                    &mut self.registers[1],
+                    [(address.value() >> 8) as u8; LANES],
+                );
+            }
+            Instruction::Last(r) => {
+                for l in lanes(mask) {
+                    self.registers[usize::from(r)][l] = self.memory[self.pointer(l)][l];
+                }
+            }
+            Instruction::Lagr(r) => {
+                for l in lanes(mask) {
+                    let address = self.pointer(l);
+                    self.memory[address][l] = self.registers[usize::from(r)][l];
+                }
+            }
+            Instruction::Skriv(_) | Instruction::Nope | Instruction::Data(_) => (),
+        }
+        for l in lanes(mask & self.running) {
+            if self.pc[l] >= MEMORY_SIZE {
+                self.stop(l, HaltReason::PcOutOfRange(self.pc[l]));
+            }
+        }
+    }
+}
+
+fn run_lanes(
+    memory: &[u8; MEMORY_SIZE],
+    inputs: &[Vec<u8>],
+    max_cycles: usize,
+    visited: &mut Visited,
+) -> Vec<Outcome> {
+    let count = inputs.len().min(LANES);
+    let mut batch = Batch {
+        memory: memory.iter().map(|&b| [b; LANES]).collect(),
+        registers: [[0; LANES]; 16],
+        flagg: [false; LANES],
+        pc: [0; LANES],
+        cycles: [0; LANES],
+        stacks: vec![Vec::new(); count],
+        read: [0; LANES],
+        inputs,
+        halt: [None; LANES],
+        running: if count == LANES {
+            u64::MAX
+        } else {
+            (1 << count) - 1
+        },
+    };
+    for l in lanes(batch.running) {
+        batch.check(l, max_cycles);
+    }
+    while batch.running != 0 {
+        let (pc, word, mask) = batch.group();
+        visit(visited, pc);
+        batch.execute(pc, decode_instruction(word), mask);
+        for l in lanes(mask & batch.running) {
+            batch.check(l, max_cycles);
+        }
+    }
+    (0..count)
+        .map(|l| Outcome {
+            halt: batch.halt[l].expect("every lane halts"),
+            pc: batch.pc[l],
+            cycles: batch.cycles[l],
+        })
+        .collect()
+}
+
+pub fn run(
+    memory: &[u8; MEMORY_SIZE],
+    inputs: &[Vec<u8>],
+    max_cycles: usize,
+    visited: &mut Visited,
+) -> Vec<Outcome> {
+    inputs
+        .chunks(LANES)
+        .flat_map(|chunk| run_lanes(memory, chunk, max_cycles, visited))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble_fragment;
+    use crate::emulator::State;
+    use crate::linker::{link, Module};
+    use crate::parser::memory_image;
+
+    const PROGRAM: &str = "
+        LES r2
+        SETT r3, 8
+        SETT r4, r2
+        VSKIFT r4, r3
+        SETT r5, r2
+        HSKIFT r5, r3
+        SETT r3, 200
+        HSKIFT r5, r3
+        SETT r6, 1
+        SETT r7, 0
+        MINUS r7, r6
+        PLUSS r7, r4
+        PLUSS r7, r5
+        SETT r0, 0x34
+        SETT r1, 0xf2
+        LAGR r7
+        SETT r1, 0x02
+        LAST r9
+        SETT r8, 0x80
+        ME r2, r8
+        BHOPP small
+        TUR done
+        STOPP
+    small:
+        LES r2
+        LIK r2, r9
+        BHOPP spin
+        RETUR
+    spin:
+        HOPP spin
+    done:
+        RETUR
+    ";
+
+    #[test]
+    fn lanes_match_the_emulator() {
+        let fragment = assemble_fragment(PROGRAM).unwrap_or_else(|_| panic!("bad test source"));
+        let program = link(&[Module {
+            name: String::from("test"),
+            fragment,
+        }])
+        .unwrap_or_else(|e| panic!("{}", e.join("\n")));
+        let memory = memory_image(&program);
+        let inputs: Vec<Vec<u8>> = (0..=0xff)
+            .flat_map(|a| [vec![a], vec![a, 0xff], vec![a, 0]])
+            .chain([Vec::new()])
+            .collect();
+        let mut visited = [0; MEMORY_SIZE / 64];
+        let outcomes = run(&memory, &inputs, 500, &mut visited);
+        assert_eq!(outcomes.len(), inputs.len());
+        let mut halts = Vec::new();
+        for (outcome, input) in outcomes.iter().zip(&inputs) {
+            let result = State::new(memory, input.clone()).run_result(500);
+            let pc = result.state.as_ref().map_or(0, |s| s.pc());
+            // The batch reports the address of the STOPP itself, not the pc after it.
+            let pc = if result.halt == HaltReason::Stopp {
+                pc - 2
+            } else {
+                pc
+            };
+            assert_eq!(
+                (outcome.halt, outcome.pc, outcome.cycles),
+                (result.halt, pc, result.cycles),
+                "input {}",
+                hex::encode(input)
+            );
+            if !halts.contains(&outcome.halt) {
+                halts.push(outcome.halt);
+            }
+        }
+        assert_eq!(halts.len(), 5);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::costs::CostModel;
+use crate::emulator::State;
+use crate::parser::{Instruction, MEMORY_SIZE};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const MAX_STEPS: usize = 10_000_000;
+const FORKS: u32 = 10_000;
+
+pub struct Report {
+    pub iterations: usize,
+    pub steps: usize,
+    pub cycles: usize,
+    pub elapsed: Duration,
+    pub distinct_pcs: usize,
+    pub loads: usize,
+    pub stores: usize,
+    pub branches: usize,
+    pub fork: Duration,
+}
+
+impl Report {
+    pub fn instructions_per_second(&self) -> f64 {
+        (self.steps * self.iterations) as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+pub fn bench(
+    memory: [u8; MEMORY_SIZE],
+    stdin: &[u8],
+    iterations: usize,
+    costs: Option<Arc<CostModel>>,
+) -> Report {
+    let initial = State::new(memory, stdin.to_vec()).with_costs(costs);
+    let mut state = initial.clone();
+    let mut pcs = HashSet::new();
+    let (mut steps, mut loads, mut stores, mut branches) = (0, 0, 0, 0);
+    while state.halted().is_none() && state.can_execute() && steps < MAX_STEPS {
+        pcs.insert(state.pc());
+        match state.next_instruction() {
+            Instruction::Last(_) => loads += 1,
+            Instruction::Lagr(_) => stores += 1,
+            i if i.is_branch() => branches += 1,
+            _ => (),
+        }
+        if state.execute().is_err() {
+            break;
+        }
+        steps += 1;
+    }
+    let cycles = state.cycles();
+
+    let mut run = initial.clone();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        run.reset(&initial, stdin);
+        run.run(cycles);
+    }
+    let elapsed = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..FORKS {
+        let fork = state.clone();
+        drop(fork);
+    }
+    let fork = start.elapsed() / FORKS;
+
+    Report {
+        iterations,
+        steps,
+        cycles,
+        elapsed,
+        distinct_pcs: pcs.len(),
+        loads,
+        stores,
+        branches,
+        fork,
+    }
+}
@@ -0,0 +1,63 @@
+use crate::emulator::State;
+use crate::loader;
+use crate::parser::{Instruction, MEMORY_SIZE};
+use crate::source::{parse_number, parse_register};
+
+pub fn parse_registers(spec: &str) -> Result<Vec<(usize, u8)>, String> {
+    spec.split(',')
+        .filter(|s| !s.is_empty())
+        .map(|assignment| {
+            let (register, value) = assignment
+                .split_once('=')
+                .ok_or_else(|| format!("expected rN=VALUE, got {}", assignment))?;
+            let register = parse_register(register.trim())
+                .filter(|&r| r < 16)
+                .ok_or_else(|| format!("{} is not a register", register))?;
+            let value = parse_number(value)
+                .filter(|&v| v <= 0xff)
+                .ok_or_else(|| format!("{} is not a byte", value))?;
+            Ok((register, value as u8))
+        })
+        .collect()
+}
+
+pub fn parse_memory(spec: &str) -> Result<Vec<(usize, Vec<u8>)>, String> {
+    spec.split(',')
+        .filter(|s| !s.is_empty())
+        .map(|assignment| {
+            let (address, value) = assignment
+                .split_once('=')
+                .ok_or_else(|| format!("expected ADDRESS=VALUE, got {}", assignment))?;
+            let address = parse_number(address)
+                .filter(|&a| (a as usize) < MEMORY_SIZE)
+                .ok_or_else(|| format!("{} is not a memory address", address))?;
+            let bytes = if let Some(path) = value.strip_prefix('@') {
+                loader::input(path).map_err(|e| format!("{}: {}", path, e))?
+            } else if value.starts_with("hex:") {
+                loader::input(value).map_err(|e| format!("{}: {}", value, e))?
+            } else {
+                let byte = parse_number(value)
+                    .filter(|&v| v <= 0xff)
+                    .ok_or_else(|| format!("{} is not a byte, @FILE or hex:...", value))?;
+                vec![byte as u8]
+            };
+            Ok((address as usize, bytes))
+        })
+        .collect()
+}
+
+pub fn call(state: &mut State, max_cycles: usize) -> bool {
+    let mut depth = 0;
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        match state.next_instruction() {
+            Instruction::Tur(_) => depth += 1,
+            Instruction::Retur if depth == 0 => return true,
+            Instruction::Retur => depth -= 1,
+            _ => (),
+        }
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+    }
+    false
+}
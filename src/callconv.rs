@@ -0,0 +1,160 @@
+use crate::emulator::State;
+use crate::functions::{find_functions, function_body};
+use crate::liveness;
+use crate::parser::{decode_at, Instruction, Operand};
+use std::collections::BTreeSet;
+
+const MAX_STEPS: usize = 1_000_000;
+
+pub struct Issue {
+    pub address: usize,
+    pub message: String,
+}
+
+fn hex_list(addresses: &[usize]) -> String {
+    addresses
+        .iter()
+        .map(|a| format!("{:#06x}", a))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn check_static(memory: &[u8], documented: &[usize]) -> Vec<Issue> {
+    let functions = find_functions(memory);
+    let summaries = liveness::analyze(memory);
+    let mut issues = Vec::new();
+
+    for &function in &functions {
+        let body = function_body(memory, function);
+        let mut returns = false;
+        let mut halts = Vec::new();
+        let mut clobbers = Vec::new();
+        for &pc in &body {
+            let instruction = decode_at(memory, pc);
+            match instruction {
+                Instruction::Retur => returns = true,
+                Instruction::Stopp => halts.push(pc),
+                _ => (),
+            }
+            if instruction
+                .writes()
+                .any(|w| matches!(w, Operand::Reg(r) if r <= 1))
+            {
+                clobbers.push(pc);
+            }
+        }
+
+        if !returns {
+            issues.push(Issue {
+                address: function,
+                message: String::from("never reaches RETUR"),
+            });
+        }
+        if !halts.is_empty() {
+            issues.push(Issue {
+                address: function,
+                message: format!("may halt without returning at {}", hex_list(&halts)),
+            });
+        }
+        for &other in functions.range(function + 1..) {
+            if body.contains(&other) {
+                issues.push(Issue {
+                    address: function,
+                    message: format!("runs into function {:#06x} without a TUR", other),
+                });
+            }
+        }
+        if !clobbers.is_empty() && !documented.contains(&function) {
+            let readers: Vec<usize> = summaries
+                .values()
+                .flat_map(|s| &s.calls)
+                .filter(|&&(_, callee, after)| callee == function && after & 0b11 != 0)
+                .map(|&(site, _, _)| site)
+                .collect();
+            let mut message = format!("clobbers r0/r1 at {}", hex_list(&clobbers));
+            if !readers.is_empty() {
+                message += &format!(", which are read after the call at {}", hex_list(&readers));
+            }
+            issues.push(Issue {
+                address: function,
+                message,
+            });
+        }
+    }
+    issues
+}
+
+pub fn check_dynamic(state: &mut State, documented: &[usize]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut frames: Vec<(usize, usize, u8, u8)> = Vec::new();
+    let mut reported = BTreeSet::new();
+
+    let mut steps = 0;
+    while state.halted().is_none() {
+        if steps == MAX_STEPS {
+            issues.push(Issue {
+                address: state.pc(),
+                message: format!("stopped after {} steps", MAX_STEPS),
+            });
+            break;
+        }
+        steps += 1;
+
+        let pc = state.pc();
+        let mut returned = None;
+        match state.next_instruction() {
+            Instruction::Tur(target) => frames.push((
+                usize::from(target),
+                pc,
+                state.register(0),
+                state.register(1),
+            )),
+            Instruction::Retur => match frames.pop() {
+                Some(frame) => returned = Some(frame),
+                None => {
+                    issues.push(Issue {
+                        address: pc,
+                        message: String::from("RETUR with an empty return stack"),
+                    });
+                    break;
+                }
+            },
+            Instruction::Les(_) if state.input_exhausted() => {
+                issues.push(Issue {
+                    address: pc,
+                    message: String::from("LES with no input left"),
+                });
+                break;
+            }
+            _ => (),
+        }
+        if state.execute().is_err() {
+            break;
+        }
+
+        if let Some((function, call, r0, r1)) = returned {
+            let changed = (r0, r1) != (state.register(0), state.register(1));
+            if changed && !documented.contains(&function) && reported.insert(function) {
+                issues.push(Issue {
+                    address: call,
+                    message: format!(
+                        "call to {:#06x} changed r1:r0 from {:02x}{:02x} to {:02x}{:02x}",
+                        function,
+                        r1,
+                        r0,
+                        state.register(1),
+                        state.register(0)
+                    ),
+                });
+            }
+        }
+    }
+
+    for (function, call, _, _) in frames {
+        issues.push(Issue {
+            address: call,
+            message: format!("call to {:#06x} never returned", function),
+        });
+    }
+    issues
+}
@@ -0,0 +1,304 @@
+use crate::functions::{find_functions, function_body};
+use crate::parser::{decode_at, Instruction};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+pub struct CallGraph {
+    pub calls: BTreeMap<usize, BTreeSet<usize>>,
+    pub reachable: BTreeSet<usize>,
+}
+
+fn callees(memory: &[u8], entry: usize) -> BTreeSet<usize> {
+    function_body(memory, entry)
+        .into_iter()
+        .filter_map(|pc| match decode_at(memory, pc) {
+            Instruction::Tur(target) => Some(usize::from(target)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn name(entry: usize, names: &BTreeMap<usize, String>) -> String {
+    match names.get(&entry) {
+        Some(name) => name.clone(),
+        None if entry == 0 => String::from("entry"),
+        None => format!("sub_{:04x}", entry),
+    }
+}
+
+impl CallGraph {
+    pub fn build(memory: &[u8], size: usize) -> CallGraph {
+        let mut queue: Vec<usize> = (0..size)
+            .step_by(2)
+            .filter_map(|pc| match decode_at(memory, pc) {
+                Instruction::Tur(target) => Some(usize::from(target)),
+                _ => None,
+            })
+            .chain(find_functions(memory))
+            .chain([0])
+            .collect();
+        let mut calls = BTreeMap::new();
+        while let Some(entry) = queue.pop() {
+            if calls.contains_key(&entry) {
+                continue;
+            }
+            let called = if entry % 2 == 0 {
+                callees(memory, entry)
+            } else {
+                BTreeSet::new()
+            };
+            queue.extend(&called);
+            calls.insert(entry, called);
+        }
+        let mut reachable = BTreeSet::new();
+        let mut queue = vec![0];
+        while let Some(entry) = queue.pop() {
+            if reachable.insert(entry) {
+                queue.extend(&calls[&entry]);
+            }
+        }
+        CallGraph { calls, reachable }
+    }
+
+    pub fn unreachable(&self) -> Vec<usize> {
+        self.calls
+            .keys()
+            .filter(|f| !self.reachable.contains(f))
+            .copied()
+            .collect()
+    }
+
+    pub fn misaligned(&self) -> Vec<usize> {
+        self.calls.keys().filter(|f| *f % 2 != 0).copied().collect()
+    }
+
+    fn path(&self, from: usize, to: usize, within: &BTreeSet<usize>) -> Option<Vec<usize>> {
+        let mut previous = BTreeMap::new();
+        let mut queue = std::collections::VecDeque::from([from]);
+        while let Some(f) = queue.pop_front() {
+            for &g in &self.calls[&f] {
+                if !within.contains(&g) || previous.contains_key(&g) {
+                    continue;
+                }
+                previous.insert(g, f);
+                if g == to {
+                    let mut path = vec![to];
+                    while path.len() == 1 || *path.last()? != from {
+                        path.push(previous[path.last()?]);
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                queue.push_back(g);
+            }
+        }
+        None
+    }
+
+    fn reached_from(&self, entry: usize) -> BTreeSet<usize> {
+        let mut seen = BTreeSet::new();
+        let mut queue = vec![entry];
+        while let Some(f) = queue.pop() {
+            for &g in &self.calls[&f] {
+                if seen.insert(g) {
+                    queue.push(g);
+                }
+            }
+        }
+        seen
+    }
+
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let reach: BTreeMap<usize, BTreeSet<usize>> = self
+            .calls
+            .keys()
+            .map(|&f| (f, self.reached_from(f)))
+            .collect();
+        let mut done: BTreeSet<usize> = BTreeSet::new();
+        let mut cycles = Vec::new();
+        for (&f, reached) in &reach {
+            if done.contains(&f) || !reached.contains(&f) {
+                continue;
+            }
+            let component: BTreeSet<usize> = reached
+                .iter()
+                .filter(|g| reach[g].contains(&f))
+                .copied()
+                .collect();
+            done.extend(&component);
+            cycles.extend(self.path(f, f, &component));
+        }
+        cycles
+    }
+
+    fn draw(
+        &self,
+        entry: usize,
+        names: &BTreeMap<usize, String>,
+        path: &mut Vec<usize>,
+        expanded: &mut BTreeSet<usize>,
+        prefix: &str,
+        text: &mut String,
+    ) {
+        let called: Vec<usize> = self.calls[&entry].iter().copied().collect();
+        for (n, &callee) in called.iter().enumerate() {
+            let (branch, indent) = if n + 1 == called.len() {
+                ("└─ ", "   ")
+            } else {
+                ("├─ ", "│  ")
+            };
+            let note = if path.contains(&callee) {
+                " (recursive)"
+            } else if expanded.contains(&callee) && !self.calls[&callee].is_empty() {
+                " (see above)"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                text,
+                "{}{}{} {:#06x}{}",
+                prefix,
+                branch,
+                name(callee, names),
+                callee,
+                note
+            );
+            if note.is_empty() {
+                expanded.insert(callee);
+                path.push(callee);
+                self.draw(
+                    callee,
+                    names,
+                    path,
+                    expanded,
+                    &format!("{}{}", prefix, indent),
+                    text,
+                );
+                path.pop();
+            }
+        }
+    }
+
+    fn tree(
+        &self,
+        entry: usize,
+        names: &BTreeMap<usize, String>,
+        expanded: &mut BTreeSet<usize>,
+        text: &mut String,
+    ) {
+        let _ = writeln!(text, "{} {:#06x}", name(entry, names), entry);
+        expanded.insert(entry);
+        self.draw(entry, names, &mut vec![entry], expanded, "", text);
+    }
+
+    pub fn text(&self, names: &BTreeMap<usize, String>) -> String {
+        let mut text = String::new();
+        let mut expanded = BTreeSet::new();
+        self.tree(0, names, &mut expanded, &mut text);
+        let unreachable = self.unreachable();
+        if unreachable.is_empty() {
+            text += "\nUnreachable functions: none\n";
+        } else {
+            let _ = writeln!(text, "\nUnreachable functions: {}", unreachable.len());
+            let called: BTreeSet<usize> = unreachable
+                .iter()
+                .flat_map(|f| &self.calls[f])
+                .copied()
+                .collect();
+            let roots = unreachable.iter().filter(|f| !called.contains(f));
+            for &entry in roots.chain(&unreachable) {
+                if !expanded.contains(&entry) {
+                    self.tree(entry, names, &mut expanded, &mut text);
+                }
+            }
+        }
+        let misaligned = self.misaligned();
+        if !misaligned.is_empty() {
+            let targets: Vec<String> = misaligned.iter().map(|f| format!("{:#06x}", f)).collect();
+            let _ = writeln!(
+                text,
+                "\nCalls to odd addresses, not followed: {}",
+                targets.join(", ")
+            );
+        }
+        let cycles = self.cycles();
+        if cycles.is_empty() {
+            text += "\nRecursion cycles: none\n";
+        } else {
+            let _ = writeln!(text, "\nRecursion cycles: {}", cycles.len());
+            for cycle in cycles {
+                let path: Vec<String> = cycle.iter().map(|&f| name(f, names)).collect();
+                let _ = writeln!(text, "  {}", path.join(" -> "));
+            }
+        }
+        text
+    }
+
+    pub fn dot(&self, names: &BTreeMap<usize, String>) -> String {
+        let mut out = String::from("digraph calls {\n    node [shape=box, fontname=monospace];\n");
+        for (&entry, called) in &self.calls {
+            let style = if entry == 0 {
+                ", style=bold"
+            } else if entry % 2 != 0 {
+                ", color=red"
+            } else if !self.reachable.contains(&entry) {
+                ", style=dashed, color=gray"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "    f{:x} [label=\"{}\\n{:#06x}\"{}];",
+                entry,
+                name(entry, names),
+                entry,
+                style
+            );
+            for &callee in called {
+                let attributes = if self.reached_from(callee).contains(&entry) {
+                    " [color=red]"
+                } else {
+                    ""
+                };
+                let _ = writeln!(out, "    f{:x} -> f{:x}{};", entry, callee, attributes);
+            }
+        }
+        out + "}\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{encode_instruction, memory_image, Operand};
+
+    fn graph(instructions: &[Instruction]) -> CallGraph {
+        let program: Vec<u8> = instructions
+            .iter()
+            .flat_map(|i| encode_instruction(i).to_le_bytes())
+            .collect();
+        CallGraph::build(&memory_image(&program), program.len())
+    }
+
+    #[test]
+    fn finds_recursion_and_unreachable_functions() {
+        let graph = graph(&[
+            Instruction::Tur(Operand::Addr(6)),
+            Instruction::Stopp,
+            Instruction::Tur(Operand::Addr(10)),
+            Instruction::Tur(Operand::Addr(6)),
+            Instruction::Retur,
+            Instruction::Retur,
+        ]);
+        assert_eq!(graph.cycles(), vec![vec![6, 6]]);
+        assert_eq!(graph.unreachable(), vec![10]);
+    }
+
+    #[test]
+    fn odd_call_target() {
+        let graph = graph(&[Instruction::Tur(Operand::Addr(7)), Instruction::Stopp]);
+        assert_eq!(graph.misaligned(), vec![7]);
+        assert!(graph.text(&BTreeMap::new()).contains("0x0007"));
+        assert!(graph.dot(&BTreeMap::new()).contains("f0 -> f7"));
+    }
+}
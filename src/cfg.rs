@@ -0,0 +1,364 @@
+use crate::emulator::State;
+use crate::parser::{decode_at, Instruction, MEMORY_SIZE};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EdgeKind {
+    Fallthrough,
+    Taken,
+    Call,
+}
+
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<(usize, EdgeKind)>,
+}
+
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, Block>,
+    pub roots: BTreeSet<usize>,
+}
+
+pub struct Exit {
+    pub from: usize,
+    pub to: usize,
+    pub condition: String,
+}
+
+pub struct Loop {
+    pub header: usize,
+    pub back_edges: Vec<usize>,
+    pub body: BTreeSet<usize>,
+    pub exits: Vec<Exit>,
+}
+
+pub struct LoopProfile {
+    pub entries: usize,
+    pub iterations: usize,
+    pub cycles: usize,
+    pub trips: Vec<usize>,
+}
+
+fn in_memory(pc: usize) -> bool {
+    pc + 1 < MEMORY_SIZE
+}
+
+impl Cfg {
+    pub fn build(memory: &[u8]) -> Cfg {
+        let mut reachable = BTreeSet::new();
+        let mut leaders = BTreeSet::new();
+        let mut roots = BTreeSet::new();
+        leaders.insert(0);
+        roots.insert(0);
+
+        let mut queue = vec![0];
+        while let Some(pc) = queue.pop() {
+            if !in_memory(pc) || !reachable.insert(pc) {
+                continue;
+            }
+            let instruction = decode_at(memory, pc);
+            match instruction {
+                Instruction::Hopp(a) => {
+                    leaders.insert(usize::from(a));
+                }
+                Instruction::BHopp(a) => {
+                    leaders.insert(usize::from(a));
+                    leaders.insert(pc + 2);
+                }
+                Instruction::Tur(a) => {
+                    leaders.insert(usize::from(a));
+                    roots.insert(usize::from(a));
+                    leaders.insert(pc + 2);
+                }
+                _ => (),
+            }
+            queue.extend(instruction.successors(pc));
+        }
+
+        let mut blocks = BTreeMap::new();
+        for &start in leaders.iter().filter(|l| reachable.contains(l)) {
+            let mut pc = start;
+            let successors = loop {
+                let next = pc + 2;
+                match decode_at(memory, pc) {
+                    Instruction::Hopp(a) => break vec![(usize::from(a), EdgeKind::Taken)],
+                    Instruction::BHopp(a) => {
+                        break vec![
+                            (next, EdgeKind::Fallthrough),
+                            (usize::from(a), EdgeKind::Taken),
+                        ]
+                    }
+                    Instruction::Tur(a) => {
+                        break vec![
+                            (usize::from(a), EdgeKind::Call),
+                            (next, EdgeKind::Fallthrough),
+                        ]
+                    }
+                    Instruction::Retur | Instruction::Stopp => break Vec::new(),
+                    _ => (),
+                }
+                if !reachable.contains(&next) {
+                    break Vec::new();
+                }
+                if leaders.contains(&next) {
+                    break vec![(next, EdgeKind::Fallthrough)];
+                }
+                pc = next;
+            };
+            let successors = successors
+                .into_iter()
+                .filter(|(to, _)| reachable.contains(to))
+                .collect();
+            blocks.insert(
+                start,
+                Block {
+                    start,
+                    end: pc + 2,
+                    successors,
+                },
+            );
+        }
+
+        Cfg { blocks, roots }
+    }
+
+    pub fn dot(&self, memory: &[u8], names: &BTreeMap<usize, String>) -> String {
+        let mut out = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        for block in self.blocks.values() {
+            let mut label = match names.get(&block.start) {
+                Some(name) => format!("{}:\\l", name),
+                None => String::new(),
+            };
+            for pc in (block.start..block.end).step_by(2) {
+                let instruction = decode_at(memory, pc).to_string().replace('\t', " ");
+                label += &format!("{:#06x}  {}\\l", pc, instruction);
+            }
+            let style = if self.roots.contains(&block.start) {
+                ", style=bold"
+            } else {
+                ""
+            };
+            out += &format!("    b{:x} [label=\"{}\"{}];\n", block.start, label, style);
+            for (to, kind) in &block.successors {
+                let attributes = match kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Taken => " [color=darkgreen, label=\"taken\"]",
+                    EdgeKind::Call => " [style=dashed, color=blue, label=\"call\"]",
+                };
+                out += &format!("    b{:x} -> b{:x}{};\n", block.start, to, attributes);
+            }
+        }
+        out + "}\n"
+    }
+
+    pub fn block_of(&self, pc: usize) -> Option<usize> {
+        self.blocks
+            .range(..=pc)
+            .next_back()
+            .filter(|(_, block)| pc < block.end && (pc - block.start).is_multiple_of(2))
+            .map(|(start, _)| *start)
+    }
+
+    fn local_successors(&self, block: usize) -> impl Iterator<Item = usize> + '_ {
+        self.blocks[&block]
+            .successors
+            .iter()
+            .filter(|(_, kind)| *kind != EdgeKind::Call)
+            .map(|(to, _)| *to)
+    }
+
+    pub fn predecessors(&self) -> BTreeMap<usize, Vec<usize>> {
+        let mut predecessors: BTreeMap<usize, Vec<usize>> =
+            self.blocks.keys().map(|b| (*b, Vec::new())).collect();
+        for &block in self.blocks.keys() {
+            for to in self.local_successors(block) {
+                predecessors.entry(to).or_default().push(block);
+            }
+        }
+        predecessors
+    }
+
+    pub fn dominators(&self) -> BTreeMap<usize, BTreeSet<usize>> {
+        let all: BTreeSet<usize> = self.blocks.keys().copied().collect();
+        let predecessors = self.predecessors();
+        let mut dominators: BTreeMap<usize, BTreeSet<usize>> = self
+            .blocks
+            .keys()
+            .map(|&b| {
+                if self.roots.contains(&b) {
+                    (b, std::iter::once(b).collect())
+                } else {
+                    (b, all.clone())
+                }
+            })
+            .collect();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in self.blocks.keys() {
+                if self.roots.contains(&block) {
+                    continue;
+                }
+                let mut new: Option<BTreeSet<usize>> = None;
+                for p in &predecessors[&block] {
+                    new = Some(match new {
+                        None => dominators[p].clone(),
+                        Some(set) => set.intersection(&dominators[p]).copied().collect(),
+                    });
+                }
+                let mut new = new.unwrap_or_default();
+                new.insert(block);
+                if new != dominators[&block] {
+                    dominators.insert(block, new);
+                    changed = true;
+                }
+            }
+        }
+        dominators
+    }
+
+    pub fn immediate_dominators(&self) -> BTreeMap<usize, usize> {
+        let dominators = self.dominators();
+        let mut idoms = BTreeMap::new();
+        for (&block, doms) in &dominators {
+            let idom = doms.iter().filter(|&&d| d != block).find(|&&d| {
+                doms.iter()
+                    .all(|&o| o == block || dominators[&d].contains(&o))
+            });
+            if let Some(&idom) = idom {
+                idoms.insert(block, idom);
+            }
+        }
+        idoms
+    }
+
+    fn exit_condition(&self, memory: &[u8], from: usize, kind: EdgeKind) -> String {
+        let block = &self.blocks[&from];
+        let compare = (block.start..block.end)
+            .step_by(2)
+            .rev()
+            .map(|pc| decode_at(memory, pc))
+            .find(|i| i.modifies_flag())
+            .map(|i| i.to_string().replace('\t', " "));
+        match (kind, compare) {
+            (EdgeKind::Taken, Some(compare)) if self.ends_with_branch(memory, from) => {
+                format!("when {}", compare)
+            }
+            (EdgeKind::Fallthrough, Some(compare)) if self.ends_with_branch(memory, from) => {
+                format!("unless {}", compare)
+            }
+            _ => String::from("always"),
+        }
+    }
+
+    fn ends_with_branch(&self, memory: &[u8], block: usize) -> bool {
+        matches!(
+            decode_at(memory, self.blocks[&block].end - 2),
+            Instruction::BHopp(_)
+        )
+    }
+
+    pub fn loops(&self, memory: &[u8]) -> Vec<Loop> {
+        let dominators = self.dominators();
+        let predecessors = self.predecessors();
+        let mut loops: BTreeMap<usize, Loop> = BTreeMap::new();
+
+        for &tail in self.blocks.keys() {
+            for header in self.local_successors(tail) {
+                if !dominators[&tail].contains(&header) {
+                    continue;
+                }
+                let l = loops.entry(header).or_insert_with(|| Loop {
+                    header,
+                    back_edges: Vec::new(),
+                    body: std::iter::once(header).collect(),
+                    exits: Vec::new(),
+                });
+                l.back_edges.push(tail);
+                let mut queue = vec![tail];
+                while let Some(block) = queue.pop() {
+                    if l.body.insert(block) {
+                        queue.extend(&predecessors[&block]);
+                    }
+                }
+            }
+        }
+
+        for l in loops.values_mut() {
+            for &block in &l.body {
+                for &(to, kind) in &self.blocks[&block].successors {
+                    if kind != EdgeKind::Call && !l.body.contains(&to) {
+                        l.exits.push(Exit {
+                            from: block,
+                            to,
+                            condition: self.exit_condition(memory, block, kind),
+                        });
+                    }
+                }
+            }
+        }
+        loops.into_values().collect()
+    }
+}
+
+pub fn profile_loops(
+    cfg: &Cfg,
+    loops: &[Loop],
+    state: &mut State,
+    max_steps: usize,
+) -> Vec<LoopProfile> {
+    let mut profiles: Vec<LoopProfile> = loops
+        .iter()
+        .map(|_| LoopProfile {
+            entries: 0,
+            iterations: 0,
+            cycles: 0,
+            trips: Vec::new(),
+        })
+        .collect();
+
+    let mut previous: Option<usize> = None;
+    let mut steps = 0;
+    while state.halted().is_none() && steps < max_steps {
+        if !state.can_execute() {
+            break;
+        }
+        let pc = state.pc();
+        let block = cfg.block_of(pc);
+        if block == Some(pc) {
+            for (l, profile) in loops.iter().zip(profiles.iter_mut()) {
+                if l.header != pc {
+                    continue;
+                }
+                match previous {
+                    Some(p) if l.back_edges.contains(&p) => {
+                        profile.iterations += 1;
+                        if let Some(trip) = profile.trips.last_mut() {
+                            *trip += 1;
+                        }
+                    }
+                    _ => {
+                        profile.entries += 1;
+                        profile.trips.push(0);
+                    }
+                }
+            }
+        }
+        previous = block.or(previous);
+        let before = state.cycles();
+        if state.execute().is_err() {
+            break;
+        }
+        steps += 1;
+        if let Some(block) = block {
+            for (l, profile) in loops.iter().zip(profiles.iter_mut()) {
+                if l.body.contains(&block) {
+                    profile.cycles += state.cycles() - before;
+                }
+            }
+        }
+    }
+    profiles
+}
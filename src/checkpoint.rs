@@ -0,0 +1,473 @@
+use crate::parser::MEMORY_SIZE;
+use crate::progress::Progress;
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const HEADER: &str = "s8checkpoint 1";
+
+pub type Visited = [u64; MEMORY_SIZE / 64];
+
+pub fn visit(visited: &mut Visited, pc: usize) {
+    visited[pc / 64] |= 1 << (pc % 64);
+}
+
+#[derive(Default)]
+pub struct Saved {
+    pub best: Option<(usize, String)>,
+    pub values: Vec<(String, String)>,
+}
+
+impl Saved {
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn values<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.values
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+pub struct Checkpoint {
+    path: Option<String>,
+    every: usize,
+    search: String,
+    explored: Mutex<Vec<Range<usize>>>,
+    coverage: Vec<AtomicU64>,
+    shard: Option<(usize, usize)>,
+    total: AtomicUsize,
+    pub saved: Saved,
+}
+
+pub fn parse_shard(spec: &str) -> Result<(usize, usize), String> {
+    let (i, n) = spec.split_once('/').ok_or("expected i/N")?;
+    match (i.parse(), n.parse()) {
+        (Ok(i), Ok(n)) if 1 <= i && i <= n => Ok((i, n)),
+        _ => Err(format!("bad shard {}, expected i/N with 1 <= i <= N", spec)),
+    }
+}
+
+fn parse_range(text: &str) -> Option<Range<usize>> {
+    let (start, end) = text.split_once('-')?;
+    Some(start.parse().ok()?..end.parse().ok()?)
+}
+
+fn parse(text: &str) -> Option<(String, Vec<Range<usize>>, Saved)> {
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return None;
+    }
+    let mut search = String::new();
+    let mut explored = Vec::new();
+    let mut saved = Saved::default();
+    for line in lines {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "search" => search = value.to_string(),
+            "explored" => explored.push(parse_range(value)?),
+            "best" => {
+                let (score, description) = value.split_once(' ')?;
+                saved.best = Some((score.parse().ok()?, description.to_string()));
+            }
+            _ => saved.values.push((key.to_string(), value.to_string())),
+        }
+    }
+    Some((search, explored, saved))
+}
+
+fn coalesce(ranges: &mut Vec<Range<usize>>) {
+    ranges.retain(|r| !r.is_empty());
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges.drain(..) {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    *ranges = merged;
+}
+
+impl Checkpoint {
+    fn new(path: Option<String>, every: usize, search: String) -> Checkpoint {
+        Checkpoint {
+            path,
+            every,
+            search,
+            explored: Mutex::new(Vec::new()),
+            coverage: (0..MEMORY_SIZE / 64).map(|_| AtomicU64::new(0)).collect(),
+            shard: None,
+            total: AtomicUsize::new(0),
+            saved: Saved::default(),
+        }
+    }
+
+    pub fn disabled() -> Checkpoint {
+        Checkpoint::new(None, usize::MAX, String::new())
+    }
+
+    pub fn open(path: &str, every: usize, search: String) -> Checkpoint {
+        let mut checkpoint = Checkpoint::new(Some(path.to_string()), every.max(1), search);
+        if let Ok(text) = fs::read_to_string(path) {
+            match parse(&text) {
+                Some((search, mut explored, saved)) if search == checkpoint.search => {
+                    coalesce(&mut explored);
+                    *checkpoint.explored.get_mut().expect("checkpoint lock") = explored;
+                    if let Some(coverage) =
+                        saved.value("coverage").and_then(|c| hex::decode(c).ok())
+                    {
+                        for (i, byte) in coverage.iter().enumerate().take(MEMORY_SIZE / 8) {
+                            checkpoint.coverage[i / 8]
+                                .fetch_or(u64::from(*byte) << (i % 8 * 8), Ordering::Relaxed);
+                        }
+                    }
+                    checkpoint.saved = saved;
+                    eprintln!(
+                        "Resuming from {} after {} tries",
+                        path,
+                        checkpoint.explored()
+                    );
+                }
+                _ => eprintln!("Ignoring {}, it belongs to a different search", path),
+            }
+        }
+        checkpoint
+    }
+
+    pub fn with_shard(mut self, shard: Option<(usize, usize)>) -> Checkpoint {
+        self.shard = shard;
+        self
+    }
+
+    fn slice(&self, total: usize) -> Range<usize> {
+        match self.shard {
+            Some((i, n)) => {
+                let bound = |i: usize| (total as u128 * i as u128 / n as u128) as usize;
+                bound(i - 1)..bound(i)
+            }
+            None => 0..total,
+        }
+    }
+
+    pub fn progress(&self, total: usize) -> Progress {
+        self.total.store(total, Ordering::Relaxed);
+        let slice = self.slice(total);
+        let explored: usize = self
+            .explored
+            .lock()
+            .expect("checkpoint lock")
+            .iter()
+            .map(|r| {
+                r.end
+                    .min(slice.end)
+                    .saturating_sub(r.start.max(slice.start))
+            })
+            .sum();
+        let progress = Progress::new(slice.len(), explored);
+        if let Some((score, description)) = &self.saved.best {
+            progress.offer(*score, || description.clone());
+        }
+        progress
+    }
+
+    pub fn every(&self) -> usize {
+        self.every
+    }
+
+    pub fn explored(&self) -> usize {
+        let explored = self.explored.lock().expect("checkpoint lock");
+        explored.iter().map(|r| r.len()).sum()
+    }
+
+    pub fn unexplored(&self, total: usize) -> Vec<Range<usize>> {
+        let explored = self.explored.lock().expect("checkpoint lock");
+        let slice = self.slice(total);
+        let mut gaps = Vec::new();
+        let mut start = slice.start;
+        for range in explored.iter() {
+            if range.start > start {
+                gaps.push(start..range.start.min(slice.end));
+            }
+            start = start.max(range.end);
+        }
+        gaps.push(start..slice.end);
+        gaps.retain(|r| !r.is_empty());
+        gaps
+    }
+
+    pub fn explore(&self, range: Range<usize>) {
+        let mut explored = self.explored.lock().expect("checkpoint lock");
+        explored.push(range);
+        coalesce(&mut explored);
+    }
+
+    pub fn cover(&self, visited: &Visited) {
+        for (word, &bits) in self.coverage.iter().zip(visited) {
+            if word.load(Ordering::Relaxed) & bits != bits {
+                word.fetch_or(bits, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn coverage(&self) -> Vec<bool> {
+        (0..MEMORY_SIZE)
+            .map(|pc| self.coverage[pc / 64].load(Ordering::Relaxed) & 1 << (pc % 64) != 0)
+            .collect()
+    }
+
+    pub fn save(&self, progress: &Progress, values: &[(&str, String)]) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let coverage: Vec<u8> = self
+            .coverage
+            .iter()
+            .flat_map(|word| word.load(Ordering::Relaxed).to_le_bytes())
+            .collect();
+        let mut values: Vec<(String, String)> = values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        values.push((
+            String::from("total"),
+            self.total.load(Ordering::Relaxed).to_string(),
+        ));
+        if let Some((i, n)) = self.shard {
+            values.push((String::from("shard"), format!("{}/{}", i, n)));
+        }
+        let text = render(
+            &self.search,
+            &self.explored.lock().expect("checkpoint lock"),
+            &progress.best(),
+            &coverage,
+            &values,
+        );
+        if let Err(e) = fs::write(path, text) {
+            eprintln!("Could not write {}: {}", path, e);
+        }
+    }
+
+    pub fn finish(&self) {
+        if let (Some(path), None) = (&self.path, self.shard) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn render(
+    search: &str,
+    explored: &[Range<usize>],
+    best: &Option<(usize, String)>,
+    coverage: &[u8],
+    values: &[(String, String)],
+) -> String {
+    let mut text = format!("{}\nsearch {}\n", HEADER, search);
+    for range in explored {
+        text += &format!("explored {}-{}\n", range.start, range.end);
+    }
+    if let Some((score, description)) = best {
+        text += &format!("best {} {}\n", score, description);
+    }
+    if coverage.iter().any(|&b| b != 0) {
+        text += &format!("coverage {}\n", hex::encode(coverage));
+    }
+    for (key, value) in values {
+        text += &format!("{} {}\n", key, value);
+    }
+    text
+}
+
+pub struct Merged {
+    pub text: String,
+    pub explored: usize,
+    pub total: usize,
+    pub found: Option<String>,
+}
+
+pub fn merge(texts: &[String]) -> Result<Merged, String> {
+    let mut search: Option<String> = None;
+    let mut explored = Vec::new();
+    let mut best: Option<(usize, String)> = None;
+    let mut coverage = vec![0u8; MEMORY_SIZE / 8];
+    let mut tallies: BTreeMap<(String, String), (usize, usize)> = BTreeMap::new();
+    let mut found: Option<(usize, String)> = None;
+    let mut total = 0;
+    let mut values: Vec<(String, String)> = Vec::new();
+    for text in texts {
+        let (other, ranges, saved) = parse(text).ok_or("not a search state file")?;
+        match &search {
+            Some(search) if *search != other => {
+                return Err(format!(
+                    "the states belong to different searches: {} and {}",
+                    search, other
+                ))
+            }
+            _ => search = Some(other),
+        }
+        explored.extend(ranges);
+        if let Some((score, description)) = saved.best {
+            if best.as_ref().is_none_or(|(s, _)| score > *s) {
+                best = Some((score, description));
+            }
+        }
+        for (key, value) in saved.values {
+            let bad = || format!("could not parse {} {}", key, value);
+            match key.as_str() {
+                "coverage" => {
+                    let bytes = hex::decode(&value).map_err(|_| bad())?;
+                    for (merged, byte) in coverage.iter_mut().zip(bytes) {
+                        *merged |= byte;
+                    }
+                }
+                "halt" | "output" => {
+                    let mut fields = value.splitn(3, ' ');
+                    let count: usize =
+                        fields.next().and_then(|c| c.parse().ok()).ok_or_else(bad)?;
+                    let first: usize =
+                        fields.next().and_then(|f| f.parse().ok()).ok_or_else(bad)?;
+                    let rest = fields.next().unwrap_or_default().to_string();
+                    let tally = tallies.entry((key.clone(), rest)).or_insert((0, first));
+                    tally.0 += count;
+                    tally.1 = tally.1.min(first);
+                }
+                "found" => {
+                    let (index, _) = value.split_once(' ').ok_or_else(bad)?;
+                    let index: usize = index.parse().map_err(|_| bad())?;
+                    if found.as_ref().is_none_or(|(i, _)| index < *i) {
+                        found = Some((index, value));
+                    }
+                }
+                "total" => total = total.max(value.parse().map_err(|_| bad())?),
+                "shard" => (),
+                "seed" if values.iter().any(|(k, _)| k == "seed") => (),
+                _ => {
+                    if !values.contains(&(key.clone(), value.clone())) {
+                        values.push((key, value));
+                    }
+                }
+            }
+        }
+    }
+    coalesce(&mut explored);
+    values.push((String::from("total"), total.to_string()));
+    for ((key, rest), (count, first)) in tallies {
+        values.push((key, format!("{} {} {}", count, first, rest)));
+    }
+    if let Some((_, found)) = &found {
+        values.push((String::from("found"), found.clone()));
+    }
+    Ok(Merged {
+        text: render(
+            &search.unwrap_or_default(),
+            &explored,
+            &best,
+            &coverage,
+            &values,
+        ),
+        explored: explored.iter().map(|r| r.len()).sum(),
+        total,
+        found: found.map(|(_, found)| found),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn render_and_parse_round_trip() {
+        let mut coverage = vec![0; MEMORY_SIZE / 8];
+        coverage[1] = 0x05;
+        let text = render(
+            "reach 0x20",
+            &[0..10, 20..30],
+            &Some((7, String::from("6162 (7 cycles)"))),
+            &coverage,
+            &values(&[("seed", "1"), ("found", "25 6162")]),
+        );
+        let (search, explored, saved) = parse(&text).unwrap_or_else(|| panic!("{}", text));
+        assert_eq!(search, "reach 0x20");
+        assert_eq!(explored, vec![0..10, 20..30]);
+        assert_eq!(saved.best, Some((7, String::from("6162 (7 cycles)"))));
+        assert_eq!(
+            saved.value("coverage"),
+            Some(hex::encode(&coverage).as_str())
+        );
+        assert_eq!(saved.value("found"), Some("25 6162"));
+        assert!(parse("s8checkpoint 0\nsearch x\n").is_none());
+    }
+
+    #[test]
+    fn shard_skips_ranges_explored_outside_it() {
+        let checkpoint = Checkpoint::disabled().with_shard(Some((2, 4)));
+        for range in [0..30, 40..45, 60..80] {
+            checkpoint.explore(range);
+        }
+        assert_eq!(checkpoint.unexplored(100), vec![30..40, 45..50]);
+        checkpoint.explore(30..40);
+        checkpoint.explore(45..50);
+        assert!(checkpoint.unexplored(100).is_empty());
+        let last = Checkpoint::disabled().with_shard(Some((3, 3)));
+        last.explore(0..70);
+        assert_eq!(last.unexplored(100), vec![70..100]);
+    }
+
+    #[test]
+    fn merge_two_shards() {
+        let first = render(
+            "reach 0x20",
+            &[0..20, 20..50],
+            &Some((10, String::from("a"))),
+            &[1],
+            &values(&[
+                ("seed", "1"),
+                ("total", "100"),
+                ("shard", "1/2"),
+                ("halt", "3 5 STOPP"),
+            ]),
+        );
+        let second = render(
+            "reach 0x20",
+            &[50..60, 60..75],
+            &Some((12, String::from("b"))),
+            &[2],
+            &values(&[
+                ("seed", "1"),
+                ("total", "100"),
+                ("shard", "2/2"),
+                ("halt", "2 60 STOPP"),
+                ("found", "70 6162"),
+            ]),
+        );
+        let merged = merge(&[first.clone(), second]).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!((merged.explored, merged.total), (75, 100));
+        assert_eq!(merged.found.as_deref(), Some("70 6162"));
+        let (search, explored, saved) =
+            parse(&merged.text).unwrap_or_else(|| panic!("{}", merged.text));
+        assert_eq!(search, "reach 0x20");
+        assert_eq!(explored, vec![0..75]);
+        assert_eq!(saved.best, Some((12, String::from("b"))));
+        assert_eq!(saved.value("halt"), Some("5 5 STOPP"));
+        assert_eq!(saved.value("seed"), Some("1"));
+        assert_eq!(saved.value("shard"), None);
+        assert!(saved.value("coverage").is_some_and(|c| c.starts_with("03")));
+        let other = render("reach 0x30", &[], &None, &[], &[]);
+        assert!(merge(&[first, other]).is_err());
+    }
+}
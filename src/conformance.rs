@@ -0,0 +1,237 @@
+use crate::emulator::State;
+use crate::parser::decode_instruction;
+use crate::source::{parse_number, parse_register};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+
+static PANIC_MESSAGE: Mutex<String> = Mutex::new(String::new());
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Register(usize),
+    Memory(usize),
+    Pc,
+    Flagg,
+    Halted,
+}
+
+pub struct Vector {
+    op: u16,
+    before: Vec<(Field, usize)>,
+    stack: Vec<usize>,
+    stdin: Vec<u8>,
+    after: Vec<(Field, usize)>,
+    stdout: Option<Vec<u8>>,
+}
+
+pub fn catch_panic<T, F: FnOnce() -> T>(f: F) -> Result<T, String> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        *PANIC_MESSAGE.lock().unwrap_or_else(|e| e.into_inner()) = info.to_string();
+    }));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(hook);
+    result.map_err(|_| {
+        let message = PANIC_MESSAGE.lock().unwrap_or_else(|e| e.into_inner());
+        message.replace('\n', " ")
+    })
+}
+
+fn field(key: &str) -> Option<Field> {
+    if let Some(address) = key.strip_prefix('[').and_then(|k| k.strip_suffix(']')) {
+        return parse_number(address)
+            .filter(|&a| a < 0x1000)
+            .map(|a| Field::Memory(a as usize));
+    }
+    match key {
+        "pc" => Some(Field::Pc),
+        "flagg" => Some(Field::Flagg),
+        "halted" => Some(Field::Halted),
+        _ => parse_register(key).filter(|&r| r < 16).map(Field::Register),
+    }
+}
+
+fn value(text: &str) -> Option<usize> {
+    match text {
+        "true" => Some(1),
+        "false" => Some(0),
+        _ => parse_number(text).map(|v| v as usize),
+    }
+}
+
+impl Vector {
+    pub fn parse(text: &str) -> Result<Vector, String> {
+        let mut vector = Vector {
+            op: 0,
+            before: Vec::new(),
+            stack: Vec::new(),
+            stdin: Vec::new(),
+            after: Vec::new(),
+            stdout: None,
+        };
+        let mut op = None;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = |what: &str| format!("line {}: bad {}", i + 1, what);
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            if key == "op" {
+                op = Some(
+                    parse_number(rest)
+                        .filter(|&op| op <= 0xffff)
+                        .ok_or_else(|| bad("opcode word"))? as u16,
+                );
+                continue;
+            }
+            let after = match key {
+                "before" => false,
+                "after" => true,
+                _ => return Err(bad("line, expected op, before or after")),
+            };
+            for assignment in rest.split_whitespace() {
+                let (name, text) = assignment.split_once('=').ok_or_else(|| bad(assignment))?;
+                match (name, after) {
+                    ("stack", false) => {
+                        vector.stack = text
+                            .split(',')
+                            .map(|p| value(p).ok_or_else(|| bad(assignment)))
+                            .collect::<Result<_, _>>()?
+                    }
+                    ("stdin", false) => {
+                        vector.stdin = hex::decode(text).map_err(|_| bad(assignment))?
+                    }
+                    ("stdout", true) => {
+                        vector.stdout = Some(hex::decode(text).map_err(|_| bad(assignment))?)
+                    }
+                    _ => {
+                        let field = field(name).ok_or_else(|| bad(assignment))?;
+                        let value = value(text).ok_or_else(|| bad(assignment))?;
+                        if after {
+                            vector.after.push((field, value));
+                        } else {
+                            vector.before.push((field, value));
+                        }
+                    }
+                }
+            }
+        }
+        vector.op = op.ok_or("missing op line")?;
+        Ok(vector)
+    }
+
+    fn initial_state(&self) -> Result<State, String> {
+        let pc = self
+            .before
+            .iter()
+            .find(|(f, _)| *f == Field::Pc)
+            .map_or(0, |&(_, pc)| pc);
+        if pc > 0xffe {
+            return Err(format!("pc {:#x} leaves no room for the opcode", pc));
+        }
+        let mut memory = [0; 4096];
+        memory[pc..pc + 2].copy_from_slice(&self.op.to_le_bytes());
+        let mut registers = [0u8; 16];
+        let mut flagg = false;
+        for &(field, value) in &self.before {
+            match field {
+                Field::Register(r) => registers[r] = value as u8,
+                Field::Memory(address) => memory[address] = value as u8,
+                Field::Flagg => flagg = value != 0,
+                Field::Pc => (),
+                Field::Halted => return Err(String::from("halted can only be expected")),
+            }
+        }
+        let stack: Vec<String> = self.stack.iter().map(|p| format!("{:x}", p)).collect();
+        let mut state = State::new(memory, self.stdin.clone());
+        state.load_checkpoint(&format!(
+            "pc={:x} flagg={} registers={} stack={}",
+            pc,
+            flagg,
+            hex::encode(registers),
+            stack.join(",")
+        ))?;
+        Ok(state)
+    }
+
+    pub fn run(&self) -> Vec<String> {
+        let mut state = match self.initial_state() {
+            Ok(state) => state,
+            Err(e) => return vec![e],
+        };
+        let state = match catch_panic(|| {
+            state.iter().next();
+            state
+        }) {
+            Ok(state) => state,
+            Err(message) => return vec![format!("the emulator {}", message)],
+        };
+        let mut failures = Vec::new();
+        for &(field, expected) in &self.after {
+            let (name, actual) = match field {
+                Field::Register(r) => (format!("r{}", r), usize::from(state.register(r))),
+                Field::Memory(a) => (format!("[{:#05x}]", a), usize::from(state.memory_at(a))),
+                Field::Pc => (String::from("pc"), state.pc()),
+                Field::Flagg => (String::from("flagg"), usize::from(state.flagg())),
+                Field::Halted => (
+                    String::from("halted"),
+                    usize::from(state.halted().is_some()),
+                ),
+            };
+            if actual != expected {
+                failures.push(format!(
+                    "{}: expected {:#x}, got {:#x}",
+                    name, expected, actual
+                ));
+            }
+        }
+        if let Some(stdout) = &self.stdout {
+            if state.stdout() != &stdout[..] {
+                failures.push(format!(
+                    "stdout: expected {}, got {}",
+                    hex::encode(stdout),
+                    hex::encode(state.stdout())
+                ));
+            }
+        }
+        failures
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{:#06x} {}", self.op, decode_instruction(self.op))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn conformance_vectors() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("conformance");
+        let mut paths: Vec<_> = fs::read_dir(&dir)
+            .unwrap_or_else(|e| panic!("{}: {}", dir.display(), e))
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|e| e == "s8t"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        let mut failures = Vec::new();
+        for path in &paths {
+            let vector = fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| Vector::parse(&text));
+            let problems = match vector {
+                Ok(vector) => vector.run(),
+                Err(e) => vec![e],
+            };
+            for problem in problems {
+                failures.push(format!("{}: {}", path.display(), problem));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
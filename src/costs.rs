@@ -0,0 +1,40 @@
+use crate::parser::Instruction;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct CostModel {
+    default: usize,
+    costs: HashMap<String, usize>,
+}
+
+impl CostModel {
+    pub fn cost(&self, instruction: &Instruction) -> usize {
+        self.costs
+            .get(instruction.mnemonic())
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+pub fn parse(text: &str) -> Result<CostModel, String> {
+    let table = match text.parse::<toml::Value>().map_err(|e| e.to_string())? {
+        toml::Value::Table(table) => table,
+        _ => return Err(String::from("expected a table of mnemonic = cycles")),
+    };
+    let mut model = CostModel {
+        default: 1,
+        costs: HashMap::new(),
+    };
+    for (key, value) in table {
+        let cycles = match value.as_integer() {
+            Some(cycles) if cycles > 0 => cycles as usize,
+            _ => return Err(format!("cost of {} must be a positive cycle count", key)),
+        };
+        if key == "default" {
+            model.default = cycles;
+        } else {
+            model.costs.insert(key.to_uppercase(), cycles);
+        }
+    }
+    Ok(model)
+}
@@ -0,0 +1,146 @@
+use crate::cfg::Cfg;
+use crate::emulator::State;
+use crate::parser::Instruction;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Default)]
+pub struct Branch {
+    pub taken: usize,
+    pub not_taken: usize,
+}
+
+#[derive(Default)]
+pub struct Coverage {
+    pub branches: BTreeMap<usize, Branch>,
+    pub edges: BTreeSet<(usize, usize)>,
+}
+
+impl Coverage {
+    pub fn record(&mut self, cfg: &Cfg, state: &mut State, max_cycles: usize) {
+        let mut block = cfg.block_of(state.pc());
+        while state.halted().is_none() && state.cycles() < max_cycles {
+            let pc = state.pc();
+            let instruction = state.next_instruction();
+            if !matches!(state.iter().next(), Some(Ok(_))) {
+                break;
+            }
+            if let Instruction::BHopp(target) = instruction {
+                let branch = self.branches.entry(pc).or_default();
+                if state.pc() == usize::from(target) && state.pc() != pc + 2 {
+                    branch.taken += 1;
+                } else {
+                    branch.not_taken += 1;
+                }
+            }
+            let next = state.pc();
+            if cfg.block_of(next) == Some(next) {
+                if let Some(from) = block {
+                    let is_edge = cfg.blocks[&from]
+                        .successors
+                        .iter()
+                        .any(|&(to, _)| to == next);
+                    if is_edge {
+                        self.edges.insert((from, next));
+                    }
+                }
+            }
+            block = cfg.block_of(next).or(block);
+        }
+    }
+}
+
+pub fn edges(cfg: &Cfg) -> BTreeSet<(usize, usize)> {
+    cfg.blocks
+        .values()
+        .flat_map(|block| {
+            block
+                .successors
+                .iter()
+                .map(move |&(to, _)| (block.start, to))
+        })
+        .collect()
+}
+
+const HEADER: &str = "s8cov 1";
+
+#[derive(Default)]
+pub struct Hits {
+    pub program: u64,
+    pub counts: BTreeMap<usize, usize>,
+}
+
+impl Hits {
+    pub fn record(&mut self, state: &mut State, max_cycles: usize) {
+        while state.halted().is_none() && state.cycles() < max_cycles {
+            let pc = state.pc();
+            if !matches!(state.iter().next(), Some(Ok(_))) {
+                break;
+            }
+            *self.counts.entry(pc).or_default() += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Hits) -> Result<(), String> {
+        if self.counts.is_empty() {
+            self.program = other.program;
+        } else if !other.counts.is_empty() && other.program != self.program {
+            return Err(format!(
+                "coverage of program {:016x} can't be merged with program {:016x}",
+                other.program, self.program
+            ));
+        }
+        for (&pc, &count) in &other.counts {
+            *self.counts.entry(pc).or_default() += count;
+        }
+        Ok(())
+    }
+
+    pub fn only_in(&self, other: &Hits) -> Vec<(usize, usize, usize)> {
+        let mut ranges: Vec<(usize, usize, usize)> = Vec::new();
+        for (&pc, &count) in &self.counts {
+            if other.counts.contains_key(&pc) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.1 + 2 == pc => {
+                    range.1 = pc;
+                    range.2 += count;
+                }
+                _ => ranges.push((pc, pc, count)),
+            }
+        }
+        ranges
+    }
+}
+
+impl fmt::Display for Hits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "program {:016x}", self.program)?;
+        for (pc, count) in &self.counts {
+            writeln!(f, "{:#06x} {}", pc, count)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse(text: &str) -> Result<Hits, String> {
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(String::from("not a coverage file"));
+    }
+    let mut hits = Hits::default();
+    for line in lines.filter(|l| !l.trim().is_empty()) {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let bad = || format!("could not parse line {} in coverage file", line);
+        if key == "program" {
+            hits.program = u64::from_str_radix(value, 16).map_err(|_| bad())?;
+            continue;
+        }
+        let pc = usize::from_str_radix(key.trim_start_matches("0x"), 16).map_err(|_| bad())?;
+        let count: usize = value.parse().map_err(|_| bad())?;
+        *hits.counts.entry(pc).or_default() += count;
+    }
+    Ok(hits)
+}
@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+use std::fmt;
+
+const MAX_KEY: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scheme {
+    Xor,
+    Add,
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scheme::Xor => write!(f, "XOR"),
+            Scheme::Add => write!(f, "ADD"),
+        }
+    }
+}
+
+impl Scheme {
+    fn key_byte(self, cipher: u8, plain: u8) -> u8 {
+        match self {
+            Scheme::Xor => cipher ^ plain,
+            Scheme::Add => cipher.wrapping_sub(plain),
+        }
+    }
+
+    fn decrypt(self, cipher: u8, key: u8) -> u8 {
+        match self {
+            Scheme::Xor => cipher ^ key,
+            Scheme::Add => cipher.wrapping_sub(key),
+        }
+    }
+}
+
+pub struct Candidate {
+    pub scheme: Scheme,
+    pub offset: usize,
+    pub key: Vec<u8>,
+    pub plaintext: Vec<u8>,
+    pub printable: f64,
+}
+
+fn derive_key(
+    scheme: Scheme,
+    data: &[u8],
+    crib: &[u8],
+    offset: usize,
+    length: usize,
+) -> Option<Vec<u8>> {
+    let mut key: Vec<Option<u8>> = vec![None; length];
+    for (j, &plain) in crib.iter().enumerate() {
+        let slot = &mut key[(offset + j) % length];
+        let byte = scheme.key_byte(data[offset + j], plain);
+        match slot {
+            Some(existing) if *existing != byte => return None,
+            _ => *slot = Some(byte),
+        }
+    }
+    key.into_iter().collect()
+}
+
+fn repeats_shorter_key(key: &[u8]) -> bool {
+    (1..key.len())
+        .filter(|&period| key.len().is_multiple_of(period))
+        .any(|period| key.iter().enumerate().all(|(i, &b)| b == key[i % period]))
+}
+
+pub fn recover_keys(data: &[u8], crib: &[u8]) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    if crib.is_empty() || data.len() < crib.len() {
+        return candidates;
+    }
+    let mut seen = HashSet::new();
+    for scheme in [Scheme::Xor, Scheme::Add] {
+        for length in 1..=crib.len().min(MAX_KEY) {
+            for offset in 0..=data.len() - crib.len() {
+                let key = match derive_key(scheme, data, crib, offset, length) {
+                    Some(key) => key,
+                    None => continue,
+                };
+                if repeats_shorter_key(&key) || !seen.insert((scheme, key.clone())) {
+                    continue;
+                }
+                let plaintext: Vec<u8> = data
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &c)| scheme.decrypt(c, key[i % length]))
+                    .collect();
+                let printable = plaintext
+                    .iter()
+                    .filter(|b| b.is_ascii_graphic() || **b == b' ')
+                    .count() as f64
+                    / plaintext.len() as f64;
+                candidates.push(Candidate {
+                    scheme,
+                    offset,
+                    key,
+                    plaintext,
+                    printable,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.printable
+            .partial_cmp(&a.printable)
+            .unwrap()
+            .then(a.key.len().cmp(&b.key.len()))
+    });
+    candidates
+}
+
+pub fn preview(plaintext: &[u8], width: usize) -> String {
+    plaintext
+        .iter()
+        .take(width)
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
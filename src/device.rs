@@ -0,0 +1,229 @@
+use crate::emulator::{HaltReason, State};
+use crate::error::S8Error;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+
+pub fn run_io<R: Read, W: Write>(
+    state: &mut State,
+    mut input: R,
+    mut output: W,
+    max_cycles: usize,
+) -> io::Result<()> {
+    let mut buffer = [0; 256];
+    loop {
+        if state.cycles() >= max_cycles {
+            state.halt(HaltReason::TickLimit(max_cycles));
+        }
+        match state.iter().next() {
+            None => break,
+            Some(Ok(_)) => (),
+            Some(Err(S8Error::InputExhausted(_))) => {
+                output.flush()?;
+                let n = input.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+                state.feed(&buffer[..n]);
+            }
+            Some(Err(_)) => break,
+        }
+        if !state.stdout().is_empty() {
+            output.write_all(state.stdout())?;
+            state.clear_stdout();
+        }
+    }
+    output.flush()
+}
+
+fn handle<S: Read + Write + TryClone>(id: usize, mut state: State, stream: S, max_cycles: usize) {
+    let result = stream
+        .try_clone()
+        .and_then(|reader| run_io(&mut state, reader, stream, max_cycles));
+    match (result, state.halted()) {
+        (Err(e), _) => eprintln!("connection {}: {}", id, e),
+        (Ok(()), Some(reason)) => eprintln!(
+            "connection {}: {} after {} cycles",
+            id,
+            reason,
+            state.cycles()
+        ),
+        (Ok(()), None) => eprintln!("connection {}: closed", id),
+    }
+}
+
+trait TryClone: Sized {
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl TryClone for std::net::TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        std::net::TcpStream::try_clone(self)
+    }
+}
+
+#[cfg(unix)]
+impl TryClone for std::os::unix::net::UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        std::os::unix::net::UnixStream::try_clone(self)
+    }
+}
+
+fn accept<S, I>(connections: I, template: &State, max_cycles: usize)
+where
+    S: Read + Write + TryClone + Send + 'static,
+    I: Iterator<Item = io::Result<S>>,
+{
+    for (id, stream) in connections.enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("connection {}: accept failed: {}", id, e);
+                thread::sleep(ACCEPT_RETRY);
+                continue;
+            }
+        };
+        let state = template.clone();
+        eprintln!("connection {}: accepted", id);
+        thread::spawn(move || handle(id, state, stream, max_cycles));
+    }
+}
+
+pub fn serve(spec: &str, template: &State, max_cycles: usize) -> io::Result<()> {
+    match spec.split_once(':') {
+        Some(("tcp", address)) => {
+            let listener = TcpListener::bind(address)?;
+            eprintln!("Listening on {}", listener.local_addr()?);
+            accept(listener.incoming(), template, max_cycles);
+            Ok(())
+        }
+        #[cfg(unix)]
+        Some(("unix", path)) => {
+            let listener = std::os::unix::net::UnixListener::bind(path)?;
+            eprintln!("Listening on {}", path);
+            accept(listener.incoming(), template, max_cycles);
+            Ok(())
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown device {}, expected tcp:ADDRESS or unix:PATH", spec),
+        )),
+    }
+}
+
+pub struct Utf8Writer<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Utf8Writer<W> {
+    pub fn new(inner: W) -> Utf8Writer<W> {
+        Utf8Writer {
+            inner,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for Utf8Writer<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(bytes);
+        loop {
+            match std::str::from_utf8(&self.pending) {
+                Ok(text) => {
+                    self.inner.write_all(text.as_bytes())?;
+                    self.pending.clear();
+                    break;
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    self.inner.write_all(&self.pending[..valid])?;
+                    let invalid = match e.error_len() {
+                        Some(invalid) => invalid,
+                        None => {
+                            self.pending.drain(..valid);
+                            break;
+                        }
+                    };
+                    for byte in &self.pending[valid..valid + invalid] {
+                        write!(self.inner, "\\x{:02x}", byte)?;
+                    }
+                    self.pending.drain(..valid + invalid);
+                }
+            }
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(fs::File::open("/dev/tty")?)
+        .output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+struct RestoreTerminal(String);
+
+impl Drop for RestoreTerminal {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.0]);
+    }
+}
+
+pub fn terminal(state: &mut State, raw: bool, max_cycles: usize) -> io::Result<()> {
+    let _restore = if raw {
+        let saved = stty(&["-g"])?;
+        let restore = saved.clone();
+        ctrlc::set_handler(move || {
+            let _ = stty(&[&restore]);
+            eprintln!();
+            std::process::exit(130);
+        })
+        .map_err(io::Error::other)?;
+        stty(&["-icanon", "-echo", "min", "1"])?;
+        Some(RestoreTerminal(saved))
+    } else {
+        None
+    };
+    let stdout = io::stdout();
+    run_io(
+        state,
+        io::stdin(),
+        Utf8Writer::new(stdout.lock()),
+        max_cycles,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{encode_instruction, memory_image, Instruction, Operand};
+
+    #[test]
+    fn output_is_passed_on_and_dropped() {
+        let program: Vec<u8> = [
+            Instruction::Les(Operand::Reg(2)),
+            Instruction::Skriv(Operand::Reg(2)),
+            Instruction::Hopp(Operand::Addr(0)),
+        ]
+        .iter()
+        .flat_map(|i| encode_instruction(i).to_le_bytes())
+        .collect();
+        let mut state = State::new(memory_image(&program), Vec::new());
+        let mut output = Vec::new();
+        run_io(&mut state, &b"abc"[..], &mut output, 1000).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(output, b"abc");
+        assert!(state.stdout().is_empty());
+    }
+}
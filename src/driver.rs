@@ -0,0 +1,138 @@
+use crate::emulator::{HaltReason, State, StepEvent};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+const QUEUED_EVENTS: usize = 1024;
+
+pub enum Command {
+    Pause,
+    Resume,
+    Step,
+    Stop,
+}
+
+pub struct StateDriver {
+    commands: Sender<Command>,
+    events: Receiver<StepEvent>,
+    handle: JoinHandle<State>,
+}
+
+fn step(state: &mut State, events: &SyncSender<StepEvent>) -> bool {
+    let written = state.stdout().len();
+    let sent = match state.iter().next() {
+        Some(Ok(event)) => events.send(event),
+        _ => return false,
+    };
+    let sent = match state.stdout().get(written) {
+        Some(&byte) => sent.and_then(|_| events.send(StepEvent::Output(byte))),
+        None => sent,
+    };
+    if sent.is_err() && state.halted().is_none() {
+        state.halt(HaltReason::UserInterrupt);
+    }
+    sent.is_ok()
+}
+
+fn drive(
+    mut state: State,
+    paused: bool,
+    commands: Receiver<Command>,
+    events: SyncSender<StepEvent>,
+) -> State {
+    let mut running = !paused;
+    if paused {
+        let _ = events.send(StepEvent::Paused { pc: state.pc() });
+    }
+    loop {
+        let command = if running {
+            match commands.try_recv() {
+                Ok(command) => Some(command),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => Some(Command::Stop),
+            }
+        } else {
+            Some(commands.recv().unwrap_or(Command::Stop))
+        };
+        let advanced = match command {
+            Some(Command::Pause) => {
+                running = false;
+                let _ = events.send(StepEvent::Paused { pc: state.pc() });
+                continue;
+            }
+            Some(Command::Resume) => {
+                running = true;
+                continue;
+            }
+            Some(Command::Step) => step(&mut state, &events),
+            Some(Command::Stop) => {
+                if state.halted().is_none() {
+                    state.halt(HaltReason::UserInterrupt);
+                }
+                false
+            }
+            None => step(&mut state, &events),
+        };
+        if !advanced {
+            break;
+        }
+    }
+    let _ = events.send(StepEvent::Halted {
+        cycles: state.cycles(),
+        reason: state.halted(),
+    });
+    state
+}
+
+impl StateDriver {
+    pub fn spawn(state: State, paused: bool) -> StateDriver {
+        let (commands, command_receiver) = channel();
+        let (event_sender, events) = sync_channel(QUEUED_EVENTS);
+        let handle = thread::spawn(move || drive(state, paused, command_receiver, event_sender));
+        StateDriver {
+            commands,
+            events,
+            handle,
+        }
+    }
+
+    pub fn controller(&self) -> Sender<Command> {
+        self.commands.clone()
+    }
+
+    pub fn events(&self) -> &Receiver<StepEvent> {
+        &self.events
+    }
+
+    pub fn join(self) -> State {
+        let StateDriver {
+            commands,
+            events,
+            handle,
+        } = self;
+        drop(commands);
+        drop(events);
+        handle.join().expect("Emulator thread panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{encode_instruction, memory_image, Instruction, Operand};
+
+    #[test]
+    fn join_without_reading_events() {
+        let program: Vec<u8> = [
+            Instruction::Skriv(Operand::Reg(0)),
+            Instruction::Hopp(Operand::Addr(0)),
+        ]
+        .iter()
+        .flat_map(|i| encode_instruction(i).to_le_bytes())
+        .collect();
+        let driver = StateDriver::spawn(State::new(memory_image(&program), Vec::new()), false);
+        let _controller = driver.controller();
+        assert!(driver.events().recv().is_ok());
+        let state = driver.join();
+        assert_eq!(state.halted(), Some(HaltReason::UserInterrupt));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::emulator::State;
+use crate::parser::MEMORY_SIZE;
+use crate::source::parse_number;
+use std::fs;
+
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+    pub at: Option<usize>,
+    pub path: String,
+}
+
+fn address(text: &str) -> Result<usize, String> {
+    parse_number(text)
+        .map(|a| a as usize)
+        .filter(|&a| a <= MEMORY_SIZE)
+        .ok_or(format!("bad address {}", text))
+}
+
+impl Region {
+    pub fn new(start: &str, end: &str, path: &str) -> Result<Region, String> {
+        let (start, end) = (address(start)?, address(end)?);
+        if start >= end {
+            return Err(format!("empty region {:#06x}-{:#06x}", start, end));
+        }
+        Ok(Region {
+            start,
+            end,
+            at: None,
+            path: path.to_string(),
+        })
+    }
+
+    pub fn parse(spec: &str) -> Result<Region, String> {
+        let (range, path) = spec.split_once('=').ok_or("expected START-END[@PC]=FILE")?;
+        let (range, at) = match range.split_once('@') {
+            Some((range, at)) => (range, Some(address(at)?)),
+            None => (range, None),
+        };
+        let (start, end) = range
+            .split_once('-')
+            .ok_or("expected START-END[@PC]=FILE")?;
+        let mut region = Region::new(start, end, path)?;
+        region.at = at;
+        Ok(region)
+    }
+
+    pub fn write(&self, state: &State) -> Result<(), String> {
+        let bytes: Vec<u8> = (self.start..self.end).map(|a| state.memory_at(a)).collect();
+        fs::write(&self.path, bytes)
+            .map_err(|e| format!("Could not write {}: {}", self.path, e))?;
+        eprintln!(
+            "Dumped {:#06x}-{:#06x} ({} bytes) to {}",
+            self.start,
+            self.end,
+            self.end - self.start,
+            self.path
+        );
+        Ok(())
+    }
+}
+
+pub fn load(state: &mut State, start: &str, path: &str) -> Result<(), String> {
+    let start = address(start)?;
+    let bytes = fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    state.write_memory(start, &bytes)?;
+    eprintln!(
+        "Loaded {} bytes from {} to {:#06x}-{:#06x}",
+        bytes.len(),
+        path,
+        start,
+        start + bytes.len()
+    );
+    Ok(())
+}
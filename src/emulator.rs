@@ -1,22 +1,29 @@
-use crate::parser::{decode_instruction, ALEOp, CMPOp, Instruction};
-use byteorder::{ByteOrder, LittleEndian};
-use std::fmt;
+use crate::costs::CostModel;
+use crate::error::S8Error;
+use crate::memory::Memory;
+use crate::parser::{decode_instruction, ALEOp, CMPOp, Instruction, Operand, MEMORY_SIZE};
+use rayon::prelude::*;
+use std::convert::TryInto;
+use std::fmt::{self, Write};
 use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Registers {
     data: [u8; 16],
 }
 
 impl fmt::Display for Registers {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut names = String::new();
-        let mut values = String::new();
         for r in 0..=15 {
-            names.push_str(&format!("r{:<2} ", r));
-            values.push_str(&format!("{:02x}h ", self.data[r]));
+            write!(f, "r{:<2} ", r)?;
         }
-        write!(f, "{}\n{}", names, values)
+        writeln!(f)?;
+        for value in &self.data {
+            write!(f, "{:02x}h ", value)?;
+        }
+        Ok(())
     }
 }
 
@@ -33,28 +40,203 @@ impl IndexMut<usize> for Registers {
     }
 }
 
-#[derive(Debug)]
+impl Index<Operand> for Registers {
+    type Output = u8;
+    fn index(&self, register: Operand) -> &u8 {
+        &self.data[usize::from(register)]
+    }
+}
+
+impl IndexMut<Operand> for Registers {
+    fn index_mut(&mut self, register: Operand) -> &mut Self::Output {
+        &mut self.data[usize::from(register)]
+    }
+}
+
+/// A running SLEDE8 machine: memory, registers, flag, call stack, input and output.
+#[derive(Clone, Debug)]
 pub struct State {
     cycles: usize,
-    memory: [u8; 4096],
+    costs: Option<Arc<CostModel>>,
+    memory: Memory,
     pc: usize,
     flagg: bool,
     returnpointers: Vec<usize>,
-    pub finished: bool,
+    halt: Option<HaltReason>,
     registers: Registers,
     stdin: Vec<u8>,
     stdinpointer: usize,
     stdout: Vec<u8>,
 }
 
+pub enum StepEvent {
+    Step {
+        pc: usize,
+        instruction: Instruction,
+        cycles: usize,
+    },
+    Output(u8),
+    Paused {
+        pc: usize,
+    },
+    Halted {
+        cycles: usize,
+        reason: Option<HaltReason>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HaltReason {
+    Stopp,
+    PcOutOfRange(usize),
+    TickLimit(usize),
+    Fault(S8Error),
+    UserInterrupt,
+}
+
+impl HaltReason {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            HaltReason::Stopp => 0,
+            HaltReason::PcOutOfRange(_) => 3,
+            HaltReason::TickLimit(_) => 4,
+            HaltReason::Fault(_) => 5,
+            HaltReason::UserInterrupt => 130,
+        }
+    }
+}
+
+impl From<S8Error> for HaltReason {
+    fn from(e: S8Error) -> HaltReason {
+        match e {
+            S8Error::PcOutOfRange(pc) => HaltReason::PcOutOfRange(pc),
+            e => HaltReason::Fault(e),
+        }
+    }
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HaltReason::Stopp => f.write_str("STOPP"),
+            HaltReason::PcOutOfRange(pc) => write!(f, "pc {:#06x} is outside memory", pc),
+            HaltReason::TickLimit(limit) => write!(f, "cycle limit {} reached", limit),
+            HaltReason::Fault(e) => write!(f, "fault: {}", e),
+            HaltReason::UserInterrupt => f.write_str("interrupted"),
+        }
+    }
+}
+
+/// The addresses of the instructions a run executed.
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    words: Box<[u64; MEMORY_SIZE / 64]>,
+}
+
+impl Default for Coverage {
+    fn default() -> Coverage {
+        Coverage {
+            words: Box::new([0; MEMORY_SIZE / 64]),
+        }
+    }
+}
+
+impl Coverage {
+    pub fn record(&mut self, pc: usize) {
+        self.words[pc / 64] |= 1 << (pc % 64);
+    }
+
+    pub fn contains(&self, pc: usize) -> bool {
+        pc < MEMORY_SIZE && self.words[pc / 64] & 1 << (pc % 64) != 0
+    }
+
+    /// The number of distinct instructions executed.
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..MEMORY_SIZE).filter(move |&pc| self.contains(pc))
+    }
+}
+
+/// How a run ended and what it did, so front-ends report the same numbers without reading the
+/// machine themselves.
+///
+/// [`quick_run`](crate::quick_run) leaves `coverage` and `state` empty to stay allocation free;
+/// [`State::run_result`] fills them in.
+#[derive(Clone, Debug)]
+pub struct RunResult {
+    pub halt: HaltReason,
+    pub cycles: usize,
+    pub wall_time: Duration,
+    pub input_read: usize,
+    pub output: Vec<u8>,
+    pub coverage: Option<Coverage>,
+    pub state: Option<Box<State>>,
+}
+
+impl fmt::Display for RunResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} after {} cycles in {:.3} ms, {} bytes read, {} bytes written",
+            self.halt,
+            self.cycles,
+            self.wall_time.as_secs_f64() * 1000.0,
+            self.input_read,
+            self.output.len()
+        )?;
+        if let Some(coverage) = &self.coverage {
+            write!(f, ", {} instructions executed", coverage.count())?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Steps<'a> {
+    state: &'a mut State,
+}
+
+impl Iterator for Steps<'_> {
+    type Item = Result<StepEvent, S8Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state.halt.is_some() {
+            return None;
+        }
+        if let Err(e) = self.state.check() {
+            self.state.halt = Some(e.into());
+            return Some(Err(e));
+        }
+        let pc = self.state.pc;
+        let instruction = self.state.next_instruction();
+        if let Err(e) = self.state.execute() {
+            return Some(Err(e));
+        }
+        Some(Ok(StepEvent::Step {
+            pc,
+            instruction,
+            cycles: self.state.cycles,
+        }))
+    }
+}
+
+const _: fn() = || {
+    fn send<T: Send>() {}
+    send::<State>();
+};
+
 impl State {
+    /// Starts at pc 0 with the given memory image and the bytes LES will read.
     pub fn new(mem: [u8; 4096], stdin: Vec<u8>) -> State {
         State {
             cycles: 0,
-            memory: mem,
+            costs: None,
+            memory: Memory::new(mem),
             pc: 0,
             flagg: false,
-            finished: false,
+            halt: None,
             registers: Registers { data: [0; 16] },
             stdin,
             returnpointers: Vec::new(),
@@ -63,12 +245,23 @@ impl State {
         }
     }
 
+    pub fn with_costs(mut self, costs: Option<Arc<CostModel>>) -> State {
+        self.costs = costs;
+        self
+    }
+
     pub fn display(&self) -> String {
+        let halt = match self.halt {
+            Some(reason) => format!(" halt: {}", reason),
+            None => String::new(),
+        };
         format!(
-            "pc: {:#6x} flagg: {} sykler: {}\n{}\n{}",
+            "pc: {:#6x} flagg: {} sykler: {} ptr: {:#05x}{}\n{}\n{}",
             self.pc,
             self.flagg,
             self.cycles,
+            self.pointer(),
+            halt,
             self.registers,
             self.next_instruction()
         )
@@ -78,41 +271,274 @@ impl State {
         self.pc
     }
 
-    pub fn dump_memory(&self, from: usize, to: usize) {
+    pub fn memory_dump(&self, from: usize, to: usize) -> Result<String, String> {
         if from > to || to > 0xfff {
-            eprintln!("Error: Memory adress incorrect.")
-        } else {
-            for (i, addr) in (from..to).enumerate() {
-                if i % 8 == 0 {
-                    print!("{:04x}: ", addr);
+            return Err(String::from("Memory adress incorrect."));
+        }
+        let mut dump = String::new();
+        for (i, addr) in (from..to).enumerate() {
+            if i % 8 == 0 {
+                let _ = write!(dump, "{:04x}: ", addr);
+            }
+            let _ = write!(dump, "{:02x} ", self.memory[addr]);
+            if i % 8 == 7 {
+                dump.push('\n');
+            }
+        }
+        Ok(dump)
+    }
+
+    pub fn next_instruction(&self) -> Instruction {
+        self.instruction_at(self.pc)
+    }
+
+    pub fn instruction_at(&self, address: usize) -> Instruction {
+        decode_instruction(self.memory.word(address))
+    }
+
+    pub fn memory_at(&self, address: usize) -> u8 {
+        self.memory[address]
+    }
+
+    pub fn write_memory(&mut self, address: usize, bytes: &[u8]) -> Result<(), String> {
+        if address + bytes.len() > MEMORY_SIZE {
+            return Err(format!(
+                "{} bytes at {:#06x} do not fit in memory",
+                bytes.len(),
+                address
+            ));
+        }
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.memory.set(address + i, byte);
+        }
+        Ok(())
+    }
+
+    pub fn dirty(&self) -> Vec<usize> {
+        self.memory.dirty()
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.memory.clear_dirty()
+    }
+
+    pub fn restore(&mut self, snapshot: &State) {
+        self.memory.restore(&snapshot.memory);
+        self.cycles = snapshot.cycles;
+        self.pc = snapshot.pc;
+        self.flagg = snapshot.flagg;
+        self.returnpointers.clone_from(&snapshot.returnpointers);
+        self.halt = snapshot.halt;
+        self.registers = snapshot.registers.clone();
+        self.stdinpointer = snapshot.stdinpointer;
+        self.stdout.clone_from(&snapshot.stdout);
+    }
+
+    pub fn reset(&mut self, snapshot: &State, stdin: &[u8]) {
+        self.restore(snapshot);
+        self.stdin.clear();
+        self.stdin.extend_from_slice(stdin);
+    }
+
+    pub fn checkpoint(&self) -> String {
+        let stack: Vec<String> = self
+            .returnpointers
+            .iter()
+            .map(|p| format!("{:x}", p))
+            .collect();
+        let memory: Vec<u8> = (0..MEMORY_SIZE).map(|a| self.memory[a]).collect();
+        format!(
+            "pc={:x} flagg={} cycles={} stdin={} registers={} stack={} stdout={} memory={}",
+            self.pc,
+            self.flagg,
+            self.cycles,
+            self.stdinpointer,
+            hex::encode(self.registers.data),
+            stack.join(","),
+            hex::encode(&self.stdout),
+            hex::encode(memory)
+        )
+    }
+
+    pub fn load_checkpoint(&mut self, text: &str) -> Result<(), String> {
+        let mut restored = self.clone();
+        for field in text.split_whitespace() {
+            let (key, value) = field.split_once('=').unwrap_or((field, ""));
+            let bad = || format!("bad {} in checkpoint", key);
+            match key {
+                "pc" => restored.pc = usize::from_str_radix(value, 16).map_err(|_| bad())?,
+                "flagg" => restored.flagg = value.parse().map_err(|_| bad())?,
+                "cycles" => restored.cycles = value.parse().map_err(|_| bad())?,
+                "stdin" => restored.stdinpointer = value.parse().map_err(|_| bad())?,
+                "registers" => {
+                    let bytes = hex::decode(value).map_err(|_| bad())?;
+                    restored.registers.data = bytes.try_into().map_err(|_| bad())?;
+                }
+                "stack" => {
+                    restored.returnpointers = value
+                        .split(',')
+                        .filter(|p| !p.is_empty())
+                        .map(|p| usize::from_str_radix(p, 16).map_err(|_| bad()))
+                        .collect::<Result<_, _>>()?
                 }
-                print!("{:02x} ", &self.memory[addr]);
-                if i % 8 == 7 {
-                    println!();
+                "stdout" => restored.stdout = hex::decode(value).map_err(|_| bad())?,
+                "memory" => {
+                    let bytes = hex::decode(value).map_err(|_| bad())?;
+                    restored.memory = Memory::new(bytes.try_into().map_err(|_| bad())?);
                 }
+                _ => return Err(format!("unknown field {} in checkpoint", key)),
             }
-            println!();
         }
+        restored.halt = None;
+        *self = restored;
+        Ok(())
     }
 
-    pub fn next_instruction(&self) -> Instruction {
-        let op = LittleEndian::read_u16(&self.memory[self.pc..self.pc + 2]);
-        decode_instruction(op)
+    pub fn flagg(&self) -> bool {
+        self.flagg
+    }
+
+    pub fn pointer(&self) -> usize {
+        (usize::from(self.registers[1]) << 8 | usize::from(self.registers[0])) & 0xfff
+    }
+
+    pub fn register(&self, r: usize) -> u8 {
+        self.registers[r]
+    }
+
+    pub fn set_register(&mut self, r: usize, value: u8) {
+        self.registers[r] = value;
+    }
+
+    pub fn set_flagg(&mut self, flagg: bool) {
+        self.flagg = flagg;
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.stdin.extend_from_slice(bytes);
+        if let Some(HaltReason::Fault(S8Error::InputExhausted(_))) = self.halt {
+            self.halt = None;
+        }
+    }
+
+    pub fn input_read(&self) -> usize {
+        self.stdinpointer
+    }
+
+    pub fn input_exhausted(&self) -> bool {
+        self.stdinpointer >= self.stdin.len()
+    }
+
+    pub fn halted(&self) -> Option<HaltReason> {
+        self.halt
+    }
+
+    pub fn halt(&mut self, reason: HaltReason) {
+        self.halt = Some(reason);
+    }
+
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+
+    pub fn check(&self) -> Result<(), S8Error> {
+        if self.pc + 1 >= MEMORY_SIZE {
+            return Err(S8Error::PcOutOfRange(self.pc));
+        }
+        match self.next_instruction() {
+            Instruction::Les(_) if self.input_exhausted() => Err(S8Error::InputExhausted(self.pc)),
+            Instruction::Retur if self.returnpointers.is_empty() => {
+                Err(S8Error::EmptyReturnStack(self.pc))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn can_execute(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Steps one instruction per item until the machine halts.
+    pub fn iter(&mut self) -> Steps<'_> {
+        Steps { state: self }
+    }
+
+    /// Runs until the machine halts or `max_cycles` more cycles have passed.
+    pub fn run(&mut self, max_cycles: usize) {
+        let limit = self.cycles + max_cycles;
+        while self.cycles < limit {
+            match self.iter().next() {
+                Some(Ok(_)) => (),
+                _ => break,
+            }
+        }
+    }
+
+    /// Runs like [`State::run`] and returns the result with the executed instructions and the
+    /// final machine.
+    pub fn run_result(mut self, max_cycles: usize) -> RunResult {
+        let started = Instant::now();
+        let mut coverage = Coverage::default();
+        let limit = self.cycles + max_cycles;
+        while self.cycles < limit {
+            match self.iter().next() {
+                Some(Ok(StepEvent::Step { pc, .. })) => coverage.record(pc),
+                Some(Ok(_)) => (),
+                _ => break,
+            }
+        }
+        let unfinished = HaltReason::TickLimit(max_cycles);
+        self.into_result(unfinished, started.elapsed(), Some(coverage))
+    }
+
+    /// Wraps up a run driven step by step. `unfinished` is the halt reason reported if the machine
+    /// has not halted.
+    pub fn into_result(
+        self,
+        unfinished: HaltReason,
+        wall_time: Duration,
+        coverage: Option<Coverage>,
+    ) -> RunResult {
+        RunResult {
+            halt: self.halt.unwrap_or(unfinished),
+            cycles: self.cycles,
+            wall_time,
+            input_read: self.stdinpointer,
+            output: self.stdout.clone(),
+            coverage,
+            state: Some(Box::new(self)),
+        }
     }
 
     pub fn stdout(&self) -> &[u8] {
         &self.stdout
     }
 
-    pub fn execute(&mut self) {
+    /// Forgets the output written so far, for front-ends that have already passed it on.
+    pub fn clear_stdout(&mut self) {
+        self.stdout.clear();
+    }
+
+    pub fn execute(&mut self) -> Result<(), S8Error> {
+        if let Err(e) = self.check() {
+            self.halt = Some(e.into());
+            return Err(e);
+        }
         let instruction = self.next_instruction();
         self.pc += 2;
-        self.cycles += 1;
+        self.cycles += match &self.costs {
+            Some(costs) => costs.cost(&instruction),
+            None => 1,
+        };
 
         match instruction {
             Instruction::Sett(ra, rb) => self.registers[ra] = self.registers[rb],
-            Instruction::SettImmediate(ra, val) => self.registers[ra] = val,
-            Instruction::Stopp => self.finished = true,
+            Instruction::SettImmediate(ra, val) => self.registers[ra] = val.value() as u8,
+            Instruction::Stopp => self.halt = Some(HaltReason::Stopp),
             Instruction::Hopp(loc) => self.pc = usize::from(loc),
             Instruction::BHopp(loc) => {
                 if self.flagg {
@@ -120,45 +546,50 @@ impl State {
                 }
             }
             Instruction::Les(r) => {
-                self.registers[r] = self.stdin[self.stdinpointer];
-                self.stdinpointer += 1;
+                if let Some(&byte) = self.stdin.get(self.stdinpointer) {
+                    self.registers[r] = byte;
+                    self.stdinpointer += 1;
+                }
             }
             Instruction::Skriv(r) => self.stdout.push(self.registers[r]),
             Instruction::Tur(adr) => {
                 self.returnpointers.push(self.pc);
                 self.pc = usize::from(adr);
             }
-            Instruction::Retur => self.pc = self.returnpointers.pop().unwrap(),
+            Instruction::Retur => {
+                if let Some(pc) = self.returnpointers.pop() {
+                    self.pc = pc;
+                }
+            }
             Instruction::Finn(adr) => {
-                let lsb = adr as u8;
-                let msb = (adr >> 8) as u8;
+                let lsb = adr.value() as u8;
+                let msb = (adr.value() >> 8) as u8;
                 self.registers[0] = lsb;
                 self.registers[1] = msb;
             }
-            Instruction::Last(n) => {
-                let msb = u16::from(self.registers[1]);
-                let adr: u16 = (msb << 8 | u16::from(self.registers[0])) & 0xfff;
-                self.registers[n] = self.memory[adr as usize];
-            }
-            Instruction::Lagr(n) => {
-                let msb = u16::from(self.registers[1]);
-                let adr: u16 = (msb << 8 | u16::from(self.registers[0])) & 0xfff;
-                self.memory[adr as usize] = self.registers[n];
-            }
+            Instruction::Last(n) => self.registers[n] = self.memory[self.pointer()],
+            Instruction::Lagr(n) => self.memory.set(self.pointer(), self.registers[n]),
             Instruction::ALE(op, ra, rb) => match op {
                 ALEOp::Pluss => {
                     self.registers[ra] =
                         ((self.registers[ra] as u16 + self.registers[rb] as u16) % 0x100) as u8
                 }
                 ALEOp::Minus => {
-                    self.registers[ra] =
-                        ((self.registers[ra] as u16 - self.registers[rb] as u16) % 0x100) as u8
+                    self.registers[ra] = self.registers[ra].wrapping_sub(self.registers[rb])
                 }
                 ALEOp::Eller => self.registers[ra] |= self.registers[rb],
                 ALEOp::XEller => self.registers[ra] ^= self.registers[rb],
                 ALEOp::Og => self.registers[ra] &= self.registers[rb],
-                ALEOp::Vskift => self.registers[ra] <<= self.registers[rb],
-                ALEOp::Hskift => self.registers[ra] >>= self.registers[rb],
+                ALEOp::Vskift => {
+                    self.registers[ra] = self.registers[ra]
+                        .checked_shl(u32::from(self.registers[rb]))
+                        .unwrap_or(0)
+                }
+                ALEOp::Hskift => {
+                    self.registers[ra] = self.registers[ra]
+                        .checked_shr(u32::from(self.registers[rb]))
+                        .unwrap_or(0)
+                }
             },
             Instruction::Sammenligne(op, ra, rb) => {
                 let a = self.registers[ra];
@@ -176,8 +607,53 @@ impl State {
             Instruction::Nope => (),
             Instruction::Data(_) => (),
         }
-        if self.pc >= self.memory.len() {
-            self.finished = true;
+        if self.pc >= MEMORY_SIZE && self.halt.is_none() {
+            self.halt = Some(HaltReason::PcOutOfRange(self.pc));
         }
+        Ok(())
+    }
+}
+
+pub fn run_many(states: &mut [State], budget: usize) {
+    states.par_iter_mut().for_each(|state| state.run(budget));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{encode_instruction, memory_image};
+
+    fn state(instructions: &[Instruction], stdin: &[u8]) -> State {
+        let program: Vec<u8> = instructions
+            .iter()
+            .flat_map(|i| encode_instruction(i).to_le_bytes())
+            .collect();
+        State::new(memory_image(&program), stdin.to_vec())
+    }
+
+    #[test]
+    fn run_result_of_a_finished_run() {
+        let result = state(
+            &[
+                Instruction::Les(Operand::Reg(2)),
+                Instruction::Skriv(Operand::Reg(2)),
+                Instruction::Stopp,
+            ],
+            b"x",
+        )
+        .run_result(100);
+        assert_eq!(result.halt, HaltReason::Stopp);
+        assert_eq!(result.cycles, 3);
+        assert_eq!((result.input_read, result.output), (1, b"x".to_vec()));
+        let coverage = result.coverage.unwrap_or_default();
+        assert_eq!(coverage.addresses().collect::<Vec<_>>(), vec![0, 2, 4]);
+        assert!(result.state.is_some());
+    }
+
+    #[test]
+    fn run_result_at_the_cycle_limit() {
+        let result = state(&[Instruction::Hopp(Operand::Addr(0))], &[]).run_result(10);
+        assert_eq!(result.halt, HaltReason::TickLimit(10));
+        assert_eq!(result.cycles, 10);
     }
 }
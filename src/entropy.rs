@@ -0,0 +1,50 @@
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+pub struct Window {
+    pub start: usize,
+    pub entropy: f64,
+}
+
+pub fn shannon(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &b in data {
+        counts[usize::from(b)] += 1;
+    }
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / data.len() as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+pub fn windows(data: &[u8], size: usize) -> Vec<Window> {
+    data.chunks(size.max(1))
+        .enumerate()
+        .map(|(i, chunk)| Window {
+            start: i * size.max(1),
+            entropy: shannon(chunk),
+        })
+        .collect()
+}
+
+pub fn histogram(data: &[u8], buckets: usize) -> Vec<usize> {
+    let mut counts = vec![0; buckets];
+    for &b in data {
+        counts[usize::from(b) * buckets / 256] += 1;
+    }
+    counts
+}
+
+pub fn spark(value: f64, max: f64) -> char {
+    if max <= 0.0 {
+        return SPARKS[0];
+    }
+    let level = (value / max * (SPARKS.len() - 1) as f64).round() as usize;
+    SPARKS[level.min(SPARKS.len() - 1)]
+}
@@ -0,0 +1,24 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum S8Error {
+    PcOutOfRange(usize),
+    InputExhausted(usize),
+    EmptyReturnStack(usize),
+    NotSlede8,
+}
+
+impl fmt::Display for S8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            S8Error::PcOutOfRange(pc) => write!(f, "pc {:#06x} is outside memory", pc),
+            S8Error::InputExhausted(pc) => write!(f, "LES at {:#06x} with no input left", pc),
+            S8Error::EmptyReturnStack(pc) => {
+                write!(f, "RETUR at {:#06x} with an empty return stack", pc)
+            }
+            S8Error::NotSlede8 => f.write_str("not a SLEDE8 program (no .SLEDE8 header)"),
+        }
+    }
+}
+
+impl std::error::Error for S8Error {}
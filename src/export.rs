@@ -0,0 +1,52 @@
+use crate::replay::Replay;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let word = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |w, (i, &b)| w | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(word >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+pub fn write(directory: &str, replay: &Replay) -> io::Result<Vec<String>> {
+    fs::create_dir_all(directory)?;
+    let files: Vec<(&str, Vec<u8>)> = vec![
+        ("solution.bin", replay.input.clone()),
+        (
+            "solution.hex",
+            format!("{}\n", hex::encode(&replay.input)).into_bytes(),
+        ),
+        (
+            "solution.b64",
+            format!("{}\n", base64(&replay.input)).into_bytes(),
+        ),
+        ("output.bin", replay.output.clone()),
+        (
+            "output.hex",
+            format!("{}\n", hex::encode(&replay.output)).into_bytes(),
+        ),
+        ("manifest.s8replay", replay.to_string().into_bytes()),
+    ];
+    let mut written = Vec::new();
+    for (name, bytes) in files {
+        let path = Path::new(directory).join(name);
+        fs::write(&path, bytes)?;
+        written.push(path.to_string_lossy().into_owned());
+    }
+    Ok(written)
+}
@@ -0,0 +1,430 @@
+use crate::emulator::State;
+use crate::source::{mnemonic_doc, parse_number, parse_register};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Xor,
+    Or,
+    LogicAnd,
+    LogicOr,
+    Pair,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnOp {
+    Neg,
+    Not,
+    LogicNot,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Register(usize),
+    Cycles,
+    Pc,
+    Flagg,
+    Pointer,
+    Mnemonic(Vec<String>),
+    Memory(Box<Expr>),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 27] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "..", "*", "/", "%", "+", "-", "<", ">", "&",
+    "^", "|", "~", "!", "(", ")", "[", "]", ":", ",",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("unexpected {:?}", rest.chars().next().unwrap()));
+            }
+            let word = &rest[..end];
+            if word.starts_with(|c: char| c.is_ascii_digit()) {
+                let value = parse_number(word).ok_or(format!("{} is not a number", word))?;
+                tokens.push(Token::Number(i64::from(value)));
+            } else {
+                tokens.push(Token::Name(word.to_string()));
+            }
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn binary(op: &str) -> Option<(BinOp, u8)> {
+    Some(match op {
+        "||" => (BinOp::LogicOr, 1),
+        "&&" => (BinOp::LogicAnd, 2),
+        "|" => (BinOp::Or, 3),
+        "^" => (BinOp::Xor, 4),
+        "&" => (BinOp::And, 5),
+        "==" => (BinOp::Eq, 6),
+        "!=" => (BinOp::Ne, 6),
+        "<" => (BinOp::Lt, 7),
+        "<=" => (BinOp::Le, 7),
+        ">" => (BinOp::Gt, 7),
+        ">=" => (BinOp::Ge, 7),
+        "<<" => (BinOp::Shl, 8),
+        ">>" => (BinOp::Shr, 8),
+        "+" => (BinOp::Add, 9),
+        "-" => (BinOp::Sub, 9),
+        "*" => (BinOp::Mul, 10),
+        "/" => (BinOp::Div, 10),
+        "%" => (BinOp::Rem, 10),
+        ":" => (BinOp::Pair, 11),
+        _ => return None,
+    })
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    aliases: &'a BTreeMap<usize, String>,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(found)) if found == op => Ok(()),
+            _ => Err(format!("expected {}", op)),
+        }
+    }
+
+    fn name(&self, name: &str) -> Result<Expr, String> {
+        if let Some(register) = parse_register(name).filter(|&r| r < 16) {
+            return Ok(Expr::Register(register));
+        }
+        if let Some((&register, _)) = self.aliases.iter().find(|(_, alias)| *alias == name) {
+            return Ok(Expr::Register(register));
+        }
+        match name {
+            "cycles" => Ok(Expr::Cycles),
+            "pc" => Ok(Expr::Pc),
+            "flagg" => Ok(Expr::Flagg),
+            "ptr" => Ok(Expr::Pointer),
+            _ => Err(format!("unknown name {}", name)),
+        }
+    }
+
+    fn is_in(&self) -> bool {
+        matches!(self.peek(), Some(Token::Name(name)) if name == "in")
+    }
+
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        if self.peek() != Some(&Token::Op("(")) {
+            return Ok(vec![item(self)?]);
+        }
+        self.position += 1;
+        let mut items = vec![item(self)?];
+        while self.peek() == Some(&Token::Op(",")) {
+            self.position += 1;
+            items.push(item(self)?);
+        }
+        self.expect(")")?;
+        Ok(items)
+    }
+
+    fn mnemonics(&mut self) -> Result<Expr, String> {
+        if !self.is_in() {
+            return Err(String::from("expected mnemonic in (NAME, ...)"));
+        }
+        self.position += 1;
+        let names = self.list(|parser| match parser.next() {
+            Some(Token::Name(name)) if mnemonic_doc(&name).is_some() => Ok(name.to_uppercase()),
+            Some(Token::Name(name)) => Err(format!("{} is not a mnemonic", name)),
+            _ => Err(String::from("expected a mnemonic")),
+        })?;
+        Ok(Expr::Mnemonic(names))
+    }
+
+    fn membership(&mut self, value: Expr) -> Result<Expr, String> {
+        let items = self.list(|parser| {
+            let lo = parser.expression(7)?;
+            if parser.peek() != Some(&Token::Op("..")) {
+                return Ok(Expr::Binary(
+                    BinOp::Eq,
+                    Box::new(value.clone()),
+                    Box::new(lo),
+                ));
+            }
+            parser.position += 1;
+            let hi = parser.expression(7)?;
+            Ok(Expr::Binary(
+                BinOp::LogicAnd,
+                Box::new(Expr::Binary(
+                    BinOp::Ge,
+                    Box::new(value.clone()),
+                    Box::new(lo),
+                )),
+                Box::new(Expr::Binary(
+                    BinOp::Le,
+                    Box::new(value.clone()),
+                    Box::new(hi),
+                )),
+            ))
+        })?;
+        Ok(items
+            .into_iter()
+            .reduce(|a, b| Expr::Binary(BinOp::LogicOr, Box::new(a), Box::new(b)))
+            .expect("a list has at least one item"))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Name(name)) if name == "mnemonic" => self.mnemonics(),
+            Some(Token::Name(name)) => self.name(&name),
+            Some(Token::Op("(")) => {
+                let expr = self.expression(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Op("[")) => {
+                let expr = self.expression(0)?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(expr)))
+            }
+            Some(Token::Op("-")) => Ok(Expr::Unary(UnOp::Neg, Box::new(self.primary()?))),
+            Some(Token::Op("~")) => Ok(Expr::Unary(UnOp::Not, Box::new(self.primary()?))),
+            Some(Token::Op("!")) => Ok(Expr::Unary(UnOp::LogicNot, Box::new(self.primary()?))),
+            Some(Token::Op(op)) => Err(format!("unexpected {}", op)),
+            None => Err(String::from("unexpected end of expression")),
+        }
+    }
+
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut left = self.primary()?;
+        loop {
+            if self.is_in() && min_precedence < 7 {
+                self.position += 1;
+                left = self.membership(left)?;
+                continue;
+            }
+            let op = match self.peek() {
+                Some(&Token::Op(op)) => op,
+                _ => break,
+            };
+            let (op, precedence) = match binary(op) {
+                Some((op, precedence)) if precedence > min_precedence => (op, precedence),
+                _ => break,
+            };
+            self.position += 1;
+            let right = self.expression(precedence)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str, aliases: &BTreeMap<usize, String>) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+            aliases,
+        };
+        let expr = parser.expression(0)?;
+        match parser.next() {
+            None => Ok(expr),
+            Some(Token::Number(value)) => Err(format!("unexpected {}", value)),
+            Some(Token::Name(name)) => Err(format!("unexpected {}", name)),
+            Some(Token::Op(op)) => Err(format!("unexpected {}", op)),
+        }
+    }
+
+    pub fn eval(&self, state: &State) -> Result<i64, String> {
+        Ok(match self {
+            Expr::Number(value) => *value,
+            Expr::Register(r) => i64::from(state.register(*r)),
+            Expr::Cycles => state.cycles() as i64,
+            Expr::Pc => state.pc() as i64,
+            Expr::Flagg => i64::from(state.flagg()),
+            Expr::Pointer => state.pointer() as i64,
+            Expr::Mnemonic(names) => {
+                let mnemonic = state.next_instruction().mnemonic();
+                i64::from(names.iter().any(|name| name == mnemonic))
+            }
+            Expr::Memory(address) => {
+                i64::from(state.memory_at((address.eval(state)? & 0xfff) as usize))
+            }
+            Expr::Unary(op, operand) => {
+                let value = operand.eval(state)?;
+                match op {
+                    UnOp::Neg => value.wrapping_neg(),
+                    UnOp::Not => !value,
+                    UnOp::LogicNot => i64::from(value == 0),
+                }
+            }
+            Expr::Binary(op, left, right) => {
+                let a = left.eval(state)?;
+                let b = right.eval(state)?;
+                match op {
+                    BinOp::Mul => a.wrapping_mul(b),
+                    BinOp::Div | BinOp::Rem if b == 0 => {
+                        return Err(String::from("division by zero"))
+                    }
+                    BinOp::Div => a.wrapping_div(b),
+                    BinOp::Rem => a.wrapping_rem(b),
+                    BinOp::Add => a.wrapping_add(b),
+                    BinOp::Sub => a.wrapping_sub(b),
+                    BinOp::Shl => a.wrapping_shl(b as u32),
+                    BinOp::Shr => a.wrapping_shr(b as u32),
+                    BinOp::Lt => i64::from(a < b),
+                    BinOp::Le => i64::from(a <= b),
+                    BinOp::Gt => i64::from(a > b),
+                    BinOp::Ge => i64::from(a >= b),
+                    BinOp::Eq => i64::from(a == b),
+                    BinOp::Ne => i64::from(a != b),
+                    BinOp::And => a & b,
+                    BinOp::Xor => a ^ b,
+                    BinOp::Or => a | b,
+                    BinOp::LogicAnd => i64::from(a != 0 && b != 0),
+                    BinOp::LogicOr => i64::from(a != 0 || b != 0),
+                    BinOp::Pair => (a & 0xff) << 8 | (b & 0xff),
+                }
+            }
+        })
+    }
+}
+
+pub fn radices(value: i64) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let magnitude = value.unsigned_abs();
+    let mut text = format!(
+        "{} {}{:#x} {}{:#o} {}{:#b}",
+        value, sign, magnitude, sign, magnitude, sign, magnitude
+    );
+    if let Some(c) = u8::try_from(value)
+        .ok()
+        .filter(|c| c.is_ascii_graphic() || *c == b' ')
+    {
+        text.push_str(&format!(" '{}'", c as char));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{encode_instruction, memory_image, Instruction, Operand};
+
+    fn state() -> State {
+        let program: Vec<u8> = [
+            Instruction::Nope,
+            Instruction::Nope,
+            Instruction::Skriv(Operand::Reg(2)),
+        ]
+        .iter()
+        .flat_map(|i| encode_instruction(i).to_le_bytes())
+        .collect();
+        let mut state = State::new(memory_image(&program), Vec::new());
+        state.run(2);
+        state.set_register(0, 0x34);
+        state.set_register(1, 0x02);
+        state.set_register(2, 0x1f);
+        let _ = state.write_memory(0x234, &[0x7b]);
+        state
+    }
+
+    fn eval(text: &str) -> Result<i64, String> {
+        let mut aliases = BTreeMap::new();
+        aliases.insert(2, String::from("count"));
+        Expr::parse(text, &aliases).and_then(|e| e.eval(&state()))
+    }
+
+    #[test]
+    fn request_examples() {
+        assert_eq!(eval("(r2 << 8 | r1) & 0xfff"), Ok(0xf02));
+        assert_eq!(eval("[r1:r0]"), Ok(0x7b));
+        assert_eq!(eval("cycles"), Ok(2));
+    }
+
+    #[test]
+    fn precedence_and_names() {
+        assert_eq!(eval("r1:r0 * 2"), Ok(0x468));
+        assert_eq!(eval("1 + 2 * 3 == 7 && !flagg"), Ok(1));
+        assert_eq!(eval("count - 0x20"), Ok(-1));
+        assert_eq!(eval("ptr == 0x234 && pc == 4"), Ok(1));
+        assert_eq!(eval("r2 in (1, 0x10..0x1f)"), Ok(1));
+        assert_eq!(eval("r2 in 0..0x10"), Ok(0));
+        assert_eq!(eval("mnemonic in (skriv, LES)"), Ok(1));
+        assert_eq!(eval("mnemonic in nope"), Ok(0));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(eval("r2 / (r1 - 2)"), Err(String::from("division by zero")));
+        assert_eq!(eval("r2 % 0"), Err(String::from("division by zero")));
+        assert_eq!(eval("nothing"), Err(String::from("unknown name nothing")));
+        assert_eq!(eval("(r2"), Err(String::from("expected )")));
+        assert_eq!(eval("r2 r3"), Err(String::from("unexpected r3")));
+        assert_eq!(
+            eval("mnemonic in (sett, bogus)"),
+            Err(String::from("bogus is not a mnemonic"))
+        );
+    }
+
+    #[test]
+    fn radices_of_negative_values() {
+        assert_eq!(radices(0x41), "65 0x41 0o101 0b1000001 'A'");
+        assert_eq!(radices(-1), "-1 -0x1 -0o1 -0b1");
+        assert_eq!(radices(-0x80), "-128 -0x80 -0o200 -0b10000000");
+        assert_eq!(
+            radices(i64::MIN),
+            format!(
+                "{} -0x8000000000000000 -0o1000000000000000000000 -0b1{}",
+                i64::MIN,
+                "0".repeat(63)
+            )
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use crate::source::{parse, SourceLine};
+
+const INDENT: &str = "    ";
+const MNEMONIC_WIDTH: usize = 7;
+
+fn format_comment(comment: &str) -> String {
+    if comment.is_empty() {
+        String::from(";")
+    } else {
+        format!("; {}", comment)
+    }
+}
+
+fn format_code(line: &SourceLine) -> Option<String> {
+    let mnemonic = line.mnemonic.as_ref()?;
+    if line.operands.is_empty() {
+        Some(format!("{}{}", INDENT, mnemonic))
+    } else {
+        Some(format!(
+            "{}{:<width$}{}",
+            INDENT,
+            mnemonic,
+            line.operands.join(", "),
+            width = MNEMONIC_WIDTH
+        ))
+    }
+}
+
+pub fn format_source(source: &str) -> String {
+    let lines = parse(source);
+
+    let mut rows: Vec<(String, Option<String>)> = Vec::new();
+    for (raw, line) in source.lines().zip(lines.iter()) {
+        if line.is_blank() {
+            rows.push((String::new(), None));
+            continue;
+        }
+        let comment = line.comment.as_deref().map(format_comment);
+        match (&line.label, format_code(line)) {
+            (Some(label), Some(code)) => {
+                rows.push((format!("{}:", label), None));
+                rows.push((code, comment));
+            }
+            (Some(label), None) => rows.push((format!("{}:", label), comment)),
+            (None, Some(code)) => rows.push((code, comment)),
+            (None, None) => {
+                let indent = if raw.starts_with(char::is_whitespace) {
+                    INDENT
+                } else {
+                    ""
+                };
+                rows.push((format!("{}{}", indent, comment.unwrap()), None));
+            }
+        }
+    }
+
+    let column = rows
+        .iter()
+        .filter(|(_, comment)| comment.is_some())
+        .map(|(code, _)| code.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+
+    let mut output = String::new();
+    let mut previous_blank = true;
+    for (code, comment) in rows {
+        let blank = code.is_empty() && comment.is_none();
+        if blank && previous_blank {
+            continue;
+        }
+        previous_blank = blank;
+        match comment {
+            Some(comment) => {
+                output.push_str(&format!("{:<width$}{}", code, comment, width = column))
+            }
+            None => output.push_str(&code),
+        }
+        output.push('\n');
+    }
+    while output.ends_with("\n\n") {
+        output.pop();
+    }
+    output
+}
@@ -0,0 +1,70 @@
+use crate::emulator::State;
+use crate::parser::Instruction;
+
+struct Frame {
+    entry: usize,
+    registers: [u8; 16],
+    cycles: usize,
+}
+
+#[derive(Default)]
+pub struct FunctionTracer {
+    stack: Vec<Frame>,
+}
+
+fn registers(state: &State) -> [u8; 16] {
+    let mut registers = [0; 16];
+    for (r, value) in registers.iter_mut().enumerate() {
+        *value = state.register(r);
+    }
+    registers
+}
+
+impl FunctionTracer {
+    pub fn before(&mut self, state: &State) -> Option<String> {
+        let indent = "  ".repeat(self.stack.len());
+        match state.next_instruction() {
+            Instruction::Tur(target) => {
+                let frame = Frame {
+                    entry: usize::from(target),
+                    registers: registers(state),
+                    cycles: state.cycles(),
+                };
+                let values: Vec<String> = frame
+                    .registers
+                    .iter()
+                    .map(|v| format!("{:02x}", v))
+                    .collect();
+                let line = format!(
+                    "{}call {:#05x} from {:#05x}  r0-r15: {}",
+                    indent,
+                    frame.entry,
+                    state.pc(),
+                    values.join(" ")
+                );
+                self.stack.push(frame);
+                Some(line)
+            }
+            Instruction::Retur => {
+                let frame = self.stack.pop()?;
+                let now = registers(state);
+                let changed: Vec<String> = (0..16)
+                    .filter(|&r| now[r] != frame.registers[r])
+                    .map(|r| format!("r{}={:02x}", r, now[r]))
+                    .collect();
+                Some(format!(
+                    "{}ret  {:#05x} after {} cycles  {}",
+                    &indent[2..],
+                    frame.entry,
+                    state.cycles() - frame.cycles,
+                    if changed.is_empty() {
+                        String::from("no registers changed")
+                    } else {
+                        changed.join(" ")
+                    }
+                ))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::parser::{decode_at, reachable, Instruction, MEMORY_SIZE};
+use std::collections::BTreeSet;
+
+pub fn find_functions(memory: &[u8]) -> BTreeSet<usize> {
+    reachable(memory)
+        .into_iter()
+        .filter_map(|pc| match decode_at(memory, pc) {
+            Instruction::Tur(target) => Some(usize::from(target)),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn function_body(memory: &[u8], entry: usize) -> BTreeSet<usize> {
+    let mut body = BTreeSet::new();
+    let mut queue = vec![entry];
+    while let Some(pc) = queue.pop() {
+        if pc + 1 >= MEMORY_SIZE || !body.insert(pc) {
+            continue;
+        }
+        match decode_at(memory, pc) {
+            Instruction::Tur(_) => queue.push(pc + 2),
+            instruction => queue.extend(instruction.successors(pc)),
+        }
+    }
+    body
+}
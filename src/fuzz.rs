@@ -0,0 +1,202 @@
+use crate::assembler::assemble_fragment;
+use crate::checkpoint::Checkpoint;
+use crate::conformance::catch_panic;
+use crate::emulator::State;
+use crate::parser;
+use crate::rng::Rng;
+use crate::signatures::fnv1a;
+use crate::source::parse_number;
+use std::collections::BTreeSet;
+use std::fs;
+
+const MAX_INPUT: usize = 8192;
+const MAX_CYCLES: usize = 10_000;
+
+const TARGETS: [&str; 4] = ["decode", "disassemble", "assemble", "execute"];
+
+pub struct Options {
+    pub target: String,
+    pub seed: u64,
+    pub count: usize,
+}
+
+pub fn parse(spec: &str, default_seed: u64) -> Result<Options, String> {
+    let mut parts = spec.split(':');
+    let target = parts.next().unwrap_or_default().to_string();
+    if !TARGETS.contains(&target.as_str()) {
+        return Err(format!(
+            "unknown fuzz target {}, expected one of {}",
+            target,
+            TARGETS.join(", ")
+        ));
+    }
+    let mut options = Options {
+        target,
+        seed: default_seed,
+        count: 100_000,
+    };
+    for part in parts.filter(|p| !p.is_empty()) {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        let number = parse_number(value).ok_or(format!("bad value in {}", part))?;
+        match key {
+            "seed" => options.seed = u64::from(number),
+            "count" => options.count = number as usize,
+            _ => return Err(format!("unknown fuzz option {}", key)),
+        }
+    }
+    Ok(options)
+}
+
+fn decode(data: &[u8]) {
+    for chunk in data.chunks(2) {
+        let op = u16::from_le_bytes([chunk[0], chunk.get(1).copied().unwrap_or(0)]);
+        let instruction = parser::decode_instruction(op);
+        let _ = instruction.to_string();
+        let op = parser::encode_instruction(&instruction);
+        assert_eq!(
+            parser::encode_instruction(&parser::decode_instruction(op)),
+            op
+        );
+    }
+}
+
+fn disassemble(data: &[u8]) {
+    for line in parser::disassemble(data, 0).into_iter().flatten() {
+        let _ = line.instruction.to_string();
+    }
+}
+
+fn assemble(data: &[u8]) {
+    let _ = assemble_fragment(&String::from_utf8_lossy(data));
+}
+
+fn execute(data: &[u8]) {
+    let (program, stdin) = data.split_at(data.len().min(parser::MEMORY_SIZE));
+    let mut state = State::new(parser::memory_image(program), stdin.to_vec());
+    state.run(MAX_CYCLES);
+}
+
+fn run_target(target: &str, data: &[u8]) {
+    match target {
+        "decode" => decode(data),
+        "disassemble" => disassemble(data),
+        "assemble" => assemble(data),
+        _ => execute(data),
+    }
+}
+
+fn mutate(rng: &mut Rng, data: &mut Vec<u8>) {
+    for _ in 0..1 + rng.next_u64() % 8 {
+        let position = match data.len() {
+            0 => 0,
+            length => rng.next_u64() as usize % length,
+        };
+        match rng.next_u64() % 5 {
+            0 if !data.is_empty() => data[position] ^= 1 << (rng.next_u64() % 8),
+            1 if !data.is_empty() => data[position] = rng.next_u64() as u8,
+            2 if !data.is_empty() => {
+                data.remove(position);
+            }
+            3 => {
+                let length = 1 + rng.next_u64() as usize % 16;
+                data.extend(rng.bytes(length));
+            }
+            _ => data.insert(position, rng.next_u64() as u8),
+        }
+    }
+    data.truncate(MAX_INPUT);
+}
+
+pub struct Crash {
+    pub message: String,
+    pub input: Vec<u8>,
+    pub path: String,
+}
+
+fn values(
+    options: &Options,
+    rng: &Rng,
+    corpus: &[Vec<u8>],
+    crashes: &[Crash],
+) -> Vec<(&'static str, String)> {
+    let mut values = vec![
+        ("seed", options.seed.to_string()),
+        ("rng", rng.state().to_string()),
+    ];
+    for input in corpus {
+        values.push(("corpus", hex::encode(input)));
+    }
+    for crash in crashes {
+        values.push((
+            "crash",
+            format!(
+                "{} {} {}",
+                crash.path,
+                hex::encode(&crash.input),
+                hex::encode(&crash.message)
+            ),
+        ));
+    }
+    values
+}
+
+pub fn fuzz(options: &Options, corpus: &[Vec<u8>], checkpoint: &Checkpoint) -> Vec<Crash> {
+    let saved = &checkpoint.saved;
+    let mut rng = Rng::new(
+        saved
+            .value("rng")
+            .and_then(|state| state.parse().ok())
+            .unwrap_or(options.seed),
+    );
+    let mut crashes: Vec<Crash> = saved
+        .values("crash")
+        .filter_map(|crash| {
+            let mut fields = crash.split(' ');
+            Some(Crash {
+                path: fields.next()?.to_string(),
+                input: hex::decode(fields.next()?).ok()?,
+                message: String::from_utf8(hex::decode(fields.next()?).ok()?).ok()?,
+            })
+        })
+        .collect();
+    let mut seen: BTreeSet<String> = crashes.iter().map(|c| c.message.clone()).collect();
+    let progress = checkpoint.progress(options.count);
+    let saved: Vec<Vec<u8>> = saved
+        .values("corpus")
+        .filter_map(|input| hex::decode(input).ok())
+        .collect();
+    let empty = [Vec::new()];
+    let corpus = match (corpus.is_empty(), saved.is_empty()) {
+        (false, _) => corpus,
+        (true, false) => &saved[..],
+        (true, true) => &empty[..],
+    };
+    for i in checkpoint.explored()..options.count {
+        let mut data = corpus[rng.next_u64() as usize % corpus.len()].clone();
+        mutate(&mut rng, &mut data);
+        if let Err(message) = catch_panic(|| run_target(&options.target, &data)) {
+            if seen.insert(message.clone()) {
+                let path = format!("crash-{}-{:016x}", options.target, fnv1a(&data));
+                if let Err(e) = fs::write(&path, &data) {
+                    eprintln!("Could not write {}: {}", path, e);
+                }
+                crashes.push(Crash {
+                    message,
+                    input: data,
+                    path,
+                });
+                progress.offer(crashes.len(), || {
+                    format!("{} distinct panic(s)", crashes.len())
+                });
+            }
+        }
+        progress.step();
+        if (i + 1) % checkpoint.every() == 0 {
+            checkpoint.explore(0..i + 1);
+            checkpoint.save(&progress, &values(options, &rng, corpus, &crashes));
+        }
+    }
+    progress.finish();
+    checkpoint.finish();
+    crashes
+}
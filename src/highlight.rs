@@ -0,0 +1,76 @@
+use crate::source::{mnemonic_doc, parse_number, parse_register};
+
+#[derive(Clone, Copy)]
+pub enum TokenKind {
+    Mnemonic,
+    Register,
+    Immediate,
+    Label,
+    Comment,
+    Plain,
+}
+
+impl TokenKind {
+    fn color(self) -> Option<&'static str> {
+        match self {
+            TokenKind::Mnemonic => Some("\x1b[1;34m"),
+            TokenKind::Register => Some("\x1b[32m"),
+            TokenKind::Immediate => Some("\x1b[33m"),
+            TokenKind::Label => Some("\x1b[35m"),
+            TokenKind::Comment => Some("\x1b[90m"),
+            TokenKind::Plain => None,
+        }
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn classify(word: &str) -> TokenKind {
+    if mnemonic_doc(word).is_some() {
+        TokenKind::Mnemonic
+    } else if parse_register(word).is_some() {
+        TokenKind::Register
+    } else if parse_number(word).is_some() {
+        TokenKind::Immediate
+    } else {
+        TokenKind::Label
+    }
+}
+
+pub fn tokenize(line: &str) -> Vec<(TokenKind, &str)> {
+    let mut tokens = Vec::new();
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let end = if c == ';' {
+            tokens.push((TokenKind::Comment, rest));
+            break;
+        } else if is_word(c) {
+            let end = rest.find(|c| !is_word(c)).unwrap_or(rest.len());
+            tokens.push((classify(&rest[..end]), &rest[..end]));
+            end
+        } else {
+            let end = rest.find(|c| is_word(c) || c == ';').unwrap_or(rest.len());
+            tokens.push((TokenKind::Plain, &rest[..end]));
+            end
+        };
+        rest = &rest[end..];
+    }
+    tokens
+}
+
+pub fn highlight(line: &str) -> String {
+    let mut output = String::with_capacity(line.len() * 2);
+    for (kind, text) in tokenize(line) {
+        match kind.color() {
+            Some(color) => {
+                output.push_str(color);
+                output.push_str(text);
+                output.push_str("\x1b[0m");
+            }
+            None => output.push_str(text),
+        }
+    }
+    output
+}
@@ -0,0 +1,181 @@
+use crate::emulator::State;
+use crate::parser::{decode_instruction, encode_instruction, Instruction, Operand};
+use std::collections::BTreeSet;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Location {
+    Register(usize),
+    Memory(usize),
+    Flagg,
+}
+
+pub struct Step {
+    pub pc: usize,
+    pub cycles: usize,
+    pub op: u16,
+    pub pointer: usize,
+    pub flagg: bool,
+    pub registers: [u8; 16],
+}
+
+impl Step {
+    pub fn capture(state: &State) -> Step {
+        let mut registers = [0; 16];
+        for (r, value) in registers.iter_mut().enumerate() {
+            *value = state.register(r);
+        }
+        Step {
+            pc: state.pc(),
+            cycles: state.cycles(),
+            op: encode_instruction(&state.next_instruction()),
+            pointer: state.pointer(),
+            flagg: state.flagg(),
+            registers,
+        }
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        decode_instruction(self.op)
+    }
+
+    pub fn differences(&self, other: &Step) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.pc != other.pc {
+            differences.push(format!(
+                "pc: expected {:#06x}, got {:#06x}",
+                self.pc, other.pc
+            ));
+        }
+        if self.op != other.op {
+            differences.push(format!(
+                "instruction: expected {}, got {}",
+                self.instruction(),
+                other.instruction()
+            ));
+        }
+        if self.cycles != other.cycles {
+            differences.push(format!(
+                "cycles: expected {}, got {}",
+                self.cycles, other.cycles
+            ));
+        }
+        if self.flagg != other.flagg {
+            differences.push(format!(
+                "flagg: expected {}, got {}",
+                self.flagg, other.flagg
+            ));
+        }
+        for r in 0..16 {
+            if self.registers[r] != other.registers[r] {
+                differences.push(format!(
+                    "r{}: expected {:02x}h, got {:02x}h",
+                    r, self.registers[r], other.registers[r]
+                ));
+            }
+        }
+        differences
+    }
+
+    fn defs(&self) -> Vec<Location> {
+        match self.instruction() {
+            Instruction::SettImmediate(r, _)
+            | Instruction::Sett(r, _)
+            | Instruction::ALE(_, r, _)
+            | Instruction::Les(r)
+            | Instruction::Last(r) => vec![Location::Register(usize::from(r))],
+            Instruction::Sammenligne(..) => vec![Location::Flagg],
+            Instruction::Finn(_) => vec![Location::Register(0), Location::Register(1)],
+            Instruction::Lagr(_) => vec![Location::Memory(self.pointer)],
+            _ => Vec::new(),
+        }
+    }
+
+    fn uses(&self) -> Vec<Location> {
+        let mut uses: Vec<Location> = self
+            .instruction()
+            .reads()
+            .filter_map(|operand| match operand {
+                Operand::Reg(r) => Some(Location::Register(usize::from(r))),
+                _ => None,
+            })
+            .collect();
+        match self.instruction() {
+            Instruction::ALE(_, r, _) => uses.push(Location::Register(usize::from(r))),
+            Instruction::Last(_) => uses.push(Location::Memory(self.pointer)),
+            Instruction::BHopp(_) => uses.push(Location::Flagg),
+            _ => (),
+        }
+        uses
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    pub steps: Vec<Step>,
+}
+
+impl History {
+    pub fn record(&mut self, state: &State) {
+        let seen = self
+            .steps
+            .last()
+            .is_some_and(|s| s.cycles == state.cycles() && s.pc == state.pc());
+        if seen || state.halted().is_some() {
+            return;
+        }
+        self.steps.push(Step::capture(state));
+    }
+
+    pub fn rewind(&mut self, cycles: usize) {
+        let kept = self.steps.partition_point(|s| s.cycles < cycles);
+        self.steps.truncate(kept);
+    }
+
+    pub fn who_wrote(&self, address: usize, before: usize) -> Option<(&Step, usize)> {
+        self.steps
+            .iter()
+            .rev()
+            .filter(|s| s.cycles < before && s.pointer == address)
+            .find_map(|s| match s.instruction() {
+                Instruction::Lagr(r) => Some((s, usize::from(r))),
+                _ => None,
+            })
+    }
+
+    pub fn backward_slice(&self, location: Location, cycles: usize) -> Vec<&Step> {
+        let mut live = BTreeSet::from([location]);
+        let mut slice: Vec<&Step> = Vec::new();
+        for step in self.steps.iter().rev().filter(|s| s.cycles < cycles) {
+            let defs = step.defs();
+            if defs.iter().any(|d| live.contains(d)) {
+                for def in defs {
+                    live.remove(&def);
+                }
+                live.extend(step.uses());
+                slice.push(step);
+            }
+        }
+        slice.reverse();
+        slice
+    }
+
+    pub fn forward_slice(&self, location: Location, from: usize, to: usize) -> Vec<&Step> {
+        let mut tainted = BTreeSet::from([location]);
+        let mut slice = Vec::new();
+        for step in self
+            .steps
+            .iter()
+            .filter(|s| from <= s.cycles && s.cycles < to)
+        {
+            if step.uses().iter().any(|u| tainted.contains(u)) {
+                tainted.extend(step.defs());
+                slice.push(step);
+            } else {
+                for def in step.defs() {
+                    tainted.remove(&def);
+                }
+            }
+        }
+        slice
+    }
+}
@@ -0,0 +1,132 @@
+use crate::parser::MAGIC;
+use serde_json::Value;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Default)]
+pub struct Bundle {
+    pub program: Option<Vec<u8>>,
+    pub inputs: Vec<Vec<u8>>,
+}
+
+fn base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+fn decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    let text = text.rsplit_once("base64,").map_or(text, |(_, data)| data);
+    let compact: String = text.split_whitespace().collect();
+    hex::decode(&compact).ok().or_else(|| base64(&compact))
+}
+
+fn is_input(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["føde", "fode", "input", "stdin"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+impl Bundle {
+    fn add(&mut self, name: &str, bytes: Vec<u8>) {
+        if bytes.starts_with(MAGIC) {
+            if self.program.is_none() {
+                self.program = Some(bytes);
+            }
+            return;
+        }
+        if let Ok(text) = std::str::from_utf8(&bytes) {
+            if let Ok(json) = serde_json::from_str::<Value>(text) {
+                self.add_json(name, &json);
+                return;
+            }
+            if let Some(decoded) = decode(text).filter(|d| d.starts_with(MAGIC)) {
+                self.add(name, decoded);
+                return;
+            }
+        }
+        if is_input(name) {
+            self.inputs.push(bytes);
+        }
+    }
+
+    fn add_json(&mut self, name: &str, value: &Value) {
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    self.add_json(key, value);
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    self.add_json(name, value);
+                }
+            }
+            Value::String(text) => match decode(text) {
+                Some(bytes) if bytes.starts_with(MAGIC) => self.add(name, bytes),
+                _ if is_input(name) => self
+                    .inputs
+                    .push(hex::decode(text.trim()).unwrap_or_else(|_| text.as_bytes().to_vec())),
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+}
+
+pub fn import(path: &str) -> Result<Bundle, String> {
+    let mut bundle = Bundle::default();
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    if bytes.starts_with(b"PK") {
+        let mut zip =
+            zip::ZipArchive::new(std::io::Cursor::new(bytes)).map_err(|e| e.to_string())?;
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).map_err(|e| e.to_string())?;
+            if file.is_dir() {
+                continue;
+            }
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).map_err(|e| e.to_string())?;
+            let name = file.name().to_string();
+            bundle.add(&name, contents);
+        }
+    } else {
+        bundle.add(path, bytes);
+    }
+    if bundle.program.is_none() {
+        return Err(String::from(
+            "found no SLEDE8 program, raw or encoded as hex or base64",
+        ));
+    }
+    Ok(bundle)
+}
+
+pub fn default_directory(path: &str) -> String {
+    Path::new(path).file_stem().map_or_else(
+        || String::from("challenge"),
+        |s| s.to_string_lossy().into_owned(),
+    )
+}
@@ -0,0 +1,127 @@
+use crate::emulator::State;
+use crate::liveness::{self, mask};
+use crate::parser::{Instruction, MEMORY_SIZE};
+use std::collections::BTreeMap;
+
+#[derive(Default)]
+pub struct Signature {
+    pub arguments: u16,
+    pub results: u16,
+    pub calls: usize,
+}
+
+impl Signature {
+    pub fn prototype(&self, name: &str) -> String {
+        let arguments = match liveness::registers(self.arguments).as_str() {
+            "-" => String::new(),
+            registers => registers.to_string(),
+        };
+        let results = match liveness::registers(self.results).as_str() {
+            "-" => String::from("()"),
+            registers => registers.to_string(),
+        };
+        let seen = match self.calls {
+            0 => String::from("never called in the runs"),
+            1 => String::from("1 call observed"),
+            n => format!("{} calls observed", n),
+        };
+        format!("{}({}) -> {}  ({})", name, arguments, results, seen)
+    }
+}
+
+struct Frame {
+    entry: usize,
+    read: u16,
+    written: u16,
+    pending: [Option<usize>; 16],
+}
+
+impl Frame {
+    fn new(entry: usize) -> Frame {
+        Frame {
+            entry,
+            read: 0,
+            written: 0,
+            pending: [None; 16],
+        }
+    }
+
+    fn read(&mut self, registers: u16, signatures: &mut BTreeMap<usize, Signature>) {
+        self.read |= registers & !self.written;
+        for r in (0..16).filter(|r| registers & 1 << r != 0) {
+            if let Some(callee) = self.pending[r].take() {
+                signatures.entry(callee).or_default().results |= 1 << r;
+            }
+        }
+    }
+}
+
+fn observe(
+    memory: [u8; MEMORY_SIZE],
+    stdin: &[u8],
+    max_cycles: usize,
+    signatures: &mut BTreeMap<usize, Signature>,
+) {
+    let mut state = State::new(memory, stdin.to_vec());
+    let mut stack = vec![Frame::new(0)];
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        let instruction = state.next_instruction();
+        if !state.can_execute() {
+            break;
+        }
+        let top = stack.len() - 1;
+        match instruction {
+            Instruction::Tur(target) => {
+                signatures.entry(usize::from(target)).or_default().calls += 1;
+                stack.push(Frame::new(usize::from(target)));
+            }
+            Instruction::Retur if stack.len() > 1 => {
+                let callee = stack.pop().expect("checked above");
+                let signature = signatures.entry(callee.entry).or_default();
+                signature.arguments |= callee.read;
+                let caller = stack.last_mut().expect("checked above");
+                caller.read(callee.read, signatures);
+                caller.written |= callee.written;
+                for r in (0..16).filter(|r| callee.written & 1 << r != 0) {
+                    caller.pending[r] = Some(callee.entry);
+                }
+            }
+            _ => {
+                stack[top].read(mask(instruction.reads()), signatures);
+                let writes = mask(instruction.writes());
+                stack[top].written |= writes;
+                for r in (0..16).filter(|r| writes & 1 << r != 0) {
+                    stack[top].pending[r] = None;
+                }
+            }
+        }
+        state.iter().next();
+    }
+    for frame in stack.iter().skip(1) {
+        signatures.entry(frame.entry).or_default().arguments |= frame.read;
+    }
+}
+
+pub fn infer(
+    memory: [u8; MEMORY_SIZE],
+    corpus: &[Vec<u8>],
+    max_cycles: usize,
+) -> BTreeMap<usize, Signature> {
+    let mut observed = BTreeMap::new();
+    for stdin in corpus {
+        observe(memory, stdin, max_cycles, &mut observed);
+    }
+    liveness::analyze(&memory)
+        .into_iter()
+        .filter(|&(entry, _)| entry != 0)
+        .map(|(entry, summary)| {
+            let seen = observed.remove(&entry).unwrap_or_default();
+            let signature = Signature {
+                arguments: summary.inputs | seen.arguments,
+                results: summary.outputs() | seen.results,
+                calls: seen.calls,
+            };
+            (entry, signature)
+        })
+        .collect()
+}
@@ -0,0 +1,59 @@
+use crate::parser::{
+    decode_at, encode_instruction, memory_image, Instruction, Operand, MEMORY_SIZE,
+};
+
+pub struct Probe {
+    pub address: usize,
+    pub registers: Vec<usize>,
+}
+
+fn push(image: &mut Vec<u8>, instruction: &Instruction) {
+    image.extend_from_slice(&encode_instruction(instruction).to_le_bytes());
+}
+
+fn patch(image: &mut [u8], pc: usize, instruction: &Instruction) {
+    image[pc..pc + 2].copy_from_slice(&encode_instruction(instruction).to_le_bytes());
+}
+
+pub fn instrument(program: &[u8], probes: &[Probe]) -> Result<Vec<u8>, String> {
+    let memory = memory_image(program);
+    let mut image = program.to_vec();
+    if !image.len().is_multiple_of(2) {
+        image.push(0);
+    }
+
+    for (i, probe) in probes.iter().enumerate() {
+        let pc = probe.address;
+        if !pc.is_multiple_of(2) || pc + 1 >= program.len() {
+            return Err(format!("{:#06x} is not an instruction in the program", pc));
+        }
+        if probes[..i].iter().any(|p| p.address == pc) {
+            return Err(format!("{:#06x} is instrumented twice", pc));
+        }
+        let stub = image.len();
+        for &register in &probe.registers {
+            push(
+                &mut image,
+                &Instruction::Skriv(Operand::Reg(register as u8)),
+            );
+        }
+        push(&mut image, &decode_at(&memory, pc));
+        push(
+            &mut image,
+            &Instruction::Hopp(Operand::Addr((pc + 2) as u16)),
+        );
+        if image.len() > MEMORY_SIZE {
+            return Err(format!(
+                "no room for the stub of {:#06x} ({} bytes past the end of memory)",
+                pc,
+                image.len() - MEMORY_SIZE
+            ));
+        }
+        patch(
+            &mut image,
+            pc,
+            &Instruction::Hopp(Operand::Addr(stub as u16)),
+        );
+    }
+    Ok(image)
+}
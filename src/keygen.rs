@@ -0,0 +1,436 @@
+use crate::emulator::State;
+use crate::parser::Operand::{Addr, Imm, Reg};
+use crate::parser::{decode_at, ALEOp, CMPOp, Instruction, MEMORY_SIZE};
+use crate::serial::SerialCheck;
+use std::fmt::Write;
+
+const MAX_BYTES: usize = 256;
+
+pub struct Snapshot {
+    pub registers: [u8; 16],
+    checkpoint: String,
+}
+
+pub struct Model {
+    pub header: usize,
+    pub load: usize,
+    pub register: usize,
+    pub steps: Vec<(usize, Instruction)>,
+    pub compare: (usize, Instruction),
+    pub branch: usize,
+    pub want: bool,
+    pub memory: Vec<u8>,
+    pub snapshots: Vec<Snapshot>,
+    pub key: Vec<u8>,
+}
+
+fn observe(
+    memory: [u8; MEMORY_SIZE],
+    input: &[u8],
+    load: usize,
+    max_cycles: usize,
+) -> Vec<Snapshot> {
+    let mut state = State::new(memory, input.to_vec());
+    let mut snapshots = Vec::new();
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        if state.pc() == load {
+            snapshots.push(Snapshot {
+                registers: std::array::from_fn(|r| state.register(r)),
+                checkpoint: state.checkpoint(),
+            });
+        }
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+    }
+    snapshots
+}
+
+fn flagg(
+    memory: [u8; MEMORY_SIZE],
+    snapshot: &Snapshot,
+    c: u8,
+    compare: usize,
+    max_cycles: usize,
+) -> Option<bool> {
+    let mut state = State::new(memory, vec![c]);
+    state
+        .load_checkpoint(&format!("{} stdin=0", snapshot.checkpoint))
+        .ok()?;
+    let limit = state.cycles() + max_cycles;
+    while state.pc() != compare + 2 {
+        if state.cycles() >= limit
+            || !matches!(state.iter().next(), Some(Ok(_)))
+            || state.halted().is_some()
+        {
+            return None;
+        }
+    }
+    Some(state.flagg())
+}
+
+pub fn extract(
+    memory: [u8; MEMORY_SIZE],
+    check: &SerialCheck,
+    max_cycles: usize,
+) -> Result<Model, String> {
+    if !check.reads_input {
+        return Err(String::from(
+            "the check compares bytes loaded with LAST, only checks that read input with LES are supported",
+        ));
+    }
+    let mut steps = Vec::new();
+    let mut pc = check.compare;
+    let (load, register) = loop {
+        pc = pc
+            .checked_sub(2)
+            .filter(|&pc| pc >= check.header)
+            .ok_or("no LES in the straight line code before the comparison")?;
+        match decode_at(&memory, pc) {
+            Instruction::Les(r) => break (pc, usize::from(r)),
+            Instruction::Hopp(_)
+            | Instruction::BHopp(_)
+            | Instruction::Tur(_)
+            | Instruction::Retur => {
+                return Err(format!(
+                    "the code between the input and the comparison jumps at {:#06x}",
+                    pc
+                ))
+            }
+            Instruction::Sammenligne(..) | Instruction::Nope => (),
+            instruction => steps.insert(0, (pc, instruction)),
+        }
+    };
+
+    let want = !check.exits_on_branch;
+    let mut key = Vec::new();
+    let mut snapshots = observe(memory, &key, load, max_cycles);
+    while key.len() < MAX_BYTES && snapshots.len() > key.len() {
+        let snapshot = &snapshots[key.len()];
+        match (0..=255u8)
+            .find(|&c| flagg(memory, snapshot, c, check.compare, max_cycles) == Some(want))
+        {
+            Some(c) => key.push(c),
+            None => break,
+        }
+        snapshots = observe(memory, &key, load, max_cycles);
+    }
+    snapshots.truncate(key.len());
+    if key.is_empty() {
+        return Err(String::from("no byte passes the first comparison"));
+    }
+    Ok(Model {
+        header: check.header,
+        load,
+        register,
+        steps,
+        compare: (check.compare, decode_at(&memory, check.compare)),
+        branch: check.branch,
+        want,
+        memory: memory[..memory.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1)].to_vec(),
+        snapshots,
+        key,
+    })
+}
+
+fn python_step(instruction: &Instruction) -> Option<String> {
+    Some(match *instruction {
+        Instruction::SettImmediate(Reg(ra), Imm(value)) => format!("r[{}] = {:#04x}", ra, value),
+        Instruction::Sett(Reg(ra), Reg(rb)) => format!("r[{}] = r[{}]", ra, rb),
+        Instruction::Finn(Addr(address)) => {
+            format!(
+                "r[0], r[1] = {:#04x}, {:#04x}",
+                address & 0xff,
+                address >> 8
+            )
+        }
+        Instruction::Last(Reg(r)) => format!("r[{}] = MEMORY[(r[1] << 8 | r[0]) & 0xfff]", r),
+        Instruction::ALE(op, Reg(ra), Reg(rb)) => match op {
+            ALEOp::Og => format!("r[{}] &= r[{}]", ra, rb),
+            ALEOp::Eller => format!("r[{}] |= r[{}]", ra, rb),
+            ALEOp::XEller => format!("r[{}] ^= r[{}]", ra, rb),
+            ALEOp::Vskift => format!("r[{0}] = (r[{0}] << r[{1}]) & 0xff", ra, rb),
+            ALEOp::Hskift => format!("r[{0}] = r[{0}] >> r[{1}]", ra, rb),
+            ALEOp::Pluss => format!("r[{0}] = (r[{0}] + r[{1}]) & 0xff", ra, rb),
+            ALEOp::Minus => format!("r[{0}] = (r[{0}] - r[{1}]) & 0xff", ra, rb),
+        },
+        Instruction::Sammenligne(_, Reg(ra), Reg(rb)) => {
+            format!("return r[{}] {} r[{}]", ra, operator(instruction), rb)
+        }
+        _ => return None,
+    })
+}
+
+fn rust_step(instruction: &Instruction) -> Option<String> {
+    Some(match *instruction {
+        Instruction::SettImmediate(Reg(ra), Imm(value)) => format!("r[{}] = {:#04x};", ra, value),
+        Instruction::Sett(Reg(ra), Reg(rb)) => format!("r[{}] = r[{}];", ra, rb),
+        Instruction::Finn(Addr(address)) => format!(
+            "r[0] = {:#04x};\n    r[1] = {:#04x};",
+            address & 0xff,
+            address >> 8
+        ),
+        Instruction::Last(Reg(r)) => format!(
+            "r[{}] = MEMORY.get((usize::from(r[1]) << 8 | usize::from(r[0])) & 0xfff).copied().unwrap_or(0);",
+            r
+        ),
+        Instruction::ALE(op, Reg(ra), Reg(rb)) => match op {
+            ALEOp::Og => format!("r[{}] &= r[{}];", ra, rb),
+            ALEOp::Eller => format!("r[{}] |= r[{}];", ra, rb),
+            ALEOp::XEller => format!("r[{}] ^= r[{}];", ra, rb),
+            ALEOp::Vskift => format!(
+                "r[{0}] = r[{0}].checked_shl(u32::from(r[{1}])).unwrap_or(0);",
+                ra, rb
+            ),
+            ALEOp::Hskift => format!(
+                "r[{0}] = r[{0}].checked_shr(u32::from(r[{1}])).unwrap_or(0);",
+                ra, rb
+            ),
+            ALEOp::Pluss => format!("r[{0}] = r[{0}].wrapping_add(r[{1}]);", ra, rb),
+            ALEOp::Minus => format!("r[{0}] = r[{0}].wrapping_sub(r[{1}]);", ra, rb),
+        },
+        Instruction::Sammenligne(_, Reg(ra), Reg(rb)) => {
+            format!("r[{}] {} r[{}]", ra, operator(instruction), rb)
+        }
+        _ => return None,
+    })
+}
+
+fn operator(instruction: &Instruction) -> &'static str {
+    match instruction {
+        Instruction::Sammenligne(CMPOp::Lik, ..) => "==",
+        Instruction::Sammenligne(CMPOp::Ulik, ..) => "!=",
+        Instruction::Sammenligne(CMPOp::Me, ..) => "<",
+        Instruction::Sammenligne(CMPOp::Mel, ..) => "<=",
+        Instruction::Sammenligne(CMPOp::Se, ..) => ">",
+        _ => ">=",
+    }
+}
+
+impl Model {
+    fn uses_memory(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|(_, i)| matches!(i, Instruction::Last(_)))
+    }
+
+    fn want_comment(&self) -> String {
+        format!(
+            "the BHOPP at {:#06x} {} the loop when the comparison holds",
+            self.branch,
+            if self.want { "stays in" } else { "leaves" }
+        )
+    }
+
+    pub fn python(&self) -> String {
+        let mut script = String::from("#!/usr/bin/env python3\n");
+        let _ = writeln!(
+            script,
+            "# Keygen for the serial check loop at {:#06x}.\n# STATE[i] holds the registers when the LES at {:#06x} reads key byte i.\n",
+            self.header, self.load
+        );
+        if self.uses_memory() {
+            script.push_str("MEMORY = bytes.fromhex(\n");
+            for chunk in self.memory.chunks(32) {
+                let _ = writeln!(script, "    \"{}\"", hex::encode(chunk));
+            }
+            script.push_str(").ljust(4096, b\"\\0\")\n\n");
+        }
+        script.push_str("STATE = [\n");
+        for snapshot in &self.snapshots {
+            let registers: Vec<String> = snapshot
+                .registers
+                .iter()
+                .map(|r| format!("{:#04x}", r))
+                .collect();
+            let _ = writeln!(script, "    [{}],", registers.join(", "));
+        }
+        let _ = writeln!(
+            script,
+            "]\n\n# {}\nWANT = {}\n\n\ndef check(state, c):\n    r = list(state)\n    r[{}] = c  # {:#06x}: LES r{}",
+            self.want_comment(),
+            if self.want { "True" } else { "False" },
+            self.register,
+            self.load,
+            self.register
+        );
+        for (pc, instruction) in self.steps.iter().chain(std::iter::once(&self.compare)) {
+            match python_step(instruction) {
+                Some(line) => {
+                    let _ = writeln!(
+                        script,
+                        "    {}  # {:#06x}: {}",
+                        line,
+                        pc,
+                        instruction.to_string().replace('\t', " ")
+                    );
+                }
+                None => {
+                    let _ = writeln!(
+                        script,
+                        "    # TODO {:#06x}: {}",
+                        pc,
+                        instruction.to_string().replace('\t', " ")
+                    );
+                }
+            }
+        }
+        script.push_str(
+            "\n\nkey = bytes(next(c for c in range(256) if check(s, c) == WANT) for s in STATE)\nprint(key)\n",
+        );
+        script
+    }
+
+    pub fn rust(&self) -> String {
+        let mut script = String::new();
+        let _ = writeln!(
+            script,
+            "// Keygen for the serial check loop at {:#06x}.\n// STATE[i] holds the registers when the LES at {:#06x} reads key byte i.\n",
+            self.header, self.load
+        );
+        if self.uses_memory() {
+            script.push_str("const MEMORY: &[u8] = &[\n");
+            for chunk in self.memory.chunks(16) {
+                let bytes: Vec<String> = chunk.iter().map(|b| format!("{:#04x}", b)).collect();
+                let _ = writeln!(script, "    {},", bytes.join(", "));
+            }
+            script.push_str("];\n\n");
+        }
+        let _ = writeln!(
+            script,
+            "const STATE: [[u8; 16]; {}] = [",
+            self.snapshots.len()
+        );
+        for snapshot in &self.snapshots {
+            let registers: Vec<String> = snapshot
+                .registers
+                .iter()
+                .map(|r| format!("{:#04x}", r))
+                .collect();
+            let _ = writeln!(script, "    [{}],", registers.join(", "));
+        }
+        let _ = writeln!(
+            script,
+            "];\n\n// {}\nconst WANT: bool = {};\n\nfn check(state: &[u8; 16], c: u8) -> bool {{\n    let mut r = *state;\n    // {:#06x}: LES r{}\n    r[{}] = c;",
+            self.want_comment(),
+            self.want,
+            self.load,
+            self.register,
+            self.register
+        );
+        for (pc, instruction) in self.steps.iter().chain(std::iter::once(&self.compare)) {
+            let _ = writeln!(
+                script,
+                "    // {:#06x}: {}",
+                pc,
+                instruction.to_string().replace('\t', " ")
+            );
+            match rust_step(instruction) {
+                Some(line) => {
+                    let _ = writeln!(script, "    {}", line);
+                }
+                None if *pc == self.compare.0 => {
+                    let _ = writeln!(
+                        script,
+                        "    todo!(\"translate the comparison at {:#06x}\")",
+                        pc
+                    );
+                }
+                None => script.push_str("    // TODO\n"),
+            }
+        }
+        script.push_str(
+            "}\n\nfn main() {\n    let key: Vec<u8> = STATE\n        .iter()\n        .map(|s| (0..=255).find(|&c| check(s, c) == WANT).expect(\"no byte passes the check\"))\n        .collect();\n    println!(\"{}\", key.escape_ascii());\n}\n",
+        );
+        script
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble_fragment;
+    use crate::linker::{link, Module};
+    use crate::parser::{encode_instruction, memory_image};
+    use crate::serial::find_serial_checks;
+
+    fn model(source: &str) -> Model {
+        let fragment = assemble_fragment(source).unwrap_or_else(|_| panic!("bad test source"));
+        let program = link(&[Module {
+            name: String::from("test"),
+            fragment,
+        }])
+        .unwrap_or_else(|e| panic!("{}", e.join("\n")));
+        let memory = memory_image(&program);
+        let checks = find_serial_checks(&memory);
+        assert_eq!(checks.len(), 1);
+        extract(memory, &checks[0], 1000).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    const CHECK: &str = "
+        SETT r4, 0
+        SETT r5, 0x13
+        SETT r6, 1
+        SETT r7, 2
+    loop:
+        LES r2
+        XELLER r2, r5
+        SETT r3, 0x72
+        ULIK r2, r3
+        BHOPP fail
+        PLUSS r4, r6
+        LIK r4, r7
+        BHOPP win
+        HOPP loop
+    fail:
+        STOPP
+    win:
+        STOPP
+    ";
+
+    #[test]
+    fn scripts_translate_each_step() {
+        let model = model(CHECK);
+        assert_eq!(model.key, b"aa");
+        let python = model.python();
+        for line in [
+            "    r[2] = c  # 0x0008: LES r2",
+            "    r[2] ^= r[5]  # 0x000a: XELLER r2, r5",
+            "    r[3] = 0x72  # 0x000c: SETT r3, 114",
+            "    return r[2] != r[3]  # 0x000e: ULIK r2, r3",
+            "WANT = False",
+        ] {
+            assert!(python.lines().any(|l| l == line), "{}\n{}", line, python);
+        }
+        let rust = model.rust();
+        for line in [
+            "    r[2] = c;",
+            "    r[2] ^= r[5];",
+            "    r[3] = 0x72;",
+            "    r[2] != r[3]",
+            "const WANT: bool = false;",
+        ] {
+            assert!(rust.lines().any(|l| l == line), "{}\n{}", line, rust);
+        }
+    }
+
+    #[test]
+    fn untranslated_comparison_still_builds() {
+        let mut model = model(CHECK);
+        model.compare.1 = Instruction::Skriv(Reg(2));
+        let rust = model.rust();
+        assert!(rust.contains("    todo!(\"translate the comparison at 0x000e\")\n}"));
+    }
+
+    #[test]
+    fn candidate_that_loops_forever() {
+        let program: Vec<u8> = [Instruction::Les(Reg(2)), Instruction::Hopp(Addr(2))]
+            .iter()
+            .flat_map(|i| encode_instruction(i).to_le_bytes())
+            .collect();
+        let memory = memory_image(&program);
+        let snapshots = observe(memory, &[], 0, 100);
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(flagg(memory, &snapshots[0], b'a', 0x100, 1000), None);
+    }
+}
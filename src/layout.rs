@@ -0,0 +1,153 @@
+use crate::emulator::State;
+use crate::parser::{decode_at, memory_image, reachable, Instruction, MEMORY_SIZE};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Code,
+    Written,
+    Table,
+    Data,
+    Free,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Kind::Code => "code",
+            Kind::Written => "written at runtime",
+            Kind::Table => "table",
+            Kind::Data => "data",
+            Kind::Free => "free",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub struct Range {
+    pub start: usize,
+    pub end: usize,
+    pub kind: Kind,
+}
+
+pub fn written_addresses(state: &mut State, max_steps: usize) -> Vec<usize> {
+    let mut written = Vec::new();
+    let mut steps = 0;
+    while state.halted().is_none() && steps < max_steps && state.can_execute() {
+        if let Instruction::Lagr(_) = state.next_instruction() {
+            let address = (usize::from(state.register(1)) << 8 | usize::from(state.register(0)))
+                & (MEMORY_SIZE - 1);
+            written.push(address);
+        }
+        if state.execute().is_err() {
+            break;
+        }
+        steps += 1;
+    }
+    written
+}
+
+pub fn layout(program: &[u8], written: &[usize]) -> Vec<Range> {
+    let memory = memory_image(program);
+    let mut kinds = [Kind::Free; MEMORY_SIZE];
+    kinds[..program.len()].fill(Kind::Data);
+    for &address in written {
+        kinds[address] = Kind::Written;
+    }
+    for pc in reachable(&memory) {
+        if pc + 1 < program.len() {
+            kinds[pc] = Kind::Code;
+            kinds[pc + 1] = Kind::Code;
+        }
+    }
+
+    let mut ranges: Vec<Range> = Vec::new();
+    for (address, &kind) in kinds.iter().enumerate() {
+        match ranges.last_mut() {
+            Some(range) if range.kind == kind => range.end = address + 1,
+            _ => ranges.push(Range {
+                start: address,
+                end: address + 1,
+                kind,
+            }),
+        }
+    }
+    ranges
+}
+
+pub struct Segment {
+    pub start: usize,
+    pub end: usize,
+    pub kind: Kind,
+    pub label: Option<String>,
+}
+
+pub fn segments(
+    program: &[u8],
+    ranges: &[Range],
+    labels: &BTreeMap<usize, String>,
+) -> Vec<Segment> {
+    let memory = memory_image(program);
+    let tables: BTreeSet<usize> = reachable(&memory)
+        .into_iter()
+        .filter_map(|pc| match decode_at(&memory, pc) {
+            Instruction::Finn(a) => Some(usize::from(a)),
+            _ => None,
+        })
+        .collect();
+    let mut segments = Vec::new();
+    for range in ranges {
+        let mut starts: BTreeSet<usize> = labels
+            .range(range.start..range.end)
+            .map(|(&a, _)| a)
+            .collect();
+        if range.kind == Kind::Data {
+            starts.extend(tables.range(range.start..range.end));
+        }
+        starts.insert(range.start);
+        let mut kind = range.kind;
+        let ends = starts
+            .iter()
+            .skip(1)
+            .copied()
+            .chain(std::iter::once(range.end));
+        for (start, end) in starts.iter().copied().zip(ends) {
+            if tables.contains(&start) && range.kind == Kind::Data {
+                kind = Kind::Table;
+            }
+            segments.push(Segment {
+                start,
+                end,
+                kind,
+                label: labels.get(&start).cloned(),
+            });
+        }
+    }
+    segments
+}
+
+pub fn overview(ranges: &[Range], columns: usize) -> String {
+    let cell = MEMORY_SIZE / columns;
+    (0..columns)
+        .map(|i| {
+            let mut counts = [0; 4];
+            for range in ranges {
+                let overlap = range
+                    .end
+                    .min((i + 1) * cell)
+                    .saturating_sub(range.start.max(i * cell));
+                counts[match range.kind {
+                    Kind::Code => 0,
+                    Kind::Written => 1,
+                    Kind::Table | Kind::Data => 2,
+                    Kind::Free => 3,
+                }] += overlap;
+            }
+            match (0..3).max_by_key(|&k| (counts[k], std::cmp::Reverse(k))) {
+                Some(k) if counts[k] > 0 => ['C', 'W', 'D'][k],
+                _ => '.',
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,22 @@
+//! The SLEDE8 decoder and emulator behind the `s8disasm` command line tool.
+//!
+//! [`disassemble`] turns a `.s8` file into a listing, [`decode_instruction`]
+//! decodes a single opcode into an [`Instruction`], and [`State`] runs a
+//! program: build one from [`parser::memory_image`] and the bytes LES should
+//! read, then call [`State::run`] or step it with [`State::iter`]. Solvers that
+//! try many inputs should load the program once with [`Program::new`] and call
+//! [`quick_run`], which reuses a machine per thread. It and [`State::run_result`]
+//! return a [`RunResult`] with the halt reason, cycles, wall time and input and
+//! output counts; `run_result` also keeps the executed instructions and the
+//! final machine.
+
+pub mod costs;
+pub mod emulator;
+pub mod error;
+pub mod memory;
+pub mod parser;
+pub mod quick;
+
+pub use emulator::{RunResult, State};
+pub use parser::{decode_instruction, disassemble, Instruction};
+pub use quick::{quick_run, Program};
@@ -0,0 +1,121 @@
+use crate::assembler::Fragment;
+use std::collections::HashMap;
+
+pub const IMAGE_SIZE: usize = 4096;
+
+pub struct Module {
+    pub name: String,
+    pub fragment: Fragment,
+}
+
+pub fn link(modules: &[Module]) -> Result<Vec<u8>, Vec<String>> {
+    let mut errors = Vec::new();
+
+    let mut bases = Vec::new();
+    let mut size = 0;
+    for module in modules {
+        size += size % 2;
+        bases.push(size);
+        size += module.fragment.code.len();
+    }
+    if size > IMAGE_SIZE {
+        errors.push(format!(
+            "linked image is {} bytes, which exceeds {} bytes",
+            size, IMAGE_SIZE
+        ));
+        return Err(errors);
+    }
+
+    let mut globals: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+    for (i, module) in modules.iter().enumerate() {
+        for (symbol, offset) in &module.fragment.symbols {
+            globals
+                .entry(symbol.as_str())
+                .or_default()
+                .push((i, bases[i] + offset));
+        }
+    }
+
+    let mut image = vec![0; size];
+    for (module, &base) in modules.iter().zip(&bases) {
+        image[base..base + module.fragment.code.len()].copy_from_slice(&module.fragment.code);
+    }
+
+    for (i, module) in modules.iter().enumerate() {
+        for relocation in &module.fragment.relocations {
+            let address = match module.fragment.symbols.get(&relocation.symbol) {
+                Some(offset) => bases[i] + offset,
+                None => match globals
+                    .get(relocation.symbol.as_str())
+                    .map(|d| d.as_slice())
+                {
+                    Some([(_, address)]) => *address,
+                    Some(definitions) => {
+                        let names: Vec<&str> = definitions
+                            .iter()
+                            .map(|(m, _)| modules[*m].name.as_str())
+                            .collect();
+                        errors.push(format!(
+                            "{}:{}: label '{}' is ambiguous, defined in {}",
+                            module.name,
+                            relocation.line,
+                            relocation.symbol,
+                            names.join(", ")
+                        ));
+                        continue;
+                    }
+                    None => {
+                        errors.push(format!(
+                            "{}:{}: undefined label '{}'",
+                            module.name, relocation.line, relocation.symbol
+                        ));
+                        continue;
+                    }
+                },
+            };
+            let position = bases[i] + relocation.offset;
+            let op = u16::from_le_bytes([image[position], image[position + 1]]);
+            let patched = (op & 0xf) | ((address as u16 & 0xfff) << 4);
+            image[position..position + 2].copy_from_slice(&patched.to_le_bytes());
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(image)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble_fragment;
+
+    fn module(name: &str, source: &str) -> Module {
+        Module {
+            name: name.to_string(),
+            fragment: assemble_fragment(source).unwrap_or_else(|_| panic!("bad test source")),
+        }
+    }
+
+    #[test]
+    fn modules_start_on_even_addresses() {
+        let image = link(&[
+            module("a", "TUR f\nSTOPP\n.DATA 0x41"),
+            module("b", "f: RETUR"),
+        ])
+        .unwrap_or_else(|e| panic!("{}", e.join("\n")));
+        assert_eq!(image.len(), 8);
+        assert_eq!(image[5], 0);
+        assert_eq!(u16::from_le_bytes([image[0], image[1]]) >> 4, 6);
+    }
+
+    #[test]
+    fn undefined_label() {
+        let errors = link(&[module("a", "HOPP nowhere")])
+            .err()
+            .unwrap_or_default();
+        assert_eq!(errors, vec![String::from("a:1: undefined label 'nowhere'")]);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::source::{parse, parse_number, parse_register, SourceLine};
+use std::collections::{HashMap, HashSet};
+
+pub struct Warning {
+    pub line: usize,
+    pub message: String,
+}
+
+struct Code<'a> {
+    line: usize,
+    labelled: bool,
+    source: &'a SourceLine,
+}
+
+impl<'a> Code<'a> {
+    fn mnemonic(&self) -> &str {
+        self.source.mnemonic.as_deref().unwrap_or("")
+    }
+
+    fn target(&self) -> Option<&str> {
+        match self.mnemonic() {
+            "HOPP" | "BHOPP" | "TUR" | "FINN" => self.source.operands.first().map(|o| o.as_str()),
+            _ => None,
+        }
+    }
+
+    fn overwritten_register(&self) -> Option<usize> {
+        match self.mnemonic() {
+            "SETT" | "LES" | "LAST" => self.source.operands.first().and_then(|o| parse_register(o)),
+            _ => None,
+        }
+    }
+}
+
+fn is_label(operand: &str) -> bool {
+    parse_number(operand).is_none() && parse_register(operand).is_none()
+}
+
+fn successors(code: &[Code], labels: &HashMap<String, usize>, index: usize) -> Vec<usize> {
+    let next = if index + 1 < code.len() {
+        vec![index + 1]
+    } else {
+        Vec::new()
+    };
+    let target = code[index]
+        .target()
+        .and_then(|t| labels.get(t))
+        .copied()
+        .into_iter();
+    match code[index].mnemonic() {
+        "STOPP" | "RETUR" | ".DATA" => Vec::new(),
+        "HOPP" => target.collect(),
+        "BHOPP" => next.into_iter().chain(target).collect(),
+        _ => next,
+    }
+}
+
+fn reaches_retur(code: &[Code], labels: &HashMap<String, usize>, entry: usize) -> bool {
+    let mut seen = HashSet::new();
+    let mut queue = vec![entry];
+    while let Some(index) = queue.pop() {
+        if !seen.insert(index) {
+            continue;
+        }
+        if code[index].mnemonic() == "RETUR" {
+            return true;
+        }
+        queue.extend(successors(code, labels, index));
+    }
+    false
+}
+
+fn check_truncation(code: &Code, warnings: &mut Vec<Warning>) {
+    let operands = &code.source.operands;
+    for operand in operands {
+        if let Some(r) = parse_register(operand) {
+            if r > 15 {
+                warnings.push(Warning {
+                    line: code.line,
+                    message: format!(
+                        "register {} does not exist, truncates to r{}",
+                        operand,
+                        r & 0xf
+                    ),
+                });
+            }
+        }
+    }
+    match code.mnemonic() {
+        "SETT" => {
+            if let Some(value) = operands.get(1).and_then(|o| parse_number(o)) {
+                if value > 0xff {
+                    warnings.push(Warning {
+                        line: code.line,
+                        message: format!(
+                            "immediate {} truncates to {:#x}",
+                            operands[1],
+                            value & 0xff
+                        ),
+                    });
+                }
+            }
+        }
+        "HOPP" | "BHOPP" | "TUR" | "FINN" => {
+            if let Some(value) = operands.first().and_then(|o| parse_number(o)) {
+                if value > 0xfff {
+                    warnings.push(Warning {
+                        line: code.line,
+                        message: format!(
+                            "address {} truncates to {:#x}",
+                            operands[0],
+                            value & 0xfff
+                        ),
+                    });
+                }
+            }
+        }
+        ".DATA" => {
+            for operand in operands {
+                if let Some(value) = parse_number(operand) {
+                    if value > 0xff {
+                        warnings.push(Warning {
+                            line: code.line,
+                            message: format!(
+                                "data byte {} truncates to {:#x}",
+                                operand,
+                                value & 0xff
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+pub fn lint(source: &str) -> Vec<Warning> {
+    let lines = parse(source);
+    let mut warnings = Vec::new();
+
+    let mut code = Vec::new();
+    let mut labels = HashMap::new();
+    let mut definitions = Vec::new();
+    let mut pending_label = false;
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(label) = &line.label {
+            labels.insert(label.clone(), code.len());
+            definitions.push((i + 1, label.clone()));
+            pending_label = true;
+        }
+        if line.mnemonic.is_some() {
+            code.push(Code {
+                line: i + 1,
+                labelled: pending_label,
+                source: line,
+            });
+            pending_label = false;
+        }
+    }
+
+    let referenced: HashSet<&str> = code
+        .iter()
+        .flat_map(|c| c.source.operands.iter())
+        .filter(|o| is_label(o))
+        .map(|o| o.as_str())
+        .collect();
+    for (line, label) in &definitions {
+        if !referenced.contains(label.as_str()) {
+            warnings.push(Warning {
+                line: *line,
+                message: format!("label '{}' is never referenced", label),
+            });
+        }
+    }
+
+    let mut unreachable = false;
+    for c in &code {
+        if c.labelled {
+            unreachable = false;
+        }
+        if unreachable && c.mnemonic() != ".DATA" {
+            warnings.push(Warning {
+                line: c.line,
+                message: String::from("unreachable code"),
+            });
+            unreachable = false;
+        }
+        if let "HOPP" | "STOPP" | "RETUR" = c.mnemonic() {
+            unreachable = true;
+        }
+    }
+
+    let mut callees = HashMap::new();
+    for c in code.iter().filter(|c| c.mnemonic() == "TUR") {
+        if let Some(target) = c.target() {
+            if let Some(&entry) = labels.get(target) {
+                let returns = *callees
+                    .entry(target)
+                    .or_insert_with(|| reaches_retur(&code, &labels, entry));
+                if !returns {
+                    warnings.push(Warning {
+                        line: c.line,
+                        message: format!("TUR to '{}' which never reaches RETUR", target),
+                    });
+                }
+            }
+        }
+    }
+
+    let mut finn = None;
+    let mut clobber = None;
+    for c in &code {
+        if c.labelled {
+            finn = None;
+            clobber = None;
+        }
+        match c.mnemonic() {
+            "FINN" => {
+                finn = Some(c.line);
+                clobber = None;
+                continue;
+            }
+            "LAST" | "LAGR" => {
+                if let (Some(f), Some((line, r))) = (finn, clobber.take()) {
+                    warnings.push(Warning {
+                        line,
+                        message: format!(
+                            "r{} overwritten after FINN on line {} but before {} on line {}",
+                            r,
+                            f,
+                            c.mnemonic(),
+                            c.line
+                        ),
+                    });
+                }
+            }
+            "HOPP" | "STOPP" | "RETUR" => {
+                finn = None;
+                clobber = None;
+            }
+            _ => (),
+        }
+        if let Some(r) = c.overwritten_register() {
+            if finn.is_some() && clobber.is_none() && r <= 1 {
+                clobber = Some((c.line, r));
+            }
+        }
+    }
+
+    for c in &code {
+        check_truncation(c, &mut warnings);
+    }
+
+    warnings.sort_by_key(|w| w.line);
+    warnings
+}
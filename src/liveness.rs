@@ -0,0 +1,201 @@
+use crate::functions::{find_functions, function_body};
+use crate::parser::{decode_at, Instruction, Operand};
+use std::collections::BTreeMap;
+
+#[derive(Default)]
+pub struct Summary {
+    pub inputs: u16,
+    pub writes: u16,
+    pub always: u16,
+    pub exit: u16,
+    pub dead: Vec<(usize, usize)>,
+    pub calls: Vec<(usize, usize, u16)>,
+}
+
+impl Summary {
+    pub fn outputs(&self) -> u16 {
+        self.writes & self.exit
+    }
+
+    pub fn clobbered(&self) -> u16 {
+        self.writes & !self.exit
+    }
+}
+
+pub fn mask(operands: impl Iterator<Item = Operand>) -> u16 {
+    operands
+        .filter_map(|o| match o {
+            Operand::Reg(r) => Some(1 << r),
+            _ => None,
+        })
+        .fold(0, |a, b| a | b)
+}
+
+pub fn registers(mask: u16) -> String {
+    let names: Vec<String> = (0..16)
+        .filter(|r| mask & 1 << r != 0)
+        .map(|r| format!("r{}", r))
+        .collect();
+    if names.is_empty() {
+        String::from("-")
+    } else {
+        names.join(", ")
+    }
+}
+
+struct Function {
+    entry: usize,
+    code: Vec<(usize, Instruction)>,
+    live_out: BTreeMap<usize, u16>,
+}
+
+fn flow(function: &mut Function, summaries: &BTreeMap<usize, Summary>, exit: u16) -> u16 {
+    let mut live_in: BTreeMap<usize, u16> = BTreeMap::new();
+    loop {
+        let mut changed = false;
+        for (pc, instruction) in function.code.iter().rev() {
+            let out = match instruction {
+                Instruction::Retur => exit,
+                Instruction::Tur(_) => live_in.get(&(pc + 2)).copied().unwrap_or(0),
+                _ => instruction
+                    .successors(*pc)
+                    .iter()
+                    .map(|s| live_in.get(s).copied().unwrap_or(0))
+                    .fold(0, |a, b| a | b),
+            };
+            let (uses, defs) = match instruction {
+                Instruction::Tur(callee) => summaries
+                    .get(&usize::from(*callee))
+                    .map_or((0, 0), |s| (s.inputs, s.always)),
+                _ => (mask(instruction.reads()), mask(instruction.writes())),
+            };
+            let value = uses | (out & !defs);
+            function.live_out.insert(*pc, out);
+            if live_in.insert(*pc, value) != Some(value) {
+                changed = true;
+            }
+        }
+        if !changed {
+            return live_in.get(&function.entry).copied().unwrap_or(0);
+        }
+    }
+}
+
+fn always_writes(function: &Function, summaries: &BTreeMap<usize, Summary>) -> u16 {
+    let mut written: BTreeMap<usize, u16> = BTreeMap::from([(function.entry, 0)]);
+    let mut always = u16::MAX;
+    loop {
+        let mut changed = false;
+        for (pc, instruction) in &function.code {
+            let before = match written.get(pc) {
+                Some(&before) => before,
+                None => continue,
+            };
+            let (after, successors) = match instruction {
+                Instruction::Tur(callee) => (
+                    before | summaries.get(&usize::from(*callee)).map_or(0, |s| s.always),
+                    vec![pc + 2],
+                ),
+                Instruction::Retur => {
+                    always &= before;
+                    continue;
+                }
+                _ => (
+                    before | mask(instruction.writes()),
+                    instruction.successors(*pc),
+                ),
+            };
+            for successor in successors {
+                let merged = written.get(&successor).map_or(after, |&w| w & after);
+                if written.insert(successor, merged) != Some(merged) {
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return always;
+        }
+    }
+}
+
+pub fn analyze(memory: &[u8]) -> BTreeMap<usize, Summary> {
+    let mut entries = find_functions(memory);
+    entries.insert(0);
+    let mut functions: Vec<Function> = entries
+        .iter()
+        .map(|&entry| Function {
+            entry,
+            code: function_body(memory, entry)
+                .into_iter()
+                .map(|pc| (pc, decode_at(memory, pc)))
+                .collect(),
+            live_out: BTreeMap::new(),
+        })
+        .collect();
+    let mut summaries: BTreeMap<usize, Summary> = entries
+        .iter()
+        .map(|&entry| (entry, Summary::default()))
+        .collect();
+
+    loop {
+        let mut changed = false;
+        for function in &mut functions {
+            let always = always_writes(function, &summaries);
+            let inputs = flow(function, &summaries, 0);
+            flow(function, &summaries, summaries[&function.entry].exit);
+            let mut writes = 0;
+            for (pc, instruction) in &function.code {
+                writes |= match instruction {
+                    Instruction::Tur(callee) => {
+                        summaries.get(&usize::from(*callee)).map_or(0, |s| s.writes)
+                    }
+                    _ => mask(instruction.writes()),
+                };
+                if let Instruction::Tur(callee) = instruction {
+                    let after = function.live_out[pc];
+                    if let Some(callee) = summaries.get_mut(&usize::from(*callee)) {
+                        if callee.exit | after != callee.exit {
+                            callee.exit |= after;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            let summary = summaries
+                .get_mut(&function.entry)
+                .expect("every function has a summary");
+            if summary.inputs != inputs || summary.writes != writes || summary.always != always {
+                summary.inputs = inputs;
+                summary.writes = writes;
+                summary.always = always;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    for function in &functions {
+        let mut dead = Vec::new();
+        let mut calls = Vec::new();
+        for (pc, instruction) in &function.code {
+            let out = function.live_out[pc];
+            match instruction {
+                Instruction::Tur(callee) => calls.push((*pc, usize::from(*callee), out)),
+                _ => {
+                    let defs = mask(instruction.writes());
+                    for r in (0..16).filter(|r| defs & !out & 1 << r != 0) {
+                        dead.push((*pc, r));
+                    }
+                }
+            }
+        }
+        let summary = summaries
+            .get_mut(&function.entry)
+            .expect("every function has a summary");
+        summary.dead = dead;
+        summary.calls = calls;
+    }
+    summaries
+}
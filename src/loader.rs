@@ -0,0 +1,74 @@
+use crate::parser::MAGIC;
+use crate::rng::{self, Rng};
+use crate::source::parse_number;
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::{self, ErrorKind, Read};
+
+fn from_zip(archive: &str, entry: Option<&str>, program: bool) -> io::Result<Vec<u8>> {
+    let mut zip = zip::ZipArchive::new(fs::File::open(archive)?)?;
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        if file.is_dir() || entry.is_some_and(|name| file.name() != name) {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        if entry.is_some() || bytes.starts_with(MAGIC) == program {
+            return Ok(bytes);
+        }
+    }
+    let wanted = match (entry, program) {
+        (Some(name), _) => format!("entry {}", name),
+        (None, true) => String::from("a SLEDE8 program"),
+        (None, false) => String::from("an input file"),
+    };
+    Err(io::Error::new(
+        ErrorKind::NotFound,
+        format!("{} has no {}", archive, wanted),
+    ))
+}
+
+pub fn load(path: &str, program: bool) -> io::Result<Vec<u8>> {
+    if let Some(split) = path.find(".zip:") {
+        let (archive, entry) = (&path[..split + 4], &path[split + 5..]);
+        return from_zip(archive, Some(entry), program);
+    }
+    if path.ends_with(".zip") {
+        return from_zip(path, None, program);
+    }
+    let bytes = fs::read(path)?;
+    if path.ends_with(".gz") {
+        let mut unpacked = Vec::new();
+        GzDecoder::new(&bytes[..]).read_to_end(&mut unpacked)?;
+        return Ok(unpacked);
+    }
+    Ok(bytes)
+}
+
+pub fn input(spec: &str) -> io::Result<Vec<u8>> {
+    if let Some(digits) = spec.strip_prefix("hex:") {
+        return hex::decode(digits).map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+    }
+    if let Some(options) = spec.strip_prefix("rng:") {
+        let (seed, length) =
+            rng::parse(options).map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+        return Ok(Rng::new(seed).bytes(length));
+    }
+    load(spec, false)
+}
+
+pub fn seed(spec: &str) -> Option<u64> {
+    rng::parse(spec.strip_prefix("rng:")?)
+        .ok()
+        .map(|(seed, _)| seed)
+}
+
+pub fn parse_pad(spec: &str) -> Option<(usize, u8)> {
+    let (length, byte) = spec.split_once(':').unwrap_or((spec, "0"));
+    let byte = parse_number(byte)?;
+    if byte > 0xff {
+        return None;
+    }
+    Some((parse_number(length)? as usize, byte as u8))
+}
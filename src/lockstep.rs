@@ -0,0 +1,121 @@
+use crate::costs::{self, CostModel};
+use crate::emulator::{HaltReason, State};
+use crate::history::Step;
+use crate::parser::{Instruction, MEMORY_SIZE};
+use crate::source::parse_number;
+use std::fs;
+use std::sync::Arc;
+
+pub struct Config {
+    pub name: String,
+    pub costs: Option<Arc<CostModel>>,
+    pub max_cycles: usize,
+}
+
+pub fn parse(spec: &str, max_cycles: usize) -> Result<Config, String> {
+    let mut config = Config {
+        name: spec.to_string(),
+        costs: None,
+        max_cycles,
+    };
+    for option in spec.split(':').filter(|o| !o.is_empty() && *o != "default") {
+        let (key, value) = option
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got {}", option))?;
+        match key {
+            "costs" => {
+                let text = fs::read_to_string(value).map_err(|e| format!("{}: {}", value, e))?;
+                config.costs = Some(Arc::new(
+                    costs::parse(&text).map_err(|e| format!("{}: {}", value, e))?,
+                ));
+            }
+            "max-cycles" => {
+                config.max_cycles = parse_number(value)
+                    .ok_or_else(|| format!("{} is not a cycle count", value))?
+                    as usize
+            }
+            _ => {
+                return Err(format!(
+                    "unknown option {}, expected costs or max-cycles",
+                    key
+                ))
+            }
+        }
+    }
+    Ok(config)
+}
+
+pub struct Divergence {
+    pub step: usize,
+    pub before: Step,
+    pub differences: Vec<String>,
+    pub states: Vec<State>,
+}
+
+fn differences(a: &State, b: &State, stored: Option<usize>) -> Vec<String> {
+    let mut expected = Step::capture(a);
+    let actual = Step::capture(b);
+    expected.cycles = actual.cycles;
+    let mut differences = expected.differences(&actual);
+    if a.stdout() != b.stdout() {
+        differences.push(format!(
+            "output: expected {}, got {}",
+            hex::encode(a.stdout()),
+            hex::encode(b.stdout())
+        ));
+    }
+    if let Some(address) = stored.filter(|&i| a.memory_at(i) != b.memory_at(i)) {
+        differences.push(format!(
+            "[{:#06x}]: expected {:02x}h, got {:02x}h",
+            address,
+            a.memory_at(address),
+            b.memory_at(address)
+        ));
+    }
+    if a.halted() != b.halted() {
+        let halt = |state: &State| match state.halted() {
+            Some(reason) => reason.to_string(),
+            None => String::from("running"),
+        };
+        differences.push(format!("halt: expected {}, got {}", halt(a), halt(b)));
+    }
+    differences
+}
+
+pub fn compare(
+    memory: [u8; MEMORY_SIZE],
+    stdin: &[u8],
+    configs: &[Config],
+) -> Result<Vec<State>, Divergence> {
+    let mut states: Vec<State> = configs
+        .iter()
+        .map(|c| State::new(memory, stdin.to_vec()).with_costs(c.costs.clone()))
+        .collect();
+    let mut step = 0;
+    loop {
+        let before = Step::capture(&states[0]);
+        let stored = match states[0].next_instruction() {
+            Instruction::Lagr(_) => Some(states[0].pointer()),
+            _ => None,
+        };
+        for (state, config) in states.iter_mut().zip(configs) {
+            if state.halted().is_none() && state.cycles() >= config.max_cycles {
+                state.halt(HaltReason::TickLimit(config.max_cycles));
+            }
+            state.iter().next();
+        }
+        let differences = differences(&states[0], &states[1], stored);
+        if !differences.is_empty() {
+            return Err(Divergence {
+                step,
+                before,
+                differences,
+                states,
+            });
+        }
+        if states[0].halted().is_some() {
+            return Ok(states);
+        }
+        step += 1;
+    }
+}
@@ -0,0 +1,260 @@
+use crate::assembler::assemble_fragment;
+use crate::lint::lint;
+use crate::source::{mnemonic_doc, parse, MNEMONICS};
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+    HoverContents, HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind,
+    OneOf, Position, PublishDiagnosticsParams, Range, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::collections::HashMap;
+use std::error::Error;
+
+type Documents = HashMap<Url, String>;
+
+fn line_range(source: &str, line: usize) -> Range {
+    let length = source
+        .lines()
+        .nth(line)
+        .map(|l| l.encode_utf16().count())
+        .unwrap_or(0);
+    Range::new(
+        Position::new(line as u32, 0),
+        Position::new(line as u32, length as u32),
+    )
+}
+
+fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let error = |line: usize, message: String| Diagnostic {
+        range: line_range(source, line - 1),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some(String::from("s8asm")),
+        message,
+        ..Default::default()
+    };
+
+    match assemble_fragment(source) {
+        Ok(fragment) => {
+            for relocation in &fragment.relocations {
+                if !fragment.symbols.contains_key(&relocation.symbol) {
+                    diagnostics.push(error(
+                        relocation.line,
+                        format!("undefined label '{}'", relocation.symbol),
+                    ));
+                }
+            }
+        }
+        Err(errors) => {
+            for e in errors {
+                diagnostics.push(error(e.line, e.message));
+            }
+        }
+    }
+
+    for warning in lint(source) {
+        diagnostics.push(Diagnostic {
+            range: line_range(source, warning.line - 1),
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some(String::from("s8lint")),
+            message: warning.message,
+            ..Default::default()
+        });
+    }
+    diagnostics
+}
+
+fn word_at(source: &str, position: Position) -> Option<String> {
+    let line: Vec<char> = source
+        .lines()
+        .nth(position.line as usize)?
+        .chars()
+        .collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_' || *c == '.';
+    let at = (position.character as usize).min(line.len());
+    let start = line[..at]
+        .iter()
+        .rposition(|c| !is_word(c))
+        .map_or(0, |i| i + 1);
+    let end = line[at..]
+        .iter()
+        .position(|c| !is_word(c))
+        .map_or(line.len(), |i| at + i);
+    if start < end {
+        Some(line[start..end].iter().collect())
+    } else {
+        None
+    }
+}
+
+fn labels(source: &str) -> HashMap<String, usize> {
+    parse(source)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, line)| line.label.map(|label| (label, i)))
+        .collect()
+}
+
+fn definition(
+    documents: &Documents,
+    params: GotoDefinitionParams,
+) -> Option<GotoDefinitionResponse> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let source = documents.get(&uri)?;
+    let word = word_at(source, params.text_document_position_params.position)?;
+    let line = *labels(source).get(&word)?;
+    Some(GotoDefinitionResponse::Scalar(Location::new(
+        uri,
+        line_range(source, line),
+    )))
+}
+
+fn hover(documents: &Documents, params: HoverParams) -> Option<Hover> {
+    let position = params.text_document_position_params;
+    let source = documents.get(&position.text_document.uri)?;
+    let word = word_at(source, position.position)?;
+    let doc = mnemonic_doc(&word)?;
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("`{}`", doc),
+        }),
+        range: None,
+    })
+}
+
+fn completion(documents: &Documents, params: CompletionParams) -> Option<CompletionResponse> {
+    let source = documents.get(&params.text_document_position.text_document.uri)?;
+    let mut items = Vec::new();
+    for label in labels(source).keys() {
+        items.push(CompletionItem {
+            label: label.clone(),
+            kind: Some(CompletionItemKind::REFERENCE),
+            ..Default::default()
+        });
+    }
+    for r in 0..16 {
+        items.push(CompletionItem {
+            label: format!("r{}", r),
+            kind: Some(CompletionItemKind::VARIABLE),
+            ..Default::default()
+        });
+    }
+    for (mnemonic, doc) in MNEMONICS.iter() {
+        items.push(CompletionItem {
+            label: mnemonic.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(doc.to_string()),
+            ..Default::default()
+        });
+    }
+    Some(CompletionResponse::Array(items))
+}
+
+fn handle_request(documents: &Documents, request: Request) -> Response {
+    let id = request.id.clone();
+    let result = match request.method.as_str() {
+        GotoDefinition::METHOD => request
+            .extract(GotoDefinition::METHOD)
+            .map(|(_, params)| serde_json::to_value(definition(documents, params))),
+        HoverRequest::METHOD => request
+            .extract(HoverRequest::METHOD)
+            .map(|(_, params)| serde_json::to_value(hover(documents, params))),
+        Completion::METHOD => request
+            .extract(Completion::METHOD)
+            .map(|(_, params)| serde_json::to_value(completion(documents, params))),
+        _ => {
+            return Response::new_err(
+                id,
+                lsp_server::ErrorCode::MethodNotFound as i32,
+                format!("unsupported request {}", request.method),
+            )
+        }
+    };
+    match result {
+        Ok(Ok(value)) => Response::new_ok(id, value),
+        _ => Response::new_err(
+            id,
+            lsp_server::ErrorCode::InvalidParams as i32,
+            String::from("invalid parameters"),
+        ),
+    }
+}
+
+fn handle_notification(documents: &mut Documents, notification: Notification) -> Option<Url> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: lsp_types::DidOpenTextDocumentParams =
+                notification.extract(DidOpenTextDocument::METHOD).ok()?;
+            let uri = params.text_document.uri;
+            documents.insert(uri.clone(), params.text_document.text);
+            Some(uri)
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: lsp_types::DidChangeTextDocumentParams =
+                notification.extract(DidChangeTextDocument::METHOD).ok()?;
+            let uri = params.text_document.uri;
+            let text = params.content_changes.into_iter().last()?.text;
+            documents.insert(uri.clone(), text);
+            Some(uri)
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: lsp_types::DidCloseTextDocumentParams =
+                notification.extract(DidCloseTextDocument::METHOD).ok()?;
+            documents.remove(&params.text_document.uri);
+            None
+        }
+        _ => None,
+    }
+}
+
+pub fn serve() -> Result<(), Box<dyn Error + Sync + Send>> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = serde_json::to_value(ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        ..Default::default()
+    })?;
+    connection.initialize(capabilities)?;
+
+    let mut documents = Documents::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                let response = handle_request(&documents, request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(uri) = handle_notification(&mut documents, notification) {
+                    let params = PublishDiagnosticsParams::new(
+                        uri.clone(),
+                        diagnostics(&documents[&uri]),
+                        None,
+                    );
+                    connection
+                        .sender
+                        .send(Message::Notification(Notification::new(
+                            PublishDiagnostics::METHOD.to_string(),
+                            params,
+                        )))?;
+                }
+            }
+            Message::Response(_) => (),
+        }
+    }
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
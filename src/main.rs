@@ -1,139 +1,5109 @@
-use argparse::{ArgumentParser, Store, StoreTrue};
-use emulator::State;
+use argparse::{ArgumentParser, Collect, List, Store, StoreOption, StoreTrue};
+use checkpoint::Checkpoint;
+use emulator::{HaltReason, State};
+use error::S8Error;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
+use s8disasm::{costs, emulator, error, parser};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-mod emulator;
-mod parser;
+mod accounting;
+mod assembler;
+mod batch;
+mod bench;
+mod call;
+mod callconv;
+mod callgraph;
+mod cfg;
+mod checkpoint;
+mod conformance;
+mod coverage;
+mod crib;
+mod device;
+mod driver;
+mod dump;
+mod entropy;
+mod export;
+mod expr;
+mod formatter;
+mod ftrace;
+mod functions;
+#[cfg(feature = "fuzz")]
+mod fuzz;
+mod highlight;
+mod history;
+mod import;
+mod infer;
+mod instrument;
+mod keygen;
+mod layout;
+mod linker;
+mod lint;
+mod liveness;
+mod loader;
+mod lockstep;
+mod lsp;
+mod minimize;
+mod obfuscation;
+mod optimizer;
+mod progress;
+mod project;
+mod reach;
+mod replay;
+mod report;
+mod rng;
+mod scan;
+mod selftest;
+mod serial;
+mod signatures;
+mod snaptree;
+mod source;
+mod stress;
+mod stub;
+mod symex;
+mod timeline;
+mod trace;
+mod types;
+mod unpack;
+mod verify;
+mod watch;
 
-fn disassemble(input: &[u8], clean: bool) {
-    let instructions = parser::disassemble(input);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+fn interrupted() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+fn read_file(path: &str, program: bool) -> Vec<u8> {
+    let loaded = if program {
+        loader::load(path, true)
+    } else {
+        loader::input(path)
+    };
+    match loaded {
+        Ok(bytes) => {
+            if let (true, Err(e)) = (program, parser::program(&bytes)) {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(2);
+            }
+            bytes
+        }
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn read_text(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(2);
+    })
+}
+
+fn write_output<C: AsRef<[u8]>>(path: &str, contents: C) {
+    if let Err(e) = fs::write(path, contents) {
+        eprintln!("Could not write {}: {}", path, e);
+        std::process::exit(1);
+    }
+}
+
+fn prompt_input() -> Vec<u8> {
+    if let Ok(spec) = std::env::var("S8_INPUT") {
+        return read_file(&spec, false);
+    }
+    if !std::io::stdin().is_terminal() {
+        eprintln!("Second argument is a file with indata (or set S8_INPUT)");
+        std::process::exit(2);
+    }
+    eprint!("Input (file or hex:...): ");
+    let mut line = String::new();
+    if std::io::stdin().read_line(&mut line).is_err() {
+        std::process::exit(2);
+    }
+    read_file(line.trim(), false)
+}
+
+fn paint(text: String, color: bool) -> String {
+    if color {
+        highlight::highlight(&text)
+    } else {
+        text
+    }
+}
+
+fn alias_comment(
+    instruction: &parser::Instruction,
+    aliases: &BTreeMap<usize, String>,
+    regions: &[project::Region],
+) -> String {
+    let mut named: Vec<String> = Vec::new();
+    for operand in instruction.operands() {
+        if let parser::Operand::Reg(r) = operand {
+            if let Some(name) = aliases.get(&usize::from(r)) {
+                let alias = format!("r{}={}", r, name);
+                if !named.contains(&alias) {
+                    named.push(alias);
+                }
+            }
+        }
+    }
+    if let parser::Instruction::Finn(address) = instruction {
+        if project::name_at(regions, usize::from(*address)).is_some() {
+            named.push(symbolic(usize::from(*address), regions));
+        }
+    }
+    if named.is_empty() {
+        String::new()
+    } else {
+        format!("   ; {}", named.join(", "))
+    }
+}
+
+fn symbolic(address: usize, regions: &[project::Region]) -> String {
+    match project::name_at(regions, address) {
+        Some((region, 0)) => region.name.clone(),
+        Some((region, offset)) => format!("{}+{:#x}", region.name, offset),
+        None => format!("{:#06x}", address),
+    }
+}
+
+fn input_options<'a>(
+    ap: &mut ArgumentParser<'a>,
+    stdin_sources: &'a mut Vec<String>,
+    stdin_pad: &'a mut Option<String>,
+) {
+    ap.refer(stdin_sources).add_option(
+        &["--stdin"],
+        Collect,
+        "Append a file or hex:4142 to the input data (repeatable)",
+    );
+    ap.refer(stdin_pad).add_option(
+        &["--stdin-pad"],
+        StoreOption,
+        "Pad the input data to N bytes, e.g. 64:0x00",
+    );
+}
+
+fn machine_options<'a>(
+    ap: &mut ArgumentParser<'a>,
+    cost_file: &'a mut Option<String>,
+    max_cycles: &'a mut Option<usize>,
+    stub_specs: &'a mut Vec<String>,
+    marker_specs: &'a mut Vec<String>,
+) {
+    ap.refer(cost_file).add_option(
+        &["--costs"],
+        StoreOption,
+        "TOML file with the cycle cost of each mnemonic",
+    );
+    ap.refer(max_cycles).add_option(
+        &["--max-cycles"],
+        StoreOption,
+        "Stop the program after this many cycles",
+    );
+    ap.refer(stub_specs).add_option(
+        &["--stub"],
+        Collect,
+        "Skip calls to a function and apply a summary instead, e.g. 0x240=return:r2=0 or 0x240=script:COMMANDS (repeatable)",
+    );
+    ap.refer(marker_specs).add_option(
+        &["--marker"],
+        Collect,
+        "Name the moments the run reaches an address in the trace and timeline, e.g. 0x40=start_decrypt (repeatable)",
+    );
+}
+
+fn hook_options<'a>(
+    ap: &mut ArgumentParser<'a>,
+    on_write_stdout: &'a mut Option<String>,
+    on_fault: &'a mut Option<String>,
+    ftrace: &'a mut bool,
+) {
+    ap.refer(on_write_stdout).add_option(
+        &["--on-write-stdout"],
+        StoreOption,
+        "Debugger commands (separated by ;) to run after every SKRIV",
+    );
+    ap.refer(on_fault).add_option(
+        &["--on-fault"],
+        StoreOption,
+        "Debugger commands (separated by ;) to run when the program faults",
+    );
+    ap.refer(ftrace).add_option(
+        &["--ftrace"],
+        StoreTrue,
+        "Log every call with its entry registers and every return with the registers it changed and the cycles spent",
+    );
+}
+
+fn inspect_options<'a>(
+    ap: &mut ArgumentParser<'a>,
+    grep_output: &'a mut Option<String>,
+    grep_memory: &'a mut bool,
+    scan_memory: &'a mut Option<String>,
+    dump_regions: &'a mut Vec<String>,
+) {
+    ap.refer(grep_output).add_option(
+        &["--grep-output"],
+        StoreOption,
+        "Print the matches of a regex in the program's output",
+    );
+    ap.refer(grep_memory).add_option(
+        &["--grep-memory"],
+        StoreTrue,
+        "Also search memory at halt for --grep-output",
+    );
+    ap.refer(scan_memory).add_option(
+        &["--scan-memory"],
+        StoreOption,
+        "Report when memory written by LAGR starts matching a regex",
+    );
+    ap.refer(dump_regions).add_option(
+        &["--dump-region"],
+        Collect,
+        "Write memory START-END to FILE at halt, or when pc reaches PC: START-END[@PC]=FILE (repeatable)",
+    );
+}
+
+fn trace_options<'a>(
+    ap: &mut ArgumentParser<'a>,
+    trace_file: &'a mut Option<String>,
+    trace_filter: &'a mut Option<String>,
+    record_file: &'a mut Option<String>,
+) {
+    ap.refer(trace_file).add_option(
+        &["--trace"],
+        StoreOption,
+        "Record every step of the run to a compressed trace file",
+    );
+    ap.refer(trace_filter).add_option(
+        &["--trace-filter"],
+        StoreOption,
+        "Only record steps where the expression is true, e.g. 'mnemonic in (LES,SKRIV) || pc in 0x100..0x180'",
+    );
+    ap.refer(record_file).add_option(
+        &["--record"],
+        StoreOption,
+        "Write a replay file of this run",
+    );
+}
+
+fn checkpoint_options<'a>(
+    ap: &mut ArgumentParser<'a>,
+    checkpoint_every: &'a mut Option<usize>,
+    search_state: &'a mut Option<String>,
+    shard: &'a mut Option<String>,
+) {
+    ap.refer(checkpoint_every).add_option(
+        &["--checkpoint-every"],
+        StoreOption,
+        "Save --reach-stopp, --stress and --fuzz progress to PROGRAM.s8checkpoint every N tries and resume from it",
+    );
+    ap.refer(search_state).add_option(
+        &["--search-state"],
+        StoreOption,
+        "Keep the --checkpoint-every state in FILE instead of PROGRAM.s8checkpoint",
+    );
+    ap.refer(shard).add_option(
+        &["--shard"],
+        StoreOption,
+        "Search only part i of N of the --reach-stopp or --stress inputs and keep its state file (i/N)",
+    );
+}
+
+fn listing_options<'a>(
+    ap: &mut ArgumentParser<'a>,
+    clean: &'a mut bool,
+    recursive: &'a mut bool,
+    raw: &'a mut bool,
+    base: &'a mut String,
+) {
+    ap.refer(clean)
+        .add_option(&["-c", "--clean"], StoreTrue, "Output just s8asm");
+    ap.refer(recursive).add_option(
+        &["--recursive"],
+        StoreTrue,
+        "Disassemble only what is reachable from the entry point and show the other words as .DATA",
+    );
+    ap.refer(raw).add_option(
+        &["--raw"],
+        StoreTrue,
+        "Disassemble a headerless stream of words (- reads stdin)",
+    );
+    ap.refer(base).add_option(
+        &["--base"],
+        Store,
+        "Address the first disassembled word is loaded at",
+    );
+}
+
+fn show_info(input: &[u8]) -> Result<(), S8Error> {
+    let program = program_bytes(input)?;
+    let memory = parser::memory_image(program);
+    let code = parser::reachable(&memory);
+    let count = |wanted: fn(&parser::Instruction) -> bool| {
+        code.iter()
+            .filter(|&&pc| wanted(&parser::decode_at(&memory, pc)))
+            .count()
+    };
+    println!("program   {:016x}", replay::program_hash(program));
+    println!("size      {} bytes", program.len());
+    println!(
+        "code      {} reachable instructions, {} bytes of other data",
+        code.len(),
+        program.len().saturating_sub(2 * code.len())
+    );
+    println!("functions {}", functions::find_functions(&memory).len());
+    println!(
+        "i/o       {} LES, {} SKRIV",
+        count(|i| matches!(i, parser::Instruction::Les(_))),
+        count(|i| matches!(i, parser::Instruction::Skriv(_)))
+    );
+    println!(
+        "halts     {} STOPP",
+        count(|i| matches!(i, parser::Instruction::Stopp))
+    );
+    Ok(())
+}
+
+fn program_bytes(input: &[u8]) -> Result<&[u8], S8Error> {
+    parser::program(input)
+}
+
+fn program_lines(input: &[u8], base: usize) -> Result<Vec<parser::Line>, S8Error> {
+    parser::disassemble_overlapping(input, base, &code(input)?)
+}
+
+fn code(input: &[u8]) -> Result<BTreeSet<usize>, S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    Ok(parser::reachable(&memory))
+}
+
+fn auto_labels(
+    instructions: &[parser::Line],
+    base: usize,
+    program: &[u8],
+    names: &BTreeMap<usize, String>,
+    session: &project::Session,
+) -> BTreeMap<usize, String> {
+    let mut labels = names.clone();
+    let calls: BTreeSet<usize> = instructions
+        .iter()
+        .filter_map(|line| match line.instruction {
+            parser::Instruction::Tur(target) => Some(usize::from(target)),
+            _ => None,
+        })
+        .collect();
+    for line in instructions {
+        for target in line.instruction.targets().into_iter().map(usize::from) {
+            let listed = target.is_multiple_of(2) && target + 1 < program.len();
+            let typed = session
+                .types
+                .iter()
+                .any(|t| (t.start..t.end(program)).contains(&target));
+            if listed && !typed && !labels.contains_key(&target) {
+                let prefix = if calls.contains(&target) {
+                    "sub"
+                } else {
+                    "loc"
+                };
+                labels.insert(target, format!("{}_{:04x}", prefix, base + target));
+            }
+        }
+    }
+    labels
+}
+
+fn labelled(instruction: &parser::Instruction, labels: &BTreeMap<usize, String>) -> String {
+    match instruction
+        .targets()
+        .and_then(|target| labels.get(&usize::from(target)))
+    {
+        Some(label) => format!("{}\t{}", instruction.mnemonic(), label),
+        None => instruction.to_string(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn disassemble(
+    input: &[u8],
+    base: usize,
+    recursive: bool,
+    clean: bool,
+    color: bool,
+    names: &BTreeMap<usize, String>,
+    prototypes: &BTreeMap<usize, String>,
+    session: &project::Session,
+) -> Result<(), S8Error> {
+    let instructions = if recursive {
+        parser::disassemble_recursive(input, base)?
+    } else {
+        program_lines(input, base)?
+    };
+    let program = program_bytes(input)?;
+    let labels = auto_labels(&instructions, base, program, names, session);
 
     for line in instructions {
+        let overlapping = !(line.pos - base).is_multiple_of(2);
+        let typed = session
+            .types
+            .iter()
+            .find(|t| (t.start..t.end(program)).contains(&(line.pos - base)));
+        if let Some(typed) = typed {
+            let first = typed.start + typed.start % 2;
+            if overlapping || line.pos - base != first {
+                continue;
+            }
+            let end = typed.end(program);
+            let last = (end - 1) & !1;
+            let name = match project::name_at(&session.regions, typed.start) {
+                Some((region, 0)) => format!("{}: ", region.name),
+                _ => String::new(),
+            };
+            let value = format!("{}{} = {}", name, typed.data, typed.render(program));
+            if clean {
+                let bytes: Vec<String> = program[first..(last + 2).min(program.len())]
+                    .iter()
+                    .map(|b| format!("{:#04x}", b))
+                    .collect();
+                println!(".DATA\t{}   ; {}", bytes.join(", "), value);
+            } else {
+                println!("{:#06x}: {}", line.pos, value);
+            }
+            continue;
+        }
+        if let Some(name) = labels.get(&(line.pos - base)) {
+            if !(clean && overlapping) {
+                println!("{}:", paint(name.clone(), color));
+            }
+        }
+        if let Some(prototype) = prototypes.get(&(line.pos - base)) {
+            if !(clean && overlapping) {
+                println!("; {}", prototype);
+            }
+        }
+        let instruction = paint(labelled(&line.instruction, &labels), color);
+        let comment = alias_comment(
+            &line.instruction,
+            &project::names_at(&session.aliases, &session.variables, line.pos - base),
+            &session.regions,
+        );
+        if clean && overlapping {
+            println!(
+                "; {:#06x}: {} (also executed from the middle of the previous instruction)",
+                line.pos,
+                line.instruction.to_string().replace('\t', " ")
+            )
+        } else if clean {
+            println!("{}{}", instruction, comment)
+        } else if overlapping {
+            println!(
+                "{:#06x}: {:#06x} ~ {}{}",
+                line.pos, line.op, instruction, comment
+            );
+        } else {
+            println!(
+                "{:#06x}: {:#06x}   {}{}",
+                line.pos, line.op, instruction, comment
+            );
+        }
+    }
+    let last = program.len().wrapping_sub(1);
+    let typed = session
+        .types
+        .iter()
+        .any(|t| (t.start..t.end(program)).contains(&last));
+    if !program.len().is_multiple_of(2) && !typed {
         if clean {
-            println!("{}", line.instruction)
+            println!(".DATA\t{:#04x}", program[last]);
         } else {
-            println!("{:#06x}: {:#06x}   {}", line.pos, line.op, line.instruction);
+            println!(
+                "{:#06x}: {:#04x}     .DATA\t{:#04x}",
+                base + last,
+                program[last],
+                program[last]
+            );
         }
     }
+    Ok(())
 }
 
-fn debugger(computer: &mut State) {
-    let mut rl = Editor::<()>::new();
-    let mut stepping = true;
-    let mut breakpoints: Vec<usize> = Vec::new();
+fn export_table(input: &[u8], base: usize, separator: &str) -> Result<(), S8Error> {
+    println!(
+        "{}",
+        ["address", "opcode", "mnemonic", "operands", "target"].join(separator)
+    );
+    for line in program_lines(input, base)? {
+        let operands: Vec<String> = line.instruction.operands().map(|o| o.to_string()).collect();
+        let target = line
+            .instruction
+            .targets()
+            .map(|t| format!("{:#06x}", t))
+            .unwrap_or_default();
+        println!(
+            "{}",
+            [
+                format!("{:#06x}", line.pos),
+                format!("{:#06x}", line.op),
+                line.instruction.mnemonic().to_string(),
+                operands.join(" "),
+                target,
+            ]
+            .join(separator)
+        );
+    }
+    Ok(())
+}
 
-    while !computer.finished {
-        if breakpoints.contains(&computer.pc()) {
-            eprintln!("Hit breakpoint");
-            stepping = true;
+fn disassemble_raw(file: &str, base: usize, color: bool) -> bool {
+    let reader: Box<dyn std::io::Read> = if file == "-" {
+        Box::new(std::io::stdin())
+    } else {
+        Box::new(fs::File::open(file).unwrap_or_else(|e| {
+            eprintln!("{}: {}", file, e);
+            std::process::exit(2);
+        }))
+    };
+    for line in parser::disassemble_stream(reader, base) {
+        match line {
+            Ok(line) => {
+                let instruction = paint(line.instruction.to_string(), color);
+                println!("{:#06x}: {:#06x}   {}", line.pos, line.op, instruction);
+            }
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                return false;
+            }
         }
-        if stepping {
-            println!("{}", computer.display());
-            let readline = rl.readline("→ ");
-            match readline {
-                Ok(cmd) => {
-                    rl.add_history_entry(cmd.as_str());
-                    if cmd.starts_with('c') {
-                        stepping = false;
-                        computer.execute();
-                    } else if cmd.starts_with('m') {
-                        let tokens = cmd.split_whitespace().collect::<Vec<&str>>();
-                        let from = usize::from_str_radix(tokens[1], 16).unwrap();
-                        let to = usize::from_str_radix(tokens[2], 16).unwrap();
-                        computer.dump_memory(from, to);
-                    } else if cmd.starts_with('b') {
-                        let tokens = cmd.split_whitespace().collect::<Vec<&str>>();
-                        if tokens.len() == 2 {
-                            match usize::from_str_radix(tokens[1], 16) {
-                                Ok(num) => breakpoints.push(num),
-                                Err(_) => eprintln!("Could not parse address"),
-                            };
-                        } else {
-                            eprintln!("Breakpoints:");
-                        }
-                    } else {
-                        computer.execute();
-                    }
-                }
-                Err(ReadlineError::Interrupted) => {
-                    println!("Abort");
-                    break;
+    }
+    true
+}
+
+fn format(file: &str, source: &str, in_place: bool, color: bool) {
+    let formatted = formatter::format_source(source);
+    if in_place {
+        write_output(file, formatted);
+    } else {
+        for line in formatted.lines() {
+            println!("{}", paint(line.to_string(), color));
+        }
+    }
+}
+
+fn lint_source(file: &str, source: &str) -> bool {
+    let warnings = lint::lint(source);
+    for warning in &warnings {
+        println!("{}:{}: warning: {}", file, warning.line, warning.message);
+    }
+    warnings.is_empty()
+}
+
+fn optimize(program: &[u8], stdin: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let (optimized, savings) = optimizer::optimize(program);
+    eprintln!(
+        "Removed {} NOPE(s) and {} redundant SETT(s), shortened {} jump(s), saved {} byte(s)",
+        savings.nopes, savings.setts, savings.jumps, savings.bytes
+    );
+    if let Some(stdin) = stdin {
+        let mut before = State::new(parser::memory_image(program), stdin.clone());
+        let mut after = State::new(parser::memory_image(&optimized), stdin);
+        before.run(1_000_000);
+        after.run(1_000_000);
+        eprintln!(
+            "Cycles {} -> {} ({} saved), output {}",
+            before.cycles(),
+            after.cycles(),
+            before.cycles() as isize - after.cycles() as isize,
+            if before.stdout() == after.stdout() {
+                "unchanged"
+            } else {
+                "CHANGED"
+            }
+        );
+        let halt = |state: &State| state.halted().map(|reason| reason.exit_code());
+        if before.stdout() != after.stdout() || halt(&before) != halt(&after) {
+            return Err(String::from(
+                "the optimized program behaves differently on the given input, not writing it",
+            ));
+        }
+    }
+    Ok(optimized)
+}
+
+fn write_program(output: &str, image: &[u8]) {
+    let mut binary = parser::MAGIC.to_vec();
+    binary.extend_from_slice(image);
+    write_output(output, binary);
+}
+
+fn link(output: &str, files: &[String], opt: bool, stdin: Option<Vec<u8>>) -> bool {
+    let mut modules = Vec::new();
+    let mut ok = true;
+    for file in files {
+        let source = read_text(file);
+        match assembler::assemble_fragment(&source) {
+            Ok(fragment) => modules.push(linker::Module {
+                name: file.clone(),
+                fragment,
+            }),
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{}:{}: error: {}", file, error.line, error.message);
                 }
-                Err(ReadlineError::Eof) => {
-                    println!("EOF");
-                    break;
+                ok = false;
+            }
+        }
+    }
+    if !ok {
+        return false;
+    }
+
+    match linker::link(&modules) {
+        Ok(image) => {
+            let image = if opt {
+                optimize(&image, stdin)
+            } else {
+                Ok(image)
+            };
+            match image {
+                Ok(image) => {
+                    write_program(output, &image);
+                    true
                 }
-                Err(err) => {
-                    println!("Error: {:?}", err);
-                    break;
+                Err(e) => {
+                    eprintln!("--opt: {}", e);
+                    false
                 }
             }
-        } else {
-            computer.execute();
+        }
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            false
         }
     }
 }
 
-fn emulate(input: &[u8], stdin: Vec<u8>, debug: bool) {
-    let mut memory: [u8; 4096] = [0; 4096];
-    let mut l = input.len();
-    if l > 4096 {
-        l = 4096;
+fn verify_program(input: &[u8]) -> Result<bool, S8Error> {
+    let findings = verify::verify(program_bytes(input)?);
+    for finding in &findings {
+        let level = if finding.error { "error" } else { "note" };
+        println!("{:#06x}: {}: {}", finding.address, level, finding.message);
+    }
+    Ok(!findings.iter().any(|f| f.error))
+}
+
+fn parse_addresses(list: &str) -> Result<Vec<usize>, String> {
+    list.split(',')
+        .filter(|a| !a.trim().is_empty())
+        .map(|a| match source::parse_number(a) {
+            Some(address) => Ok(address as usize),
+            None => Err(format!("could not parse address {}", a)),
+        })
+        .collect()
+}
+
+fn parse_probes(spec: &str) -> Result<Vec<instrument::Probe>, String> {
+    spec.split(',')
+        .filter(|p| !p.trim().is_empty())
+        .map(|probe| {
+            let (address, registers) = probe
+                .split_once('=')
+                .ok_or_else(|| format!("probe {} must look like ADDR=r2+r3", probe))?;
+            let address = match parse_addresses(address)?[..] {
+                [address] => address,
+                _ => return Err(format!("probe {} needs one address", probe)),
+            };
+            let registers = registers
+                .split('+')
+                .map(|r| match source::parse_register(r.trim()) {
+                    Some(register) => Ok(register),
+                    None => Err(format!("could not parse register {}", r)),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(instrument::Probe { address, registers })
+        })
+        .collect()
+}
+
+fn check_calls(
+    input: &[u8],
+    stdin: Option<Vec<u8>>,
+    documented: &[usize],
+) -> Result<bool, S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let mut issues = callconv::check_static(&memory, documented);
+    if let Some(stdin) = stdin {
+        let mut computer = State::new(memory, stdin);
+        issues.extend(callconv::check_dynamic(&mut computer, documented));
     }
-    for (i, v) in input[7..l].iter().enumerate() {
-        memory[i] = *v;
+    for issue in &issues {
+        println!("{:#06x}: {}", issue.address, issue.message);
     }
-    let mut computer = State::new(memory, stdin);
+    Ok(issues.is_empty())
+}
 
-    if debug {
-        debugger(&mut computer);
-    } else {
-        while !computer.finished {
-            computer.execute()
+fn show_liveness(input: &[u8], names: &BTreeMap<usize, String>) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    for (entry, summary) in liveness::analyze(&memory) {
+        match names.get(&entry) {
+            Some(name) => println!("{:#06x} {}", entry, name),
+            None if entry == 0 => println!("{:#06x} (entry)", entry),
+            None => println!("{:#06x}", entry),
+        }
+        println!("  inputs:    {}", liveness::registers(summary.inputs));
+        println!("  outputs:   {}", liveness::registers(summary.outputs()));
+        println!("  clobbered: {}", liveness::registers(summary.clobbered()));
+        let dead: Vec<String> = summary
+            .dead
+            .iter()
+            .map(|(pc, r)| format!("{:#06x} r{}", pc, r))
+            .collect();
+        if !dead.is_empty() {
+            println!("  dead:      {}", dead.join(", "));
         }
     }
-    eprintln!("End state:\n{}", computer.display());
-    println!("{}", hex::encode(computer.stdout()));
+    Ok(())
 }
 
-fn main() {
-    let mut file = String::new();
-    let mut clean: bool = false;
-    let mut disass: bool = false;
-    let mut debug: bool = false;
-    let mut stdin = String::new();
-    {
-        let mut ap = ArgumentParser::new();
-        ap.set_description("Disassemble s8");
-        ap.refer(&mut file)
-            .add_argument("file", Store, "Filename to load")
-            .required();
-        ap.refer(&mut stdin)
-            .add_argument("stdin", Store, "Input data")
-            .required();
-        ap.refer(&mut clean)
-            .add_option(&["-c", "--clean"], StoreTrue, "Output just s8asm");
-        ap.refer(&mut disass).add_option(
-            &["-D", "--disassemble"],
-            StoreTrue,
-            "Do disassembly instead of running",
+fn infer_signatures(
+    input: &[u8],
+    corpus: &[Vec<u8>],
+    names: &BTreeMap<usize, String>,
+    max_cycles: usize,
+) -> Result<BTreeMap<usize, String>, S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let prototypes = infer::infer(memory, corpus, max_cycles)
+        .into_iter()
+        .map(|(entry, signature)| {
+            let name = names
+                .get(&entry)
+                .cloned()
+                .unwrap_or_else(|| format!("{:#06x}", entry));
+            (entry, signature.prototype(&name))
+        })
+        .collect();
+    Ok(prototypes)
+}
+
+fn show_dominators(input: &[u8]) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let cfg = cfg::Cfg::build(&memory);
+    let idoms = cfg.immediate_dominators();
+    for block in cfg.blocks.keys() {
+        match idoms.get(block) {
+            Some(idom) => println!("{:#06x}: idom {:#06x}", block, idom),
+            None => println!("{:#06x}: root", block),
+        }
+    }
+    Ok(())
+}
+
+fn show_loops(
+    input: &[u8],
+    stdin: Option<Vec<u8>>,
+    costs: Option<Arc<costs::CostModel>>,
+) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let cfg = cfg::Cfg::build(&memory);
+    let loops = cfg.loops(&memory);
+    let profiles = stdin.map(|stdin| {
+        let mut computer = State::new(memory, stdin).with_costs(costs);
+        cfg::profile_loops(&cfg, &loops, &mut computer, 1_000_000)
+    });
+
+    for (i, l) in loops.iter().enumerate() {
+        let hex = |blocks: &mut dyn Iterator<Item = &usize>| {
+            blocks
+                .map(|b| format!("{:#06x}", b))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        println!("loop at {:#06x}", l.header);
+        println!("  back edges from {}", hex(&mut l.back_edges.iter()));
+        println!("  blocks {}", hex(&mut l.body.iter()));
+        for exit in &l.exits {
+            println!(
+                "  exit {:#06x} -> {:#06x} {}",
+                exit.from, exit.to, exit.condition
+            );
+        }
+        if let Some(profiles) = &profiles {
+            let profile = &profiles[i];
+            println!(
+                "  entered {} time(s), {} iteration(s), {} cycle(s)",
+                profile.entries, profile.iterations, profile.cycles
+            );
+            if let (Some(min), Some(max)) = (profile.trips.iter().min(), profile.trips.iter().max())
+            {
+                println!("  iterations per entry: min {}, max {}", min, max);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn load_signatures(input: &[u8], database: &str) -> Result<Vec<signatures::Signature>, S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let text = read_text(database);
+    Ok(signatures::match_functions(
+        &memory,
+        &signatures::parse_database(&text),
+    ))
+}
+
+fn show_signatures(signatures: &[signatures::Signature]) {
+    for signature in signatures {
+        println!(
+            "{:#06x}: {:016x} {}",
+            signature.entry,
+            signature.hash,
+            signature.name.as_deref().unwrap_or("?")
+        );
+    }
+}
+
+fn region<'a>(input: &'a [u8], range: &str) -> Result<(usize, &'a [u8]), String> {
+    let program = program_bytes(input).map_err(|e| e.to_string())?;
+    match parse_addresses(range)?[..] {
+        [] => Ok((0, program)),
+        [start, end] if start <= end && end <= program.len() => Ok((start, &program[start..end])),
+        _ => Err(String::from("region must be START,END inside the program")),
+    }
+}
+
+fn crack_crib(start: usize, data: &[u8], crib: &str) {
+    for candidate in crib::recover_keys(data, crib.as_bytes()).iter().take(10) {
+        println!(
+            "{} key {} at {:#06x} ({:.0}% printable): {}",
+            candidate.scheme,
+            hex::encode(&candidate.key),
+            start + candidate.offset,
+            candidate.printable * 100.0,
+            crib::preview(&candidate.plaintext, 64)
         );
-        ap.refer(&mut debug)
-            .add_option(&["-d", "--debug"], StoreTrue, "Run in debug mode");
+    }
+}
 
-        ap.parse_args_or_exit();
+fn show_entropy(start: usize, data: &[u8], window: usize) {
+    let windows = entropy::windows(data, window);
+    let line: String = windows
+        .iter()
+        .map(|w| entropy::spark(w.entropy, 8.0))
+        .collect();
+    println!("entropy per {} bytes: {}", window, line);
+    for w in &windows {
+        println!(
+            "{:#06x}: {} {:.2} bits/byte",
+            start + w.start,
+            entropy::spark(w.entropy, 8.0),
+            w.entropy
+        );
     }
 
-    let input = fs::read(file).expect("Could not read input file");
-    let stdin = fs::read(stdin).expect("Second argument is a file with indata.");
+    let counts = entropy::histogram(data, 16);
+    let max = counts.iter().copied().max().unwrap_or(0) as f64;
+    let line: String = counts
+        .iter()
+        .map(|&c| entropy::spark(c as f64, max))
+        .collect();
+    println!("byte histogram 0x00..0xff: {}", line);
+    println!("overall {:.2} bits/byte", entropy::shannon(data));
+}
+
+fn show_serial_checks(input: &[u8]) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let listing = |pc: usize| format!("{:#06x}: {}", pc, parser::decode_at(&memory, pc));
+    for check in serial::find_serial_checks(&memory) {
+        let source = if check.reads_input {
+            "input read with LES"
+        } else {
+            "bytes loaded with LAST"
+        };
+        println!("serial check loop at {:#06x} ({})", check.header, source);
+        for table in &check.tables {
+            println!("  table at {:#06x}", table);
+        }
+        for pc in &check.transforms {
+            println!("  transform {}", listing(*pc));
+        }
+        println!("  compare {}", listing(check.compare));
+        println!(
+            "  branch at {:#06x} to {:#06x} {}",
+            check.branch,
+            check.target,
+            if check.exits_on_branch {
+                "leaves the loop when the comparison holds"
+            } else {
+                "stays in the loop when the comparison holds"
+            }
+        );
+    }
+    Ok(())
+}
 
-    if disass {
-        disassemble(&input, clean);
+fn write_keygen(
+    input: &[u8],
+    language: &str,
+    output: Option<String>,
+    max_cycles: usize,
+) -> Result<bool, S8Error> {
+    if language != "python" && language != "rust" {
+        eprintln!(
+            "--keygen: unknown language {}, expected python or rust",
+            language
+        );
+        std::process::exit(2);
+    }
+    let memory = parser::memory_image(program_bytes(input)?);
+    let checks = serial::find_serial_checks(&memory);
+    let check = match checks.iter().find(|c| c.reads_input) {
+        Some(check) => check,
+        None => {
+            eprintln!("No serial check that reads input found");
+            return Ok(false);
+        }
+    };
+    let model = match keygen::extract(memory, check, max_cycles) {
+        Ok(model) => model,
+        Err(e) => {
+            eprintln!("Serial check loop at {:#06x}: {}", check.header, e);
+            return Ok(false);
+        }
+    };
+    eprintln!(
+        "Serial check loop at {:#06x}: {} constraints, key \"{}\"",
+        model.header,
+        model.snapshots.len(),
+        printable(&model.key)
+    );
+    let script = if language == "python" {
+        model.python()
     } else {
-        emulate(&input, stdin, debug);
+        model.rust()
+    };
+    match output {
+        Some(path) => write_output(&path, script),
+        None => print!("{}", script),
+    }
+    Ok(true)
+}
+
+fn show_unpacked(
+    input: &[u8],
+    stdin: Vec<u8>,
+    output: Option<String>,
+    max_cycles: usize,
+    color: bool,
+) -> Result<bool, S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let stages = unpack::unpack(memory, stdin, max_cycles);
+    if stages.is_empty() {
+        println!("The program never executed code it wrote");
+    }
+    for stage in &stages {
+        let writers: Vec<String> = stage
+            .writers
+            .iter()
+            .map(|w| format!("{:#06x}", w))
+            .collect();
+        println!(
+            "Entered written code at {:#06x} after {} cycles: {:#06x}-{:#06x}, written by LAGR at {}",
+            stage.entry,
+            stage.cycles,
+            stage.start,
+            stage.start + stage.bytes.len() - 1,
+            writers.join(", ")
+        );
+        if let Some(prefix) = &output {
+            let path = format!("{}-{:#06x}.bin", prefix, stage.start);
+            write_output(&path, &stage.bytes);
+            println!(
+                "Saved to {} (list with --raw {} --base {:#06x})",
+                path, path, stage.start
+            );
+        }
+        let image = [parser::MAGIC, &stage.bytes[..]].concat();
+        for line in parser::disassemble(&image, stage.start)
+            .into_iter()
+            .flatten()
+        {
+            let instruction = paint(line.instruction.to_string(), color);
+            let marker = if line.pos == stage.entry { ">" } else { " " };
+            println!(
+                "{} {:#06x}: {:#06x}   {}",
+                marker, line.pos, line.op, instruction
+            );
+        }
+    }
+    Ok(!stages.is_empty())
+}
+
+fn show_obfuscation(
+    input: &[u8],
+    stdin: Option<Vec<u8>>,
+    max_cycles: usize,
+) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let findings = obfuscation::analyze(memory, stdin, max_cycles);
+    if findings.is_empty() {
+        println!("No obfuscation found");
+    }
+    for finding in findings {
+        println!("{:#06x}: {}", finding.address, finding.description);
+        println!("        {}", finding.suggestion);
+    }
+    Ok(())
+}
+
+fn layout_ranges(program: &[u8], stdin: Option<Vec<u8>>) -> Vec<layout::Range> {
+    let written = match stdin {
+        Some(stdin) => {
+            let mut computer = State::new(parser::memory_image(program), stdin);
+            layout::written_addresses(&mut computer, 1_000_000)
+        }
+        None => Vec::new(),
+    };
+    layout::layout(program, &written)
+}
+
+fn show_map(
+    input: &[u8],
+    stdin: Option<Vec<u8>>,
+    labels: &BTreeMap<usize, String>,
+) -> Result<(), S8Error> {
+    let program = program_bytes(input)?;
+    let ranges = layout_ranges(program, stdin);
+    println!("0x0000 {} 0x0fff", layout::overview(&ranges, 64));
+    println!("       (64 bytes per character: C code, W written at runtime, D data, . free)");
+    println!();
+    for segment in layout::segments(program, &ranges, labels) {
+        let line = format!(
+            "{:#06x}-{:#06x}  {:<18} {:>5} bytes  {}",
+            segment.start,
+            segment.end - 1,
+            segment.kind.to_string(),
+            segment.end - segment.start,
+            segment.label.unwrap_or_default()
+        );
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+fn show_layout(input: &[u8], stdin: Option<Vec<u8>>) -> Result<(), S8Error> {
+    let ranges = layout_ranges(program_bytes(input)?, stdin);
+    for range in &ranges {
+        println!(
+            "{:#06x}-{:#06x}: {} ({} bytes)",
+            range.start,
+            range.end - 1,
+            range.kind,
+            range.end - range.start
+        );
+    }
+    let free = ranges.iter().filter(|r| r.kind == layout::Kind::Free);
+    let total: usize = free.clone().map(|r| r.end - r.start).sum();
+    if let Some(largest) = free.max_by_key(|r| r.end - r.start) {
+        println!(
+            "{} bytes free, largest gap {:#06x}-{:#06x}",
+            total,
+            largest.start,
+            largest.end - 1
+        );
+    }
+    Ok(())
+}
+
+const MAX_UNDO: usize = 100;
+
+struct Debugger {
+    stepping: bool,
+    color: bool,
+    breakpoints: Vec<usize>,
+    watchpoints: Vec<watch::Watchpoint>,
+    logs: Vec<watch::Log>,
+    history: history::History,
+    trace: Option<Arc<trace::Trace>>,
+    snapshot: Option<State>,
+    tree: snaptree::SnapshotTree,
+    undo: Vec<(String, State)>,
+    before: State,
+    aliases: BTreeMap<usize, String>,
+    regions: Vec<project::Region>,
+    variables: Vec<project::Variable>,
+    types: Vec<types::Typed>,
+}
+
+impl Debugger {
+    fn new(computer: &State, color: bool, stepping: bool) -> Debugger {
+        Debugger {
+            stepping,
+            color,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            logs: Vec::new(),
+            history: history::History::default(),
+            trace: None,
+            snapshot: None,
+            tree: snaptree::SnapshotTree::default(),
+            undo: Vec::new(),
+            before: computer.clone(),
+            aliases: BTreeMap::new(),
+            regions: Vec::new(),
+            variables: Vec::new(),
+            types: Vec::new(),
+        }
     }
 
-    std::process::exit(0);
+    fn command(&mut self, computer: &mut State, cmd: &str) {
+        if cmd.starts_with('c') {
+            self.stepping = false;
+            self.before = computer.clone();
+            computer.iter().next();
+        } else if let Some(spec) = cmd.strip_prefix("name") {
+            if let Err(e) = self.name(spec) {
+                eprintln!("Error: {}", e);
+            }
+        } else if cmd.trim() == "markers" {
+            match &self.trace {
+                Some(trace) => {
+                    for marker in &trace.markers {
+                        println!("{:>8} {}", marker.cycles, marker.name);
+                    }
+                }
+                None => eprintln!("No trace loaded"),
+            }
+        } else if cmd.starts_with('m') {
+            let tokens = cmd.split_whitespace().collect::<Vec<&str>>();
+            let range = match tokens[..] {
+                [_, from, to] => usize::from_str_radix(from, 16)
+                    .and_then(|from| Ok((from, usize::from_str_radix(to, 16)?)))
+                    .ok(),
+                _ => None,
+            };
+            let (from, to) = match range {
+                Some(range) => range,
+                None => {
+                    eprintln!("Error: expected m FROM TO in hex");
+                    return;
+                }
+            };
+            match self.typed_dump(computer, from, to) {
+                Ok(dump) => println!("{}", dump),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        } else if let Some(args) = cmd.strip_prefix("dumpmem") {
+            let tokens = args.split_whitespace().collect::<Vec<&str>>();
+            let region = match tokens[..] {
+                [start, end, path] => dump::Region::new(start, end, path),
+                _ => Err(String::from("Usage: dumpmem START END FILE")),
+            };
+            if let Err(e) = region.and_then(|r| r.write(computer)) {
+                eprintln!("{}", e);
+            }
+        } else if let Some(args) = cmd.strip_prefix("loadmem") {
+            let before = computer.clone();
+            let result = match args.split_whitespace().collect::<Vec<&str>>()[..] {
+                [start, path] => dump::load(computer, start, path),
+                _ => Err(String::from("Usage: loadmem START FILE")),
+            };
+            match result {
+                Ok(()) => self.push_undo(cmd, before),
+                Err(e) => eprintln!("{}", e),
+            }
+        } else if let Some(spec) = cmd.strip_prefix("set ") {
+            let before = computer.clone();
+            match self.set(computer, spec) {
+                Ok(()) => self.push_undo(cmd, before),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        } else if let Some(address) = cmd.strip_prefix("jump") {
+            match expr::Expr::parse(address, &self.names(computer.pc()))
+                .and_then(|e| e.eval(computer))
+            {
+                Ok(address) => {
+                    self.push_undo(cmd, computer.clone());
+                    computer.set_pc((address & 0xfff) as usize);
+                }
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        } else if let Some(count) = cmd.strip_prefix("skip") {
+            let count = match count.trim() {
+                "" => Some(1),
+                count => source::parse_number(count),
+            };
+            match count {
+                Some(count) => {
+                    self.push_undo(cmd, computer.clone());
+                    computer.set_pc(computer.pc() + 2 * count as usize);
+                }
+                None => eprintln!("Usage: skip [COUNT]"),
+            }
+        } else if cmd.trim() == "undo" {
+            match self.undo.pop() {
+                Some((action, state)) => {
+                    computer.restore(&state);
+                    self.history.rewind(state.cycles());
+                    eprintln!("Undid {}", action);
+                }
+                None => eprintln!("Nothing to undo"),
+            }
+        } else if cmd.starts_with('d') {
+            let tokens = cmd.split_whitespace().collect::<Vec<&str>>();
+            let count = tokens.get(1).and_then(|n| n.parse().ok()).unwrap_or(8);
+            let mut address = computer.pc();
+            for _ in 0..count {
+                if address + 2 > 0x1000 {
+                    break;
+                }
+                let instruction = computer.instruction_at(address);
+                println!(
+                    "{:#06x}: {}{}",
+                    address,
+                    paint(instruction.to_string(), self.color),
+                    alias_comment(&instruction, &self.names(address), &self.regions)
+                );
+                address += 2;
+            }
+        } else if cmd.starts_with('b') {
+            let tokens = cmd.split_whitespace().collect::<Vec<&str>>();
+            if tokens.len() == 2 {
+                match usize::from_str_radix(tokens[1], 16) {
+                    Ok(num) => self.breakpoints.push(num),
+                    Err(_) => eprintln!("Could not parse address"),
+                };
+            } else {
+                eprintln!("Breakpoints:");
+            }
+        } else if let Some(address) = cmd.strip_prefix("whowrote") {
+            let address = expr::Expr::parse(address, &self.names(computer.pc()))
+                .and_then(|e| e.eval(computer));
+            match address.map(|a| (a & 0xfff) as usize) {
+                Ok(address) => match self.history.who_wrote(address, computer.cycles()) {
+                    Some((step, source)) => println!(
+                        "{:#06x} was written at cycle {} by {:#06x}: {} (r{} = {:02x}h)",
+                        address,
+                        step.cycles,
+                        step.pc,
+                        step.instruction(),
+                        source,
+                        step.registers[source]
+                    ),
+                    None => println!(
+                        "{:#06x} has not been written since the debugger started",
+                        address
+                    ),
+                },
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        } else if matches!(cmd.trim(), "w" | "watchdiff") {
+            for address in computer
+                .dirty()
+                .into_iter()
+                .filter(|&a| self.before.memory_at(a) != computer.memory_at(a))
+            {
+                println!(
+                    "{:#06x}: {:02x} -> {:02x}",
+                    address,
+                    self.before.memory_at(address),
+                    computer.memory_at(address)
+                );
+            }
+        } else if let Some(condition) = cmd
+            .strip_prefix("watch")
+            .filter(|c| c.is_empty() || c.starts_with(char::is_whitespace))
+        {
+            if condition.trim().is_empty() {
+                for watchpoint in &self.watchpoints {
+                    eprintln!("{}", watchpoint);
+                }
+            } else {
+                match watch::Watchpoint::parse(condition) {
+                    Ok(mut watchpoint) => {
+                        watchpoint.triggered(watchpoint.value(computer));
+                        self.watchpoints.push(watchpoint);
+                    }
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+        } else if let Some(spec) = cmd.strip_prefix("log") {
+            match spec.trim() {
+                "" => {
+                    for log in &self.logs {
+                        eprintln!("{}", log);
+                    }
+                }
+                "off" => self.logs.clear(),
+                spec => match watch::Log::parse(spec, &self.names(computer.pc())) {
+                    Ok(mut log) => match log.sample(computer) {
+                        Ok(()) => self.logs.push(log),
+                        Err(e) => eprintln!("Error: {}", e),
+                    },
+                    Err(e) => eprintln!("Error: {}", e),
+                },
+            }
+        } else if cmd.starts_with('i') {
+            self.show(computer);
+        } else if let Some(target) = cmd.strip_prefix("seek") {
+            let marker = self.trace.as_ref().and_then(|trace| {
+                trace
+                    .markers
+                    .iter()
+                    .find(|m| m.name == target.trim())
+                    .map(|m| m.cycles as u32)
+            });
+            let target = marker
+                .or_else(|| source::parse_number(target))
+                .ok_or_else(|| String::from("expected seek CYCLE or seek MARKER"))
+                .and_then(|target| self.seek(computer, target as usize));
+            if let Err(e) = target {
+                eprintln!("Error: {}", e);
+            }
+        } else if let Some(spec) = cmd.strip_prefix("slice") {
+            match self.slice(computer, spec) {
+                Ok(()) => (),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        } else if let Some(text) = cmd.strip_prefix("p ") {
+            match expr::Expr::parse(text, &self.names(computer.pc())).and_then(|e| e.eval(computer))
+            {
+                Ok(value) => println!("{}", expr::radices(value)),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        } else if let Some(name) = cmd.strip_prefix("snap") {
+            match self.tree.save(name.trim(), computer) {
+                Ok(()) => eprintln!("Saved {} at pc {:#06x}", name.trim(), computer.pc()),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        } else if let Some(name) = cmd.strip_prefix("goto") {
+            match self.tree.goto(name.trim()) {
+                Ok(snapshot) => {
+                    computer.restore(snapshot);
+                    self.history.rewind(snapshot.cycles());
+                }
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        } else if let Some(names) = cmd.strip_prefix("rename") {
+            let renamed = match names.split_whitespace().collect::<Vec<&str>>()[..] {
+                [old, new] => self.tree.rename(old, new),
+                _ => Err(String::from("Usage: rename OLD NEW")),
+            };
+            if let Err(e) = renamed {
+                eprintln!("Error: {}", e);
+            }
+        } else if cmd.trim() == "tree" {
+            print!("{}", self.tree.text());
+        } else if cmd.starts_with('s') {
+            self.snapshot = Some(computer.clone());
+            eprintln!("Saved snapshot at pc {:#06x}", computer.pc());
+        } else if cmd.starts_with('r') {
+            match &self.snapshot {
+                Some(snapshot) => {
+                    computer.restore(snapshot);
+                    self.history.rewind(snapshot.cycles());
+                }
+                None => eprintln!("No snapshot saved"),
+            }
+        } else {
+            self.before = computer.clone();
+            computer.iter().next();
+        }
+    }
+
+    fn typed_dump(&self, computer: &State, from: usize, to: usize) -> Result<String, String> {
+        let memory: Vec<u8> = (0..parser::MEMORY_SIZE)
+            .map(|a| computer.memory_at(a))
+            .collect();
+        let mut dump = String::new();
+        let mut address = from;
+        for typed in &self.types {
+            let end = typed.end(&memory);
+            if typed.start >= to || end <= address {
+                continue;
+            }
+            if typed.start > address {
+                dump += &computer.memory_dump(address, typed.start)?;
+                if !dump.ends_with('\n') {
+                    dump.push('\n');
+                }
+            }
+            let name = match project::name_at(&self.regions, typed.start) {
+                Some((region, 0)) => format!("{}: ", region.name),
+                _ => String::new(),
+            };
+            dump += &format!(
+                "{:04x}: {}{} = {}\n",
+                typed.start,
+                name,
+                typed.data,
+                typed.render(&memory)
+            );
+            address = address.max(end);
+        }
+        if address < to {
+            dump += &computer.memory_dump(address, to)?;
+        }
+        Ok(dump)
+    }
+
+    fn names(&self, pc: usize) -> BTreeMap<usize, String> {
+        project::names_at(&self.aliases, &self.variables, pc)
+    }
+
+    fn name(&mut self, spec: &str) -> Result<(), String> {
+        let usage = || String::from("expected name rN NAME START-END or name START[-END] NAME");
+        let tokens: Vec<&str> = spec.split_whitespace().collect();
+        match tokens[..] {
+            [] => {
+                for region in &self.regions {
+                    println!("label {}", region);
+                }
+                for variable in &self.variables {
+                    println!("var {}", variable);
+                }
+            }
+            [register, name, range] => {
+                let register = source::parse_register(register)
+                    .filter(|&r| r < 16)
+                    .ok_or_else(usage)?;
+                let (start, end) = match project::parse_range(range) {
+                    Some((start, Some(end))) => (start, end),
+                    _ => return Err(usage()),
+                };
+                self.variables
+                    .retain(|v| !(v.register == register && v.start == start && v.end == end));
+                self.variables.push(project::Variable {
+                    start,
+                    end,
+                    register,
+                    name: name.to_string(),
+                });
+            }
+            [range, name] => {
+                let (start, end) = project::parse_range(range).ok_or_else(usage)?;
+                self.regions.retain(|r| r.start != start);
+                self.regions.push(project::Region {
+                    start,
+                    end,
+                    name: name.to_string(),
+                });
+            }
+            _ => return Err(usage()),
+        }
+        Ok(())
+    }
+
+    fn push_undo(&mut self, cmd: &str, before: State) {
+        if self.undo.len() == MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.undo.push((cmd.trim().to_string(), before));
+    }
+
+    fn set(&self, computer: &mut State, spec: &str) -> Result<(), String> {
+        let (location, value) = spec
+            .split_once('=')
+            .ok_or_else(|| String::from("expected set LOCATION = VALUE"))?;
+        let value = expr::Expr::parse(value, &self.names(computer.pc()))?.eval(computer)?;
+        match expr::Expr::parse(location, &self.names(computer.pc()))? {
+            expr::Expr::Register(r) => computer.set_register(r, value as u8),
+            expr::Expr::Flagg => computer.set_flagg(value != 0),
+            expr::Expr::Pc => computer.set_pc((value & 0xfff) as usize),
+            expr::Expr::Pointer => {
+                computer.set_register(0, value as u8);
+                computer.set_register(1, (value >> 8) as u8 & 0x0f);
+            }
+            expr::Expr::Memory(address) => {
+                let address = (address.eval(computer)? & 0xfff) as usize;
+                computer.write_memory(address, &[value as u8])?;
+            }
+            _ => {
+                return Err(String::from(
+                    "expected a register, flagg, pc, ptr or [ADDRESS]",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn seek(&mut self, computer: &mut State, target: usize) -> Result<(), String> {
+        if let Some(trace) = self.trace.as_ref().filter(|_| target < computer.cycles()) {
+            let i = trace
+                .block_at(target)
+                .ok_or_else(|| format!("the trace starts after cycle {}", target))?;
+            let block = trace.block(i)?;
+            computer.load_checkpoint(&block.keyframe)?;
+            self.history.rewind(computer.cycles());
+            for step in block.steps.iter().take_while(|s| s.cycles < target) {
+                while computer.cycles() < step.cycles && computer.halted().is_none() {
+                    self.history.record(computer);
+                    computer.iter().next();
+                }
+                if step.pc != computer.pc() || step.cycles != computer.cycles() {
+                    return Err(format!(
+                        "execution diverged from the trace at cycle {}",
+                        step.cycles
+                    ));
+                }
+                self.history.record(computer);
+                computer.iter().next();
+            }
+        } else if target < computer.cycles() {
+            return Err(String::from(
+                "seeking backwards needs a trace loaded with --load-trace",
+            ));
+        }
+        while computer.cycles() < target && computer.halted().is_none() {
+            self.history.record(computer);
+            computer.iter().next();
+        }
+        eprintln!("At cycle {}", computer.cycles());
+        Ok(())
+    }
+
+    fn slice(&self, computer: &State, spec: &str) -> Result<(), String> {
+        let usage = || String::from("expected slice back|forward LOCATION [at CYCLE]");
+        let (spec, at) = match spec.rsplit_once(" at ") {
+            Some((spec, at)) => (
+                spec,
+                Some(source::parse_number(at).ok_or_else(usage)? as usize),
+            ),
+            None => (spec, None),
+        };
+        let (direction, location) = spec.trim().split_once(' ').ok_or_else(usage)?;
+        let location = match expr::Expr::parse(location, &self.names(computer.pc()))? {
+            expr::Expr::Register(r) => history::Location::Register(r),
+            expr::Expr::Flagg => history::Location::Flagg,
+            expr::Expr::Memory(address) => {
+                history::Location::Memory((address.eval(computer)? & 0xfff) as usize)
+            }
+            _ => return Err(String::from("expected a register, flagg or [ADDRESS]")),
+        };
+        let slice = match direction {
+            "back" => self
+                .history
+                .backward_slice(location, at.unwrap_or_else(|| computer.cycles())),
+            "forward" => self
+                .history
+                .forward_slice(location, at.unwrap_or(0), computer.cycles()),
+            _ => return Err(usage()),
+        };
+        for step in &slice {
+            println!(
+                "{:>8} {:#06x}: {}",
+                step.cycles,
+                step.pc,
+                paint(step.instruction().to_string(), self.color)
+            );
+        }
+        eprintln!(
+            "{} of {} recorded steps",
+            slice.len(),
+            self.history.steps.len()
+        );
+        Ok(())
+    }
+
+    fn show(&self, computer: &State) {
+        println!("{}", computer.display());
+        let names = self.names(computer.pc());
+        if !names.is_empty() {
+            let values: Vec<String> = names
+                .iter()
+                .map(|(&r, name)| format!("{}={:02x}h", name, computer.register(r)))
+                .collect();
+            println!("{}", values.join(" "));
+        }
+        if let parser::Instruction::Last(_) | parser::Instruction::Lagr(_) =
+            computer.next_instruction()
+        {
+            let address = computer.pointer();
+            println!(
+                "[{}] = {:02x}h",
+                symbolic(address, &self.regions),
+                computer.memory_at(address)
+            );
+        }
+    }
+
+    fn script(&mut self, computer: &mut State, commands: &str) {
+        for cmd in commands.split(';') {
+            self.command(computer, cmd.trim());
+        }
+    }
+
+    fn check_stops(&mut self, computer: &State) {
+        self.history.record(computer);
+        if !self.stepping && interrupted() {
+            eprintln!("Interrupted");
+            self.stepping = true;
+        }
+        if self.breakpoints.contains(&computer.pc()) {
+            eprintln!("Hit breakpoint");
+            self.stepping = true;
+        }
+        self.logs.retain_mut(|log| match log.sample(computer) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Stopped log {}: {}", log, e);
+                false
+            }
+        });
+        for watchpoint in &mut self.watchpoints {
+            let value = watchpoint.value(computer);
+            if watchpoint.triggered(value) {
+                eprintln!("Hit watchpoint {} ({:#04x})", watchpoint, value);
+                self.stepping = true;
+            }
+        }
+    }
+}
+
+fn debugger(
+    computer: &mut State,
+    color: bool,
+    stepping: bool,
+    session: &mut project::Session,
+    trace: Option<Arc<trace::Trace>>,
+) {
+    let mut rl = Editor::<()>::new();
+    let mut debugger = Debugger::new(computer, color, stepping);
+    debugger.trace = trace;
+    debugger.breakpoints = std::mem::take(&mut session.breakpoints);
+    debugger.aliases = session.aliases.clone();
+    debugger.regions = session.regions.clone();
+    debugger.variables = session.variables.clone();
+    debugger.types = session.types.clone();
+    debugger.watchpoints = session
+        .watchpoints
+        .iter()
+        .filter_map(|w| watch::Watchpoint::parse(w).ok())
+        .collect();
+    if let Some(checkpoint) = &session.checkpoint {
+        let mut saved = computer.clone();
+        match saved.load_checkpoint(checkpoint) {
+            Ok(()) => {
+                eprintln!("Loaded snapshot at pc {:#06x} (r restores it)", saved.pc());
+                debugger.snapshot = Some(saved);
+            }
+            Err(e) => eprintln!("Could not load snapshot: {}", e),
+        }
+    }
+
+    while computer.halted().is_none() {
+        debugger.check_stops(computer);
+        if debugger.stepping {
+            debugger.show(computer);
+            let readline = rl.readline("→ ");
+            match readline {
+                Ok(cmd) => {
+                    rl.add_history_entry(cmd.as_str());
+                    debugger.command(computer, &cmd);
+                }
+                Err(ReadlineError::Interrupted) => {
+                    println!("Abort");
+                    computer.halt(HaltReason::UserInterrupt);
+                    break;
+                }
+                Err(ReadlineError::Eof) => {
+                    println!("EOF");
+                    break;
+                }
+                Err(err) => {
+                    println!("Error: {:?}", err);
+                    break;
+                }
+            }
+        } else {
+            computer.iter().next();
+        }
+    }
+    session.breakpoints = debugger.breakpoints;
+    session.watchpoints = debugger.watchpoints.iter().map(|w| w.to_string()).collect();
+    session.checkpoint = debugger.snapshot.as_ref().map(State::checkpoint);
+    session.regions = debugger.regions;
+    session.variables = debugger.variables;
+}
+
+#[derive(Default)]
+struct RunOptions {
+    costs: Option<Arc<costs::CostModel>>,
+    max_cycles: Option<usize>,
+    on_write_stdout: Option<String>,
+    on_fault: Option<String>,
+    trace: Option<String>,
+    trace_filter: Option<expr::Expr>,
+    markers: Vec<(usize, String)>,
+    stubs: Vec<stub::Stub>,
+    ftrace: bool,
+    loaded_trace: Option<Arc<trace::Trace>>,
+    scan_memory: Option<regex::bytes::Regex>,
+    dump_regions: Vec<dump::Region>,
+}
+
+fn emulate(
+    input: &[u8],
+    stdin: Vec<u8>,
+    stepping: bool,
+    session: Option<&mut project::Session>,
+    color: bool,
+    options: &RunOptions,
+) -> Result<emulator::RunResult, S8Error> {
+    let program = program_bytes(input)?;
+    let memory = parser::memory_image(program);
+    let mut computer = State::new(memory, stdin).with_costs(options.costs.clone());
+    if let Err(e) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst)) {
+        eprintln!("Could not install Ctrl-C handler: {}", e);
+    }
+    let started = std::time::Instant::now();
+    let mut coverage = None;
+
+    if let Some(session) = session {
+        debugger(
+            &mut computer,
+            color,
+            stepping,
+            session,
+            options.loaded_trace.clone(),
+        );
+    } else {
+        let limit = options.max_cycles.unwrap_or(usize::MAX);
+        let executed = coverage.insert(emulator::Coverage::default());
+        let mut hooks = Debugger::new(&computer, color, false);
+        let mut scanner = options.scan_memory.clone().map(scan::MemoryScanner::new);
+        let mut pending: Vec<&dump::Region> = options.dump_regions.iter().collect();
+        let mut tracer = None;
+        let mut ftracer = options.ftrace.then(ftrace::FunctionTracer::default);
+        if let Some(path) = &options.trace {
+            match trace::TraceWriter::create(path, replay::program_hash(program)) {
+                Ok(writer) => tracer = Some(writer),
+                Err(e) => eprintln!("Could not write trace {}: {}", path, e),
+            }
+        }
+        while computer.halted().is_none() {
+            if computer.cycles() >= limit {
+                computer.halt(HaltReason::TickLimit(limit));
+            }
+            if interrupted() {
+                eprintln!("Interrupted at {:#06x}", computer.pc());
+                debugger(
+                    &mut computer,
+                    color,
+                    true,
+                    &mut project::Session::default(),
+                    options.loaded_trace.clone(),
+                );
+                break;
+            }
+            pending.retain(|region| {
+                if region.at != Some(computer.pc()) {
+                    return true;
+                }
+                if let Err(e) = region.write(&computer) {
+                    eprintln!("{}", e);
+                }
+                false
+            });
+            let written = computer.stdout().len();
+            if let Some(writer) = &mut tracer {
+                for (_, name) in options.markers.iter().filter(|m| m.0 == computer.pc()) {
+                    writer.mark(&computer, name);
+                }
+            }
+            let wanted = match &options.trace_filter {
+                Some(filter) => filter.eval(&computer).is_ok_and(|v| v != 0),
+                None => true,
+            };
+            let failed = match (&mut tracer, computer.halted()) {
+                (Some(writer), None) if wanted => writer.record(&computer).err(),
+                _ => None,
+            };
+            if let Some(e) = failed {
+                eprintln!("Could not write trace: {}", e);
+                tracer = None;
+            }
+            let stored = match computer.next_instruction() {
+                parser::Instruction::Lagr(_) => Some(computer.pointer()),
+                parser::Instruction::Tur(target) => {
+                    let target = usize::from(target);
+                    if let Some(stub) = options.stubs.iter().find(|s| s.address == target) {
+                        computer.set_pc(computer.pc() + 2);
+                        match &stub.effect {
+                            stub::Effect::Return(registers) => {
+                                for &(register, value) in registers {
+                                    computer.set_register(register, value);
+                                }
+                            }
+                            stub::Effect::Script(commands) => hooks.script(&mut computer, commands),
+                        }
+                        continue;
+                    }
+                    None
+                }
+                _ => None,
+            };
+            if let Some(line) = ftracer.as_mut().and_then(|t| t.before(&computer)) {
+                eprintln!("{}", line);
+            }
+            let pc = computer.pc();
+            if let Some(Ok(_)) = computer.iter().next() {
+                executed.record(pc);
+            }
+            if let (Some(scanner), Some(address)) = (&mut scanner, stored) {
+                for found in scanner.written(&computer, address) {
+                    eprintln!(
+                        "Cycle {}, pc {:#06x}: {} appeared in memory at {:#06x}",
+                        computer.cycles(),
+                        pc,
+                        printable(&found.bytes),
+                        found.offset
+                    );
+                }
+            }
+            if let Some(commands) = &options.on_write_stdout {
+                if computer.stdout().len() > written {
+                    hooks.script(&mut computer, commands);
+                }
+            }
+            if let (
+                Some(commands),
+                Some(HaltReason::Fault(_)) | Some(HaltReason::PcOutOfRange(_)),
+            ) = (&options.on_fault, computer.halted())
+            {
+                hooks.script(&mut computer, commands);
+            }
+        }
+        if let Some(Err(e)) = tracer.map(trace::TraceWriter::finish) {
+            eprintln!("Could not write trace: {}", e);
+        }
+    }
+    let limit = options.max_cycles.unwrap_or(usize::MAX);
+    let unfinished = if computer.cycles() >= limit {
+        HaltReason::TickLimit(limit)
+    } else {
+        HaltReason::UserInterrupt
+    };
+    Ok(computer.into_result(unfinished, started.elapsed(), coverage))
+}
+
+fn show_result(result: &emulator::RunResult) {
+    eprintln!("{}", result);
+    if let Some(computer) = &result.state {
+        eprintln!("End state:\n{}", computer.display());
+    }
+    println!("{}", hex::encode(&result.output));
+}
+
+fn stream_events(input: &[u8], stdin: Vec<u8>, paused: bool) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let driver = driver::StateDriver::spawn(State::new(memory, stdin), paused);
+    let controller = driver.controller();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let command = match line.as_deref().map(str::trim) {
+                Ok("pause") => driver::Command::Pause,
+                Ok("resume") => driver::Command::Resume,
+                Ok("step") => driver::Command::Step,
+                Ok("stop") => driver::Command::Stop,
+                Err(_) => break,
+                Ok(other) => {
+                    eprintln!("Unknown command {}", other);
+                    continue;
+                }
+            };
+            if controller.send(command).is_err() {
+                return;
+            }
+        }
+        let _ = controller.send(driver::Command::Resume);
+    });
+
+    for event in driver.events() {
+        let json = match event {
+            emulator::StepEvent::Step {
+                pc,
+                instruction,
+                cycles,
+            } => serde_json::json!({
+                "event": "step",
+                "pc": pc,
+                "instruction": instruction.to_string().replace('\t', " "),
+                "cycles": cycles,
+            }),
+            emulator::StepEvent::Output(byte) => {
+                serde_json::json!({ "event": "output", "byte": byte })
+            }
+            emulator::StepEvent::Paused { pc } => {
+                serde_json::json!({ "event": "paused", "pc": pc })
+            }
+            emulator::StepEvent::Halted { cycles, reason } => {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "event": "halted",
+                        "cycles": cycles,
+                        "reason": reason.map(|r| r.to_string()),
+                        "exit_code": reason.map_or(0, |r| r.exit_code()),
+                    })
+                );
+                break;
+            }
+        };
+        println!("{}", json);
+    }
+    driver.join();
+    Ok(())
+}
+
+fn run_parallel(input: &[u8], inputs: &[String]) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let mut computers: Vec<State> = inputs
+        .iter()
+        .map(|path| State::new(memory, read_file(path, false)))
+        .collect();
+    emulator::run_many(&mut computers, 1_000_000);
+    for (path, computer) in inputs.iter().zip(&computers) {
+        println!(
+            "{}: {} ({} cycles{})",
+            path,
+            hex::encode(computer.stdout()),
+            computer.cycles(),
+            match computer.halted() {
+                Some(HaltReason::Stopp) => String::new(),
+                Some(reason) => format!(", {}", reason),
+                None => String::from(", unfinished"),
+            }
+        );
+    }
+    Ok(())
+}
+
+fn show_input_timeline(
+    input: &[u8],
+    stdin: Vec<u8>,
+    costs: Option<Arc<costs::CostModel>>,
+    max_cycles: Option<usize>,
+    names: &BTreeMap<usize, String>,
+) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let functions = functions::find_functions(&memory);
+    let mut computer = State::new(memory, stdin).with_costs(costs);
+    let limit = max_cycles.unwrap_or(usize::MAX);
+    for span in accounting::input_timeline(&mut computer, &functions, limit) {
+        let function = match names.get(&span.function) {
+            Some(name) => format!("{:#06x} {}", span.function, name),
+            None => format!("{:#06x}", span.function),
+        };
+        let (start, end) = (span.cycles.0, span.cycles.1.saturating_sub(1));
+        match span.bytes {
+            None if span.cycles.0 == span.cycles.1 => (),
+            None => println!(
+                "before any input was read, cycles {}-{} ran in function {}",
+                start, end, function
+            ),
+            Some((first, last)) if first == last => println!(
+                "byte {} of input was processed in cycles {}-{} by function {}",
+                first, start, end, function
+            ),
+            Some((first, last)) => println!(
+                "bytes {}-{} of input were processed in cycles {}-{} by function {}",
+                first, last, start, end, function
+            ),
+        }
+    }
+    match computer.halted() {
+        Some(reason) => println!("{} cycles, {}", computer.cycles(), reason),
+        None => println!("{} cycles, unfinished", computer.cycles()),
+    }
+    Ok(())
+}
+
+fn show_function_cycles(
+    input: &[u8],
+    stdin: Vec<u8>,
+    costs: Option<Arc<costs::CostModel>>,
+    max_cycles: Option<usize>,
+    names: &BTreeMap<usize, String>,
+) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let functions = functions::find_functions(&memory);
+    let mut computer = State::new(memory, stdin).with_costs(costs);
+    let limit = max_cycles.unwrap_or(usize::MAX);
+    let table = accounting::function_cycles(&mut computer, &functions, limit);
+    let total = computer.cycles().max(1);
+
+    let mut rows: Vec<_> = table.iter().collect();
+    rows.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+    println!(
+        "{:<24} {:>7} {:>10} {:>7} {:>10} {:>7}",
+        "function", "calls", "inclusive", "%", "exclusive", "%"
+    );
+    for (entry, cycles) in rows {
+        let name = match names.get(entry) {
+            Some(name) => format!("{:#06x} {}", entry, name),
+            None => format!("{:#06x}", entry),
+        };
+        println!(
+            "{:<24} {:>7} {:>10} {:>6.1}% {:>10} {:>6.1}%",
+            name,
+            cycles.calls,
+            cycles.inclusive,
+            cycles.inclusive as f64 * 100.0 / total as f64,
+            cycles.exclusive,
+            cycles.exclusive as f64 * 100.0 / total as f64
+        );
+    }
+    match computer.halted() {
+        Some(reason) => println!("{} cycles, {}", computer.cycles(), reason),
+        None => println!("{} cycles, unfinished", computer.cycles()),
+    }
+    Ok(())
+}
+
+fn show_branch_coverage(
+    input: &[u8],
+    corpus: &[(String, Vec<u8>)],
+    costs: Option<Arc<costs::CostModel>>,
+    max_cycles: usize,
+) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let cfg = cfg::Cfg::build(&memory);
+    let mut coverage = coverage::Coverage::default();
+    for (name, stdin) in corpus {
+        let mut computer = State::new(memory, stdin.clone()).with_costs(costs.clone());
+        coverage.record(&cfg, &mut computer, max_cycles);
+        match computer.halted() {
+            Some(reason) => println!("{}: {} cycles, {}", name, computer.cycles(), reason),
+            None => println!("{}: {} cycles, unfinished", name, computer.cycles()),
+        }
+    }
+
+    let mut unflipped = 0;
+    let mut total = 0;
+    for pc in parser::reachable(&memory) {
+        let target = match parser::decode_at(&memory, pc) {
+            parser::Instruction::BHopp(target) => target,
+            _ => continue,
+        };
+        total += 1;
+        let (taken, not_taken) = coverage
+            .branches
+            .get(&pc)
+            .map_or((0, 0), |b| (b.taken, b.not_taken));
+        let note = match (taken, not_taken) {
+            (0, 0) => " never executed",
+            (0, _) => " never taken",
+            (_, 0) => " always taken",
+            _ => "",
+        };
+        if !note.is_empty() {
+            unflipped += 1;
+        }
+        println!(
+            "{:#06x} BHOPP {:#06x}: taken {}, not taken {}{}",
+            pc, target, taken, not_taken, note
+        );
+    }
+    println!("{} of {} branches never flipped", unflipped, total);
+
+    let edges = coverage::edges(&cfg);
+    let missed: Vec<String> = edges
+        .difference(&coverage.edges)
+        .map(|(from, to)| format!("{:#06x} -> {:#06x}", from, to))
+        .collect();
+    println!(
+        "{} of {} edges executed",
+        edges.len() - missed.len(),
+        edges.len()
+    );
+    for edge in missed {
+        println!("  never executed {}", edge);
+    }
+    Ok(())
+}
+
+fn load_corpus(stdin: &[String], indata: &Option<Vec<u8>>) -> Vec<(String, Vec<u8>)> {
+    if stdin.len() > 1 {
+        stdin
+            .iter()
+            .map(|path| (path.clone(), read_file(path, false)))
+            .collect()
+    } else {
+        vec![(
+            stdin.first().cloned().unwrap_or_default(),
+            indata.clone().unwrap_or_else(prompt_input),
+        )]
+    }
+}
+
+fn write_coverage(
+    input: &[u8],
+    corpus: &[(String, Vec<u8>)],
+    costs: Option<Arc<costs::CostModel>>,
+    max_cycles: usize,
+    path: &str,
+) -> Result<(), S8Error> {
+    let program = program_bytes(input)?;
+    let memory = parser::memory_image(program);
+    let mut hits = coverage::Hits {
+        program: replay::program_hash(program),
+        ..Default::default()
+    };
+    for (name, stdin) in corpus {
+        let mut computer = State::new(memory, stdin.clone()).with_costs(costs.clone());
+        hits.record(&mut computer, max_cycles);
+        match computer.halted() {
+            Some(reason) => println!("{}: {} cycles, {}", name, computer.cycles(), reason),
+            None => println!("{}: {} cycles, unfinished", name, computer.cycles()),
+        }
+    }
+    write_output(path, hits.to_string());
+    println!(
+        "{} instructions executed, written to {}",
+        hits.counts.len(),
+        path
+    );
+    Ok(())
+}
+
+fn read_coverage(paths: &str) -> coverage::Hits {
+    let mut hits = coverage::Hits::default();
+    for path in paths.split(',') {
+        let parsed = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| coverage::parse(&text))
+            .and_then(|other| hits.merge(&other));
+        if let Err(e) = parsed {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(2);
+        }
+    }
+    hits
+}
+
+fn merge_coverage(output: &str, paths: &[String]) -> bool {
+    let mut hits = coverage::Hits::default();
+    for path in paths {
+        if let Err(e) = hits.merge(&read_coverage(path)) {
+            eprintln!("{}: {}", path, e);
+            return false;
+        }
+    }
+    write_output(output, hits.to_string());
+    println!(
+        "Merged {} coverage file(s): {} instructions executed, written to {}",
+        paths.len(),
+        hits.counts.len(),
+        output
+    );
+    true
+}
+
+fn merge_files(output: &str, paths: &[String]) -> bool {
+    let texts: Vec<String> = paths.iter().map(|path| read_text(path)).collect();
+    if texts.iter().all(|text| text.starts_with("s8cov")) {
+        return merge_coverage(output, paths);
+    }
+    let merged = match checkpoint::merge(&texts) {
+        Ok(merged) => merged,
+        Err(e) => {
+            eprintln!("--merge: {}", e);
+            return false;
+        }
+    };
+    write_output(output, merged.text);
+    println!(
+        "Merged {} search state file(s): {} of {} tries explored, written to {}",
+        paths.len(),
+        merged.explored,
+        merged.total,
+        output
+    );
+    if let Some(found) = merged.found {
+        let (index, input) = found.split_once(' ').unwrap_or((&found, ""));
+        println!("Found by try #{}: {}", index, input);
+    }
+    true
+}
+
+fn diff_coverage(first: &str, second: &str) {
+    let a = read_coverage(first);
+    let b = read_coverage(second);
+    if a.program != b.program {
+        eprintln!(
+            "Warning: the coverage sets are for different programs ({:016x} and {:016x})",
+            a.program, b.program
+        );
+    }
+    for (name, only, other) in [(first, &a, &b), (second, &b, &a)] {
+        let ranges = only.only_in(other);
+        let count: usize = ranges.iter().map(|r| (r.1 - r.0) / 2 + 1).sum();
+        println!("Only reached by {}: {} instructions", name, count);
+        for (start, end, hits) in ranges {
+            if start == end {
+                println!("  {:#06x}           {} hits", start, hits);
+            } else {
+                println!("  {:#06x}-{:#06x}    {} hits", start, end, hits);
+            }
+        }
+    }
+}
+
+fn run_lockstep(
+    input: &[u8],
+    stdin: &[u8],
+    specs: &[String],
+    max_cycles: usize,
+) -> Result<bool, S8Error> {
+    if specs.len() != 2 {
+        eprintln!("--lockstep needs exactly two configurations");
+        std::process::exit(2);
+    }
+    let configs: Vec<lockstep::Config> = specs
+        .iter()
+        .map(|spec| {
+            lockstep::parse(spec, max_cycles).unwrap_or_else(|e| {
+                eprintln!("--lockstep {}: {}", spec, e);
+                std::process::exit(2);
+            })
+        })
+        .collect();
+    let memory = parser::memory_image(program_bytes(input)?);
+    match lockstep::compare(memory, stdin, &configs) {
+        Ok(states) => {
+            println!("No divergence, both runs ended the same way");
+            for (config, state) in configs.iter().zip(&states) {
+                match state.halted() {
+                    Some(reason) => {
+                        println!("  {}: {} cycles, {}", config.name, state.cycles(), reason)
+                    }
+                    None => println!("  {}: {} cycles, unfinished", config.name, state.cycles()),
+                }
+            }
+            Ok(true)
+        }
+        Err(divergence) => {
+            println!(
+                "Diverged at step {} (pc {:#06x}: {})",
+                divergence.step,
+                divergence.before.pc,
+                divergence.before.instruction()
+            );
+            println!("  expected {}, got {}", configs[0].name, configs[1].name);
+            for difference in &divergence.differences {
+                println!("  {}", difference);
+            }
+            for (config, state) in configs.iter().zip(&divergence.states) {
+                println!("{}:\n{}", config.name, state.display());
+            }
+            Ok(false)
+        }
+    }
+}
+
+fn run_call(
+    input: &[u8],
+    stdin: &[u8],
+    entry: &str,
+    regs: &str,
+    mem: &[String],
+    max_cycles: usize,
+) -> Result<bool, S8Error> {
+    let fail = |flag: &str, e: String| -> ! {
+        eprintln!("{}: {}", flag, e);
+        std::process::exit(2);
+    };
+    let entry = source::parse_number(entry)
+        .filter(|&a| a < 0x1000)
+        .unwrap_or_else(|| fail("--call", format!("bad address {}", entry)));
+    let registers = call::parse_registers(regs).unwrap_or_else(|e| fail("--regs", e));
+    let memory = parser::memory_image(program_bytes(input)?);
+    let mut state = emulator::State::new(memory, stdin.to_vec());
+    state.set_pc(entry as usize);
+    for (register, value) in registers {
+        state.set_register(register, value);
+    }
+    for spec in mem {
+        for (address, bytes) in call::parse_memory(spec).unwrap_or_else(|e| fail("--mem", e)) {
+            state
+                .write_memory(address, &bytes)
+                .unwrap_or_else(|e| fail("--mem", e));
+        }
+    }
+    let before = state.clone();
+    let returned = call::call(&mut state, max_cycles);
+    match (returned, state.halted()) {
+        (true, _) => println!(
+            "Returned from {:#05x} at {:#05x} after {} cycles",
+            entry,
+            state.pc(),
+            state.cycles()
+        ),
+        (false, Some(reason)) => println!("Halted after {} cycles: {}", state.cycles(), reason),
+        (false, None) => println!("Stopped after {} cycles without returning", state.cycles()),
+    }
+    for r in 0..16 {
+        let marker = if before.register(r) != state.register(r) {
+            format!("  (was {:#04x})", before.register(r))
+        } else {
+            String::new()
+        };
+        println!("  r{:<2} = {:#04x}{}", r, state.register(r), marker);
+    }
+    println!("  flagg = {}", state.flagg());
+    let changed: Vec<usize> = (0..parser::MEMORY_SIZE)
+        .filter(|&a| before.memory_at(a) != state.memory_at(a))
+        .collect();
+    if !changed.is_empty() {
+        println!("Memory changed:");
+        for address in changed {
+            println!(
+                "  {:#06x}: {:02x} -> {:02x}",
+                address,
+                before.memory_at(address),
+                state.memory_at(address)
+            );
+        }
+    }
+    if !state.stdout().is_empty() {
+        println!("Output: {}", hex::encode(state.stdout()));
+    }
+    Ok(returned)
+}
+
+fn import_challenge(path: &str, directory: Option<String>) -> Result<bool, S8Error> {
+    let bundle = match import::import(path) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return Ok(false);
+        }
+    };
+    let name = import::default_directory(path);
+    let directory = directory.unwrap_or_else(|| name.clone());
+    let program = bundle.program.expect("import checks for a program");
+    let hash = replay::program_hash(program_bytes(&program)?);
+    let mut files = vec![(format!("{}.s8", name), program.clone())];
+    for (i, input) in bundle.inputs.iter().enumerate() {
+        let file = match bundle.inputs.len() {
+            1 => String::from("input.bin"),
+            _ => format!("input{}.bin", i + 1),
+        };
+        files.push((file, input.clone()));
+    }
+    let written = fs::create_dir_all(&directory).and_then(|_| {
+        for (file, bytes) in &files {
+            fs::write(Path::new(&directory).join(file), bytes)?;
+        }
+        let mut project = project::Project::default();
+        project.set(hash, "imported", vec![path.to_string()]);
+        project.save(
+            &Path::new(&directory)
+                .join(project::DEFAULT_PATH)
+                .to_string_lossy(),
+        )
+    });
+    if let Err(e) = written {
+        eprintln!("Could not write {}: {}", directory, e);
+        return Ok(false);
+    }
+    for (file, bytes) in &files {
+        println!("{}/{} ({} bytes)", directory, file, bytes.len());
+    }
+    println!("{}/{}", directory, project::DEFAULT_PATH);
+    if bundle.inputs.is_empty() {
+        eprintln!("Warning: found no sample input");
+    }
+    Ok(true)
+}
+
+fn run_bench(
+    input: &[u8],
+    stdin: &[u8],
+    iterations: usize,
+    costs: Option<Arc<costs::CostModel>>,
+) -> Result<(), S8Error> {
+    let memory = parser::memory_image(program_bytes(input)?);
+    let report = bench::bench(memory, stdin, iterations, costs);
+    println!(
+        "{} iteration(s) of {} instructions ({} cycles) in {:.3} s",
+        report.iterations,
+        report.steps,
+        report.cycles,
+        report.elapsed.as_secs_f64()
+    );
+    println!(
+        "{:.0} instructions/second",
+        report.instructions_per_second()
+    );
+    println!(
+        "{} distinct instruction address(es); every step decodes its word, there is no decode cache to measure",
+        report.distinct_pcs
+    );
+    println!(
+        "{} LAST load(s), {} LAGR store(s), {} branch(es) per run",
+        report.loads, report.stores, report.branches
+    );
+    println!("{:?} per state fork", report.fork);
+    Ok(())
+}
+
+fn replay_of(
+    input: &[u8],
+    stdin: Vec<u8>,
+    seeds: Vec<u64>,
+    settings: replay::Settings,
+    computer: &State,
+) -> Result<replay::Replay, S8Error> {
+    Ok(replay::Replay {
+        version: String::from(env!("CARGO_PKG_VERSION")),
+        program: replay::program_hash(program_bytes(input)?),
+        input: stdin,
+        seeds,
+        settings,
+        cycles: computer.cycles(),
+        output: computer.stdout().to_vec(),
+    })
+}
+
+fn record(
+    file: &str,
+    input: &[u8],
+    stdin: Vec<u8>,
+    seeds: Vec<u64>,
+    settings: replay::Settings,
+    computer: &State,
+) -> Result<(), S8Error> {
+    let replay = replay_of(input, stdin, seeds, settings, computer)?;
+    write_output(file, replay.to_string());
+    Ok(())
+}
+
+fn export_solution(
+    directory: &str,
+    input: &[u8],
+    stdin: Vec<u8>,
+    seeds: Vec<u64>,
+    costs: Option<Arc<costs::CostModel>>,
+    settings: replay::Settings,
+) -> Result<bool, S8Error> {
+    let max_cycles = settings.max_cycles.unwrap_or(1_000_000);
+    let memory = parser::memory_image(program_bytes(input)?);
+    let result = State::new(memory, stdin.clone())
+        .with_costs(costs)
+        .run_result(max_cycles);
+    match result.halt {
+        HaltReason::Stopp => (),
+        HaltReason::TickLimit(_) => {
+            eprintln!("Warning: the run did not halt within {} cycles", max_cycles)
+        }
+        reason => eprintln!("Warning: the run ended with {}", reason),
+    }
+    let computer = result.state.expect("run_result keeps the final state");
+    let replay = replay_of(input, stdin, seeds, settings, &computer)?;
+    match export::write(directory, &replay) {
+        Ok(files) => {
+            for file in files {
+                println!("{}", file);
+            }
+            Ok(true)
+        }
+        Err(e) => {
+            eprintln!("Could not write {}: {}", directory, e);
+            Ok(false)
+        }
+    }
+}
+
+fn replay_run(file: &str, input: &[u8], debug: bool, color: bool) -> Result<bool, S8Error> {
+    let text = read_text(file);
+    let replay = match replay::parse(&text) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("{}: {}", file, e);
+            return Ok(false);
+        }
+    };
+    if replay::program_hash(program_bytes(input)?) != replay.program {
+        eprintln!("{}: recorded for a different program", file);
+        return Ok(false);
+    }
+    if replay.version != env!("CARGO_PKG_VERSION") {
+        eprintln!(
+            "{}: recorded with version {}, running {}",
+            file,
+            replay.version,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+    let costs = match replay
+        .settings
+        .costs
+        .as_deref()
+        .map(costs::parse)
+        .transpose()
+    {
+        Ok(costs) => costs.map(Arc::new),
+        Err(e) => {
+            eprintln!("{}: costs: {}", file, e);
+            return Ok(false);
+        }
+    };
+    let stubs = match replay
+        .settings
+        .stubs
+        .iter()
+        .map(|spec| stub::parse(spec))
+        .collect()
+    {
+        Ok(stubs) => stubs,
+        Err(e) => {
+            eprintln!("{}: stub: {}", file, e);
+            return Ok(false);
+        }
+    };
+    let options = RunOptions {
+        costs,
+        max_cycles: replay.settings.max_cycles,
+        stubs,
+        ..RunOptions::default()
+    };
+    let mut session = project::Session::default();
+    let result = emulate(
+        input,
+        replay.input,
+        true,
+        if debug { Some(&mut session) } else { None },
+        color,
+        &options,
+    )?;
+    show_result(&result);
+    if result.halt == HaltReason::UserInterrupt {
+        eprintln!("Replay stopped before the end of the recording");
+        return Ok(true);
+    }
+    let identical = result.cycles == replay.cycles && result.output == replay.output;
+    if identical {
+        eprintln!("Replay identical to recording ({} cycles)", replay.cycles);
+    } else {
+        eprintln!(
+            "Replay DIVERGED: {} cycles (recorded {}), output {}",
+            result.cycles,
+            replay.cycles,
+            if result.output == replay.output {
+                "identical"
+            } else {
+                "different"
+            }
+        );
+    }
+    Ok(identical)
+}
+
+fn verify_trace(
+    file: &str,
+    input: &[u8],
+    stdin: Vec<u8>,
+    costs: Option<Arc<costs::CostModel>>,
+) -> Result<bool, S8Error> {
+    let trace = match trace::Trace::open(file) {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("{}: {}", file, e);
+            return Ok(false);
+        }
+    };
+    if replay::program_hash(program_bytes(input)?) != trace.program {
+        eprintln!("{}: recorded for a different program", file);
+        return Ok(false);
+    }
+    let memory = parser::memory_image(program_bytes(input)?);
+    let mut computer = State::new(memory, stdin).with_costs(costs);
+    let mut count = 0;
+    for i in 0..trace.index.len() {
+        let block = match trace.block(i) {
+            Ok(block) => block,
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                return Ok(false);
+            }
+        };
+        for expected in &block.steps {
+            while computer.cycles() < expected.cycles && computer.halted().is_none() {
+                computer.iter().next();
+            }
+            let actual = history::Step::capture(&computer);
+            let differences = expected.differences(&actual);
+            if computer.halted().is_some() || !differences.is_empty() {
+                eprintln!(
+                    "Diverged at step {} (cycle {}, pc {:#06x}: {})",
+                    count,
+                    expected.cycles,
+                    expected.pc,
+                    expected.instruction()
+                );
+                if let Some(reason) = computer.halted() {
+                    eprintln!("  the run halted: {}", reason);
+                }
+                for difference in differences {
+                    eprintln!("  {}", difference);
+                }
+                return Ok(false);
+            }
+            computer.iter().next();
+            count += 1;
+        }
+    }
+    eprintln!("All {} recorded steps match", count);
+    if computer.halted().is_none() {
+        eprintln!("The run continues past the end of the trace");
+    }
+    Ok(true)
+}
+
+#[cfg(feature = "fuzz")]
+fn run_fuzz(spec: &str, corpus: &[String], checkpoint: Option<(String, usize)>) -> bool {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let mut options = match fuzz::parse(spec, now & 0xffff_ffff) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("--fuzz: {}", e);
+            return false;
+        }
+    };
+    let checkpoint = open_checkpoint(checkpoint, format!("fuzz {}", spec));
+    resume_seed(&checkpoint, &mut options.seed);
+    let corpus: Vec<Vec<u8>> = corpus.iter().map(|path| read_file(path, false)).collect();
+    println!(
+        "fuzz {}:seed={}:count={} with {} corpus file(s)",
+        options.target,
+        options.seed,
+        options.count,
+        corpus.len()
+    );
+    let crashes = fuzz::fuzz(&options, &corpus, &checkpoint);
+    for crash in &crashes {
+        println!(
+            "{} ({} bytes): {}",
+            crash.path,
+            crash.input.len(),
+            crash.message
+        );
+    }
+    println!("{} distinct panic(s)", crashes.len());
+    crashes.is_empty()
+}
+
+fn printable(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                (b as char).to_string()
+            } else {
+                format!("\\x{:02x}", b)
+            }
+        })
+        .collect()
+}
+
+fn open_checkpoint(checkpoint: Option<(String, usize)>, search: String) -> Checkpoint {
+    match checkpoint {
+        Some((path, every)) => Checkpoint::open(&path, every, search),
+        None => Checkpoint::disabled(),
+    }
+}
+
+fn resume_seed(checkpoint: &Checkpoint, seed: &mut u64) {
+    if let Some(saved) = checkpoint.saved.value("seed").and_then(|s| s.parse().ok()) {
+        *seed = saved;
+    }
+}
+
+fn run_stress(
+    input: &[u8],
+    spec: &str,
+    max_cycles: usize,
+    checkpoint: Option<(String, usize)>,
+    shard: Option<(usize, usize)>,
+) -> Result<(), S8Error> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let mut options = match stress::parse(spec, now & 0xffff_ffff) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("--stress: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if shard.is_some() && !spec.contains("seed=") {
+        eprintln!("--shard: give every shard the same seed=N so they split the same inputs");
+        std::process::exit(2);
+    }
+    let program = program_bytes(input)?;
+    let checkpoint = open_checkpoint(
+        checkpoint,
+        format!(
+            "stress {} max-cycles {} program {:016x}",
+            spec,
+            max_cycles,
+            replay::program_hash(program)
+        ),
+    )
+    .with_shard(shard);
+    resume_seed(&checkpoint, &mut options.seed);
+    let outcome = stress::stress(
+        parser::memory_image(program),
+        &options,
+        max_cycles,
+        &checkpoint,
+    );
+    println!(
+        "stress seed={}:count={}:len={}",
+        options.seed, options.count, options.length
+    );
+    println!("Halt reasons:");
+    for (reason, (count, first)) in &outcome.halts {
+        let example = match stress::input(&options, *first) {
+            example if example.is_empty() => String::from("(empty)"),
+            example => hex::encode(example),
+        };
+        println!(
+            "  {:<20} {:>6}  first input #{}: {}",
+            reason, count, first, example
+        );
+    }
+    let covered = outcome.coverage.iter().filter(|&&c| c).count();
+    println!(
+        "Coverage: {} of {} instructions executed by some input",
+        covered,
+        program.len().div_ceil(2)
+    );
+    println!("Outputs: {} distinct", outcome.outputs.len());
+    for output in outcome.outputs.iter().take(5) {
+        println!(
+            "  {:>6}x  first input #{}: \"{}\"",
+            output.count,
+            output.first,
+            printable(&output.stdout)
+        );
+    }
+    Ok(())
+}
+
+fn run_reach(
+    input: &[u8],
+    address: &str,
+    spec: &str,
+    max_cycles: usize,
+    checkpoint: Option<(String, usize)>,
+    shard: Option<(usize, usize)>,
+) -> Result<bool, S8Error> {
+    let target = match source::parse_number(address).filter(|&a| a < 0x1000) {
+        Some(target) => target as usize,
+        None => {
+            eprintln!("--reach-stopp: bad address {}", address);
+            std::process::exit(2);
+        }
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let mut options = match reach::parse(spec, now & 0xffff_ffff) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("--reach-with: {}", e);
+            std::process::exit(2);
+        }
+    };
+    match options.backend.as_str() {
+        "guided" if shard.is_some() => {
+            eprintln!(
+                "--shard: the guided search builds one input step by step and can't be split"
+            );
+            std::process::exit(2);
+        }
+        "symex" if shard.is_some() => {
+            eprintln!("--shard: the symbolic search follows one tree of paths and can't be split");
+            std::process::exit(2);
+        }
+        "random" if shard.is_some() && !spec.contains("seed=") => {
+            eprintln!("--shard: give every shard the same seed=N so they split the same inputs");
+            std::process::exit(2);
+        }
+        _ => (),
+    }
+    let program = program_bytes(input)?;
+    let checkpoint = open_checkpoint(
+        checkpoint,
+        format!(
+            "reach {:#05x} {} max-cycles {} program {:016x}",
+            target,
+            spec,
+            max_cycles,
+            replay::program_hash(program)
+        ),
+    )
+    .with_shard(shard);
+    resume_seed(&checkpoint, &mut options.seed);
+    let memory = parser::memory_image(program);
+    let instruction = parser::decode_at(&memory, target);
+    if !matches!(instruction, parser::Instruction::Stopp) {
+        eprintln!(
+            "Warning: the instruction at {:#05x} is {}, not STOPP",
+            target, instruction
+        );
+    }
+    if options.backend == "random" {
+        println!(
+            "reach random seed={}:count={}:len={}",
+            options.seed, options.count, options.length
+        );
+    }
+    let search = reach::reach(memory, target, &options, max_cycles, &checkpoint);
+    println!(
+        "Coverage: {} of {} instructions executed by some tried input",
+        search.coverage.iter().filter(|&&c| c).count(),
+        program.len().div_ceil(2)
+    );
+    let found = match search.found {
+        Some(found) => found,
+        None => {
+            println!(
+                "No input reaching STOPP at {:#05x} after {} tries",
+                target, search.tried
+            );
+            return Ok(false);
+        }
+    };
+    println!(
+        "Found after {} tries: \"{}\" (hex {})",
+        search.tried,
+        printable(&found.input),
+        hex::encode(&found.input)
+    );
+    println!("Path:");
+    for edge in &found.path {
+        if edge.count > 1 {
+            println!("  {:#05x} -> {:#05x} ({}x)", edge.from, edge.to, edge.count);
+        } else {
+            println!("  {:#05x} -> {:#05x}", edge.from, edge.to);
+        }
+    }
+    println!("  {:#05x} STOPP", target);
+    Ok(true)
+}
+
+fn show_matches(pattern: &regex::bytes::Regex, computer: &State, memory: bool, color: bool) {
+    let mut regions = vec![("stdout", computer.stdout().to_vec())];
+    if memory {
+        regions.push((
+            "memory",
+            (0..0x1000).map(|a| computer.memory_at(a)).collect(),
+        ));
+    }
+    for (name, bytes) in regions {
+        for found in scan::find(pattern, &bytes) {
+            let text = printable(&found.bytes);
+            eprintln!(
+                "Match in {} at {:#06x}: {}",
+                name,
+                found.offset,
+                if color {
+                    format!("\x1b[1;31m{}\x1b[0m", text)
+                } else {
+                    text
+                }
+            );
+        }
+    }
+}
+
+fn run_selftest(spec: &str) -> bool {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let options = match selftest::parse(spec, now & 0xffff_ffff) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("--selftest: {}", e);
+            return false;
+        }
+    };
+    println!("selftest seed={}:count={}", options.seed, options.count);
+    let mut rng = rng::Rng::new(options.seed);
+    let mut ok = true;
+    for (name, failures) in [
+        ("decode/encode", selftest::words(&mut rng, options.count)),
+        (
+            "assemble/disassemble",
+            selftest::programs(&mut rng, options.count),
+        ),
+    ] {
+        println!("{}: {} of {} failed", name, failures.len(), options.count);
+        for failure in failures.iter().take(3) {
+            println!("  {}", failure.replace('\n', "\n    "));
+        }
+        ok &= failures.is_empty();
+    }
+    ok
+}
+
+fn run_conformance(dir: &str) -> bool {
+    let mut paths: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|e| e == "s8t"))
+            .collect(),
+        Err(e) => {
+            eprintln!("{}: {}", dir, e);
+            return false;
+        }
+    };
+    paths.sort();
+    let mut failed = 0;
+    for path in &paths {
+        let name = path.display();
+        let vector = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| conformance::Vector::parse(&text));
+        let failures = match &vector {
+            Ok(vector) => vector.run(),
+            Err(e) => vec![e.clone()],
+        };
+        if failures.is_empty() {
+            println!("ok   {}", name);
+        } else {
+            failed += 1;
+            match &vector {
+                Ok(vector) => println!("FAIL {} ({})", name, vector.describe()),
+                Err(_) => println!("FAIL {}", name),
+            }
+            for failure in failures {
+                println!("     {}", failure);
+            }
+        }
+    }
+    println!("{} passed, {} failed", paths.len() - failed, failed);
+    failed == 0
+}
+
+/// Everything the command line can set. A command registers the options it
+/// accepts in its `*_arguments` function and its `*_command` reads them back.
+#[derive(Default)]
+struct Options {
+    file: String,
+    stdin: Vec<String>,
+    stdin_sources: Vec<String>,
+    stdin_pad: Option<String>,
+    output: Option<String>,
+    color: bool,
+    clean: bool,
+    recursive: bool,
+    raw: bool,
+    base: String,
+    disass: bool,
+    debug: bool,
+    break_at_entry: bool,
+    break_at: String,
+    project_file: String,
+    load_trace: Option<String>,
+    cost_file: Option<String>,
+    max_cycles: Option<usize>,
+    stub_specs: Vec<String>,
+    marker_specs: Vec<String>,
+    on_write_stdout: Option<String>,
+    on_fault: Option<String>,
+    ftrace: bool,
+    grep_output: Option<String>,
+    grep_memory: bool,
+    scan_memory: Option<String>,
+    dump_regions: Vec<String>,
+    trace_file: Option<String>,
+    trace_filter: Option<String>,
+    record_file: Option<String>,
+    io_device: Option<String>,
+    fmt: bool,
+    in_place: bool,
+    lint: bool,
+    lsp: bool,
+    link_output: Option<String>,
+    conformance_dir: Option<String>,
+    selftest_spec: Option<String>,
+    #[cfg(feature = "fuzz")]
+    fuzz_spec: Option<String>,
+    info: bool,
+    verify: bool,
+    callconv: bool,
+    clobbers: String,
+    dominators: bool,
+    cfg_dot: bool,
+    call_graph: bool,
+    call_graph_dot: bool,
+    liveness: bool,
+    infer: bool,
+    loops: bool,
+    opt: bool,
+    probes: String,
+    minimize: bool,
+    signature_db: Option<String>,
+    crib: Option<String>,
+    range: String,
+    entropy: bool,
+    window: usize,
+    serial_checks: bool,
+    keygen_language: Option<String>,
+    layout: bool,
+    map: bool,
+    unpack: bool,
+    obfuscation: bool,
+    replay_file: Option<String>,
+    verify_trace_file: Option<String>,
+    stress_spec: Option<String>,
+    reach_stopp: Option<String>,
+    reach_with: String,
+    checkpoint_every: Option<usize>,
+    search_state: Option<String>,
+    shard: Option<String>,
+    merge: Option<String>,
+    coverage_out: Option<String>,
+    coverage_merge: Option<String>,
+    coverage_diff: bool,
+    branch_coverage: bool,
+    export_dir: Option<String>,
+    import: bool,
+    import_dir: Option<String>,
+    call_entry: Option<String>,
+    call_regs: String,
+    call_mem: Vec<String>,
+    lockstep_specs: Vec<String>,
+    bench: bool,
+    iterations: usize,
+    function_cycles: bool,
+    input_timeline: bool,
+    timeline_format: Option<String>,
+    parallel: bool,
+    events: bool,
+    markdown: bool,
+    csv: bool,
+    tsv: bool,
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            project_file: String::from(project::DEFAULT_PATH),
+            window: 64,
+            reach_with: String::from("brute"),
+            iterations: 10,
+            ..Options::default()
+        }
+    }
+
+    fn program(&self) -> Vec<u8> {
+        read_file(&self.file, true)
+    }
+
+    /// The file argument followed by the other positional arguments.
+    fn files(&self) -> Vec<String> {
+        std::iter::once(self.file.clone())
+            .chain(self.stdin.iter().cloned())
+            .collect()
+    }
+
+    /// STDIN: the first input file followed by the `--stdin` sources,
+    /// padded as `--stdin-pad` says.
+    fn input(&self) -> Option<Vec<u8>> {
+        let mut indata = self.stdin.first().map(|path| read_file(path, false));
+        for source in &self.stdin_sources {
+            let bytes = read_file(source, false);
+            indata.get_or_insert_with(Vec::new).extend(bytes);
+        }
+        if let Some(spec) = &self.stdin_pad {
+            let (length, byte) = loader::parse_pad(spec).unwrap_or_else(|| {
+                eprintln!("Invalid --stdin-pad {}, expected N:0xBB", spec);
+                std::process::exit(2);
+            });
+            let data = indata.get_or_insert_with(Vec::new);
+            if data.len() < length {
+                data.resize(length, byte);
+            }
+        }
+        indata
+    }
+
+    fn seeds(&self) -> Vec<u64> {
+        self.stdin
+            .iter()
+            .take(1)
+            .chain(&self.stdin_sources)
+            .filter_map(|s| loader::seed(s))
+            .collect()
+    }
+
+    fn cost_text(&self) -> Option<String> {
+        self.cost_file.as_deref().map(read_text)
+    }
+
+    fn costs(&self) -> Option<Arc<costs::CostModel>> {
+        let path = self.cost_file.as_deref()?;
+        match costs::parse(&read_text(path)) {
+            Ok(model) => Some(Arc::new(model)),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    fn names(&self, input: &[u8]) -> Result<BTreeMap<usize, String>, S8Error> {
+        self.signature_db
+            .as_deref()
+            .map(|database| Ok(signatures::names(&load_signatures(input, database)?)))
+            .unwrap_or_else(|| Ok(BTreeMap::new()))
+    }
+
+    fn base(&self) -> usize {
+        parse_addresses(&self.base)
+            .unwrap_or_else(|e| {
+                eprintln!("--base {}: {}", self.base, e);
+                std::process::exit(2);
+            })
+            .first()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn part(&self) -> Option<(usize, usize)> {
+        self.shard.as_deref().map(|spec| {
+            checkpoint::parse_shard(spec).unwrap_or_else(|e| {
+                eprintln!("--shard: {}", e);
+                std::process::exit(2);
+            })
+        })
+    }
+
+    fn checkpoint(&self, name: &str) -> Option<(String, usize)> {
+        let shard = self.part();
+        let every = self
+            .checkpoint_every
+            .or((self.search_state.is_some() || shard.is_some()).then_some(10_000))?;
+        let path = self.search_state.clone().unwrap_or_else(|| match shard {
+            Some((i, n)) => format!("{}.shard{}of{}.s8checkpoint", name, i, n),
+            None => format!("{}.s8checkpoint", name),
+        });
+        Some((path, every))
+    }
+
+    fn emit(&self, text: String) {
+        match &self.output {
+            Some(output) => write_output(output, text),
+            None => print!("{}", text),
+        }
+    }
+}
+
+fn status(ok: bool) -> i32 {
+    if ok {
+        0
+    } else {
+        1
+    }
+}
+
+struct Command {
+    name: &'static str,
+    arguments: for<'a> fn(&mut ArgumentParser<'a>, &'a mut Options),
+    run: fn(Options) -> Result<i32, S8Error>,
+}
+
+const COMMANDS: [Command; 27] = [
+    Command {
+        name: "disasm",
+        arguments: disasm_arguments,
+        run: disasm_command,
+    },
+    Command {
+        name: "run",
+        arguments: run_arguments,
+        run: run_command,
+    },
+    Command {
+        name: "debug",
+        arguments: debug_arguments,
+        run: debug_command,
+    },
+    Command {
+        name: "asm",
+        arguments: asm_arguments,
+        run: asm_command,
+    },
+    Command {
+        name: "info",
+        arguments: info_arguments,
+        run: info_command,
+    },
+    Command {
+        name: "cfg",
+        arguments: cfg_arguments,
+        run: cfg_command,
+    },
+    Command {
+        name: "calls",
+        arguments: calls_arguments,
+        run: calls_command,
+    },
+    Command {
+        name: "solve",
+        arguments: solve_arguments,
+        run: solve_command,
+    },
+    Command {
+        name: "merge",
+        arguments: merge_arguments,
+        run: merge_command,
+    },
+    Command {
+        name: "fmt",
+        arguments: fmt_arguments,
+        run: fmt_command,
+    },
+    Command {
+        name: "lint",
+        arguments: lint_arguments,
+        run: lint_command,
+    },
+    Command {
+        name: "lsp",
+        arguments: lsp_arguments,
+        run: lsp_command,
+    },
+    Command {
+        name: "verify",
+        arguments: verify_arguments,
+        run: verify_command,
+    },
+    Command {
+        name: "opt",
+        arguments: opt_arguments,
+        run: opt_command,
+    },
+    Command {
+        name: "minimize",
+        arguments: minimize_arguments,
+        run: minimize_command,
+    },
+    Command {
+        name: "entropy",
+        arguments: entropy_arguments,
+        run: entropy_command,
+    },
+    Command {
+        name: "map",
+        arguments: map_arguments,
+        run: map_command,
+    },
+    Command {
+        name: "unpack",
+        arguments: unpack_arguments,
+        run: unpack_command,
+    },
+    Command {
+        name: "replay",
+        arguments: replay_arguments,
+        run: replay_command,
+    },
+    Command {
+        name: "stress",
+        arguments: stress_arguments,
+        run: stress_command,
+    },
+    Command {
+        name: "call",
+        arguments: call_arguments,
+        run: call_command,
+    },
+    Command {
+        name: "bench",
+        arguments: bench_arguments,
+        run: bench_command,
+    },
+    Command {
+        name: "coverage-diff",
+        arguments: coverage_diff_arguments,
+        run: coverage_diff_command,
+    },
+    Command {
+        name: "report",
+        arguments: report_arguments,
+        run: report_command,
+    },
+    Command {
+        name: "import",
+        arguments: import_arguments,
+        run: import_command,
+    },
+    Command {
+        name: "conformance",
+        arguments: conformance_arguments,
+        run: conformance_command,
+    },
+    Command {
+        name: "selftest",
+        arguments: selftest_arguments,
+        run: selftest_command,
+    },
+];
+
+fn signature_option<'a>(ap: &mut ArgumentParser<'a>, signature_db: &'a mut Option<String>) {
+    ap.refer(signature_db).add_option(
+        &["--signatures"],
+        StoreOption,
+        "Name the functions found in this signature database",
+    );
+}
+
+fn disasm_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Disassemble a SLEDE8 program");
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to disassemble");
+    listing_options(ap, &mut o.clean, &mut o.recursive, &mut o.raw, &mut o.base);
+    signature_option(ap, &mut o.signature_db);
+    ap.refer(&mut o.project_file).add_option(
+        &["--project"],
+        Store,
+        "Project file with aliases and typed data (default .s8project)",
+    );
+    ap.refer(&mut o.color)
+        .add_option(&["--color"], StoreTrue, "Syntax highlight the listing");
+}
+
+fn disasm_command(o: Options) -> Result<i32, S8Error> {
+    let base = o.base();
+    if o.raw {
+        return Ok(status(disassemble_raw(&o.file, base, o.color)));
+    }
+    let input = o.program();
+    let names = o.names(&input)?;
+    let prototypes = if o.infer {
+        observed_prototypes(&o, &input, &names)?
+    } else {
+        BTreeMap::new()
+    };
+    let session = match project::Project::load(&o.project_file) {
+        Ok(project) => project.session(replay::program_hash(program_bytes(&input)?)),
+        Err(e) => {
+            eprintln!("{}: {}", o.project_file, e);
+            project::Session::default()
+        }
+    };
+    disassemble(
+        &input,
+        base,
+        o.recursive,
+        o.clean,
+        o.color,
+        &names,
+        &prototypes,
+        &session,
+    )?;
+    Ok(0)
+}
+
+fn program_arguments<'a>(
+    ap: &mut ArgumentParser<'a>,
+    file: &'a mut String,
+    stdin: &'a mut Vec<String>,
+    stdin_sources: &'a mut Vec<String>,
+    stdin_pad: &'a mut Option<String>,
+) {
+    ap.refer(file)
+        .required()
+        .add_argument("file", Store, "Program to run");
+    ap.refer(stdin).add_argument("stdin", List, "Input data");
+    input_options(ap, stdin_sources, stdin_pad);
+}
+
+fn emulation_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options, debug: bool) {
+    if debug {
+        ap.set_description("Run a SLEDE8 program in the debugger");
+    } else {
+        ap.set_description("Run a SLEDE8 program");
+    }
+    program_arguments(
+        ap,
+        &mut o.file,
+        &mut o.stdin,
+        &mut o.stdin_sources,
+        &mut o.stdin_pad,
+    );
+    machine_options(
+        ap,
+        &mut o.cost_file,
+        &mut o.max_cycles,
+        &mut o.stub_specs,
+        &mut o.marker_specs,
+    );
+    hook_options(ap, &mut o.on_write_stdout, &mut o.on_fault, &mut o.ftrace);
+    inspect_options(
+        ap,
+        &mut o.grep_output,
+        &mut o.grep_memory,
+        &mut o.scan_memory,
+        &mut o.dump_regions,
+    );
+    if debug {
+        ap.refer(&mut o.break_at).add_option(
+            &["--break-at"],
+            Store,
+            "Comma separated breakpoints to run to",
+        );
+        ap.refer(&mut o.project_file).add_option(
+            &["--project"],
+            Store,
+            "Project file the session is saved to (default .s8project)",
+        );
+        ap.refer(&mut o.load_trace).add_option(
+            &["--load-trace"],
+            StoreOption,
+            "Trace file to seek in",
+        );
+    } else {
+        trace_options(
+            ap,
+            &mut o.trace_file,
+            &mut o.trace_filter,
+            &mut o.record_file,
+        );
+        ap.refer(&mut o.verify_trace_file).add_option(
+            &["--verify-trace"],
+            StoreOption,
+            "Check every step matches a recorded trace instead of running freely",
+        );
+        ap.refer(&mut o.io_device).add_option(
+            &["--io"],
+            StoreOption,
+            "Connect LES/SKRIV to tty, tty:raw, or serve the program on tcp:ADDRESS or unix:PATH",
+        );
+    }
+    ap.refer(&mut o.color)
+        .add_option(&["--color"], StoreTrue, "Syntax highlight assembly output");
+}
+
+fn run_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    emulation_arguments(ap, o, false);
+}
+
+fn debug_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    emulation_arguments(ap, o, true);
+}
+
+fn debug_command(mut o: Options) -> Result<i32, S8Error> {
+    o.debug = true;
+    run_command(o)
+}
+
+fn run_command(o: Options) -> Result<i32, S8Error> {
+    if o.verify_trace_file.is_some() {
+        return verify_trace_command(o);
+    }
+    if o.io_device.is_some() {
+        return io_command(o);
+    }
+    let input = o.program();
+    let costs = o.costs();
+    let markers: Vec<(usize, String)> = o
+        .marker_specs
+        .iter()
+        .flat_map(|spec| {
+            trace::parse_markers(spec).unwrap_or_else(|e| {
+                eprintln!("--marker {}: {}", spec, e);
+                std::process::exit(2);
+            })
+        })
+        .collect();
+    let stubs: Vec<stub::Stub> = o
+        .stub_specs
+        .iter()
+        .map(|spec| {
+            stub::parse(spec).unwrap_or_else(|e| {
+                eprintln!("--stub {}: {}", spec, e);
+                std::process::exit(2);
+            })
+        })
+        .collect();
+    let grep_pattern = o.grep_output.as_deref().map(|pattern| {
+        regex::bytes::Regex::new(pattern).unwrap_or_else(|e| {
+            eprintln!("--grep-output: {}", e);
+            std::process::exit(2);
+        })
+    });
+    let scan_memory = o.scan_memory.as_deref().map(|pattern| {
+        regex::bytes::Regex::new(pattern).unwrap_or_else(|e| {
+            eprintln!("--scan-memory: {}", e);
+            std::process::exit(2);
+        })
+    });
+    let (dump_at_pc, dump_at_halt): (Vec<dump::Region>, Vec<dump::Region>) = o
+        .dump_regions
+        .iter()
+        .map(|spec| {
+            dump::Region::parse(spec).unwrap_or_else(|e| {
+                eprintln!("--dump-region {}: {}", spec, e);
+                std::process::exit(2);
+            })
+        })
+        .partition(|r| r.at.is_some());
+    let stdin = o.input().unwrap_or_else(prompt_input);
+    let stepping = o.debug || o.break_at_entry || o.load_trace.is_some();
+    let program = replay::program_hash(program_bytes(&input)?);
+    if o.trace_file.is_some() && (stepping || !o.break_at.is_empty()) {
+        eprintln!("--trace records free runs and can not be used with the debugger");
+        std::process::exit(2);
+    }
+    let trace_filter = o.trace_filter.as_deref().map(|text| {
+        if o.trace_file.is_none() {
+            eprintln!("--trace-filter needs --trace");
+            std::process::exit(2);
+        }
+        expr::Expr::parse(text, &BTreeMap::new()).unwrap_or_else(|e| {
+            eprintln!("--trace-filter {}: {}", text, e);
+            std::process::exit(2);
+        })
+    });
+    let loaded_trace = o
+        .load_trace
+        .as_deref()
+        .map(|path| match trace::Trace::open(path) {
+            Ok(trace) if trace.program == program => Arc::new(trace),
+            Ok(_) => {
+                eprintln!("{}: recorded for a different program", path);
+                std::process::exit(2);
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(2);
+            }
+        });
+    let mut project = None;
+    let mut session = project::Session::default();
+    if stepping || !o.break_at.is_empty() {
+        let loaded = project::Project::load(&o.project_file).unwrap_or_else(|e| {
+            eprintln!("{}: {}", o.project_file, e);
+            std::process::exit(2);
+        });
+        session = loaded.session(program);
+        project = Some(loaded);
+    }
+    let breakpoints = parse_addresses(&o.break_at).unwrap_or_else(|e| {
+        eprintln!("--break-at {}: {}", o.break_at, e);
+        std::process::exit(2);
+    });
+    for address in breakpoints {
+        if !session.breakpoints.contains(&address) {
+            session.breakpoints.push(address);
+        }
+    }
+    let result = emulate(
+        &input,
+        stdin.clone(),
+        stepping,
+        project.as_ref().map(|_| &mut session),
+        o.color,
+        &RunOptions {
+            costs,
+            max_cycles: o.max_cycles,
+            on_write_stdout: o.on_write_stdout.clone(),
+            on_fault: o.on_fault.clone(),
+            trace: o.trace_file.clone(),
+            trace_filter,
+            markers,
+            stubs,
+            ftrace: o.ftrace,
+            loaded_trace,
+            scan_memory,
+            dump_regions: dump_at_pc,
+        },
+    )?;
+    if let Some(mut project) = project {
+        project.store_session(program, &session);
+        if let Err(e) = project.save(&o.project_file) {
+            eprintln!("Could not save session to {}: {}", o.project_file, e);
+        }
+    }
+    show_result(&result);
+    let computer = result
+        .state
+        .as_deref()
+        .expect("emulate keeps the final state");
+    for region in &dump_at_halt {
+        if let Err(e) = region.write(computer) {
+            eprintln!("{}", e);
+        }
+    }
+    if let Some(pattern) = &grep_pattern {
+        show_matches(pattern, computer, o.grep_memory, o.color);
+    }
+    if let Some(record_file) = &o.record_file {
+        let settings = replay::Settings {
+            costs: o.cost_text(),
+            max_cycles: o.max_cycles,
+            stubs: o.stub_specs.clone(),
+        };
+        record(record_file, &input, stdin, o.seeds(), settings, computer)?;
+    }
+    Ok(computer.halted().map_or(0, |reason| reason.exit_code()))
+}
+
+fn verify_trace_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    let stdin = o.input().unwrap_or_else(prompt_input);
+    let trace_file = o.verify_trace_file.as_deref().unwrap_or_default();
+    Ok(status(verify_trace(trace_file, &input, stdin, o.costs())?))
+}
+
+fn io_command(o: Options) -> Result<i32, S8Error> {
+    let spec = o.io_device.as_deref().unwrap_or_default();
+    let memory = parser::memory_image(program_bytes(&o.program())?);
+    let mut computer = State::new(memory, o.input().unwrap_or_default()).with_costs(o.costs());
+    let limit = o.max_cycles.unwrap_or(usize::MAX);
+    if spec.starts_with("tty") {
+        if let Err(e) = device::terminal(&mut computer, spec == "tty:raw", limit) {
+            eprintln!("\n{}: {}", spec, e);
+        }
+        eprintln!("\nEnd state:\n{}", computer.display());
+        return Ok(computer.halted().map_or(0, |reason| reason.exit_code()));
+    }
+    if let Err(e) = device::serve(spec, &computer, limit) {
+        eprintln!("{}: {}", spec, e);
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+fn asm_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Assemble s8asm modules and link them into a program");
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("source", Store, "s8asm source to assemble");
+    ap.refer(&mut o.stdin)
+        .add_argument("modules", List, "Further modules to link after it");
+    ap.refer(&mut o.link_output).required().add_option(
+        &["-o", "--output"],
+        StoreOption,
+        "Program file to write",
+    );
+    ap.refer(&mut o.opt).add_option(
+        &["--opt"],
+        StoreTrue,
+        "Run the peephole optimizer on the linked program",
+    );
+    ap.refer(&mut o.stdin_sources).add_option(
+        &["--stdin"],
+        Collect,
+        "Check the optimized program against a file or hex:4142 as input (repeatable)",
+    );
+}
+
+fn asm_command(o: Options) -> Result<i32, S8Error> {
+    let output = o.link_output.as_deref().unwrap_or_default();
+    let indata = (!o.stdin_sources.is_empty()).then(|| {
+        o.stdin_sources
+            .iter()
+            .flat_map(|source| read_file(source, false))
+            .collect()
+    });
+    Ok(status(link(output, &o.files(), o.opt, indata)))
+}
+
+fn info_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description(
+        "Print the program hash, size, amount of code, functions and I/O instructions",
+    );
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to describe");
+}
+
+fn info_command(o: Options) -> Result<i32, S8Error> {
+    show_info(&o.program())?;
+    Ok(0)
+}
+
+fn graph_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options, calls: bool) {
+    if calls {
+        ap.set_description(
+            "Print the call graph as a tree with unreachable functions and recursion cycles",
+        );
+        ap.refer(&mut o.call_graph_dot).add_option(
+            &["--dot"],
+            StoreTrue,
+            "Print the call graph as Graphviz DOT instead",
+        );
+    } else {
+        ap.set_description("Print the control flow graph as Graphviz DOT");
+    }
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to analyze");
+    signature_option(ap, &mut o.signature_db);
+    ap.refer(&mut o.output)
+        .add_option(&["-o", "--output"], StoreOption, "Output file");
+}
+
+fn cfg_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    graph_arguments(ap, o, false);
+}
+
+fn calls_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    graph_arguments(ap, o, true);
+}
+
+fn cfg_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    let memory = parser::memory_image(program_bytes(&input)?);
+    o.emit(cfg::Cfg::build(&memory).dot(&memory, &o.names(&input)?));
+    Ok(0)
+}
+
+fn calls_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    let names = o.names(&input)?;
+    let program = program_bytes(&input)?;
+    let memory = parser::memory_image(program);
+    let graph = callgraph::CallGraph::build(&memory, program.len());
+    o.emit(if o.call_graph_dot {
+        graph.dot(&names)
+    } else {
+        graph.text(&names)
+    });
+    Ok(0)
+}
+
+fn solve_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Search for an input that makes the program halt at the STOPP at ADDRESS");
+    ap.refer(&mut o.reach_stopp).required().add_argument(
+        "address",
+        StoreOption,
+        "Address of the STOPP to reach",
+    );
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to solve");
+    reach_option(ap, &mut o.reach_with);
+    checkpoint_options(
+        ap,
+        &mut o.checkpoint_every,
+        &mut o.search_state,
+        &mut o.shard,
+    );
+    ap.refer(&mut o.max_cycles).add_option(
+        &["--max-cycles"],
+        StoreOption,
+        "Give up on an input after this many cycles (default 100000)",
+    );
+}
+
+fn reach_option<'a>(ap: &mut ArgumentParser<'a>, reach_with: &'a mut String) {
+    ap.refer(reach_with).add_option(
+        &["--reach-with"],
+        Store,
+        "Search backend for --reach-stopp: brute:len=4, guided:len=32, random:seed=N:count=M:len=L or symex:len=32",
+    );
+}
+
+fn solve_command(o: Options) -> Result<i32, S8Error> {
+    let address = o.reach_stopp.as_deref().unwrap_or_default();
+    let ok = run_reach(
+        &o.program(),
+        address,
+        &o.reach_with,
+        o.max_cycles.unwrap_or(100_000),
+        o.checkpoint(&o.file),
+        o.part(),
+    );
+    Ok(status(ok?))
+}
+
+fn merge_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Merge search state or coverage files into one");
+    ap.refer(&mut o.merge)
+        .required()
+        .add_argument("output", StoreOption, "File to write");
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "First file to merge");
+    ap.refer(&mut o.stdin)
+        .add_argument("files", List, "The other files to merge");
+}
+
+fn merge_command(o: Options) -> Result<i32, S8Error> {
+    let merged = o.merge.as_deref().unwrap_or_default();
+    Ok(status(merge_files(merged, &o.files())))
+}
+
+fn coverage_merge_command(o: Options) -> Result<i32, S8Error> {
+    let merged = o.coverage_merge.as_deref().unwrap_or_default();
+    Ok(status(merge_coverage(merged, &o.files())))
+}
+
+fn coverage_diff_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Print the instructions reached by only one of two coverage sets");
+    ap.refer(&mut o.file).required().add_argument(
+        "first",
+        Store,
+        "Coverage file, or comma separated files to merge",
+    );
+    ap.refer(&mut o.stdin).required().add_argument(
+        "second",
+        List,
+        "Coverage file, or comma separated files to merge",
+    );
+}
+
+fn coverage_diff_command(o: Options) -> Result<i32, S8Error> {
+    match o.stdin.first() {
+        Some(second) => diff_coverage(&o.file, second),
+        None => {
+            eprintln!("--coverage-diff needs two coverage sets");
+            return Ok(2);
+        }
+    }
+    Ok(0)
+}
+
+fn fmt_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Format s8asm source");
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("source", Store, "s8asm source to format");
+    ap.refer(&mut o.in_place).add_option(
+        &["-i", "--in-place"],
+        StoreTrue,
+        "Rewrite the source file instead of printing",
+    );
+    ap.refer(&mut o.color).add_option(
+        &["--color"],
+        StoreTrue,
+        "Syntax highlight the formatted source",
+    );
+}
+
+fn fmt_command(o: Options) -> Result<i32, S8Error> {
+    format(&o.file, &read_text(&o.file), o.in_place, o.color);
+    Ok(0)
+}
+
+fn lint_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Check s8asm source for unreachable code, unused labels and other mistakes");
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("source", Store, "s8asm source to check");
+}
+
+fn lint_command(o: Options) -> Result<i32, S8Error> {
+    Ok(status(lint_source(&o.file, &read_text(&o.file))))
+}
+
+fn lsp_arguments<'a>(ap: &mut ArgumentParser<'a>, _: &'a mut Options) {
+    ap.set_description("Run an s8asm language server on stdin/stdout");
+}
+
+fn lsp_command(_: Options) -> Result<i32, S8Error> {
+    match lsp::serve() {
+        Ok(()) => Ok(0),
+        Err(e) => {
+            eprintln!("Language server failed: {}", e);
+            Ok(1)
+        }
+    }
+}
+
+fn verify_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Statically check jump targets and reachability");
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to check");
+}
+
+fn verify_command(o: Options) -> Result<i32, S8Error> {
+    Ok(status(verify_program(&o.program())?))
+}
+
+fn callconv_command(o: Options) -> Result<i32, S8Error> {
+    let documented = parse_addresses(&o.clobbers).unwrap_or_else(|e| {
+        eprintln!("--clobbers {}: {}", o.clobbers, e);
+        std::process::exit(2);
+    });
+    Ok(status(check_calls(&o.program(), o.input(), &documented)?))
+}
+
+fn rewrite_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options, description: &'a str) {
+    ap.set_description(description);
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to rewrite");
+    ap.refer(&mut o.stdin)
+        .add_argument("stdin", List, "Input data to check the result against");
+    input_options(ap, &mut o.stdin_sources, &mut o.stdin_pad);
+    ap.refer(&mut o.output).required().add_option(
+        &["-o", "--output"],
+        StoreOption,
+        "Program file to write",
+    );
+}
+
+fn opt_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    rewrite_arguments(
+        ap,
+        o,
+        "Remove NOPE padding and redundant SETTs and shorten jump ladders",
+    );
+}
+
+fn opt_command(o: Options) -> Result<i32, S8Error> {
+    let output = o.output.as_deref().unwrap_or_else(|| {
+        eprintln!("--opt needs an output file (-o)");
+        std::process::exit(2);
+    });
+    match optimize(program_bytes(&o.program())?, o.input()) {
+        Ok(optimized) => write_program(output, &optimized),
+        Err(e) => {
+            eprintln!("--opt: {}", e);
+            return Ok(1);
+        }
+    }
+    Ok(0)
+}
+
+fn instrument_command(o: Options) -> Result<i32, S8Error> {
+    let output = o.output.as_deref().unwrap_or_else(|| {
+        eprintln!("--instrument needs an output file (-o)");
+        std::process::exit(2);
+    });
+    let probes = parse_probes(&o.probes).unwrap_or_else(|e| {
+        eprintln!("--instrument {}: {}", o.probes, e);
+        std::process::exit(2);
+    });
+    match instrument::instrument(program_bytes(&o.program())?, &probes) {
+        Ok(image) => write_program(output, &image),
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(1);
+        }
+    }
+    Ok(0)
+}
+
+fn minimize_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    rewrite_arguments(
+        ap,
+        o,
+        "NOP out every instruction not needed for the same output on the input data",
+    );
+}
+
+fn minimize_command(o: Options) -> Result<i32, S8Error> {
+    let output = o.output.as_deref().unwrap_or_else(|| {
+        eprintln!("--minimize needs an output file (-o)");
+        std::process::exit(2);
+    });
+    let stdin = o.input().unwrap_or_else(prompt_input);
+    let input = o.program();
+    let program = program_bytes(&input)?;
+    let minimal = minimize::minimize(program, &stdin);
+    eprintln!(
+        "Kept {} of {} instruction word(s)",
+        minimize::live_words(&minimal),
+        minimize::live_words(program)
+    );
+    write_program(output, &minimal);
+    Ok(0)
+}
+
+fn entropy_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Plot entropy per window and a byte histogram of the program");
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to analyze");
+    region_options(ap, &mut o.range);
+    ap.refer(&mut o.window)
+        .add_option(&["--window"], Store, "Window size in bytes (default 64)");
+}
+
+fn region_options<'a>(ap: &mut ArgumentParser<'a>, range: &'a mut String) {
+    ap.refer(range).add_option(
+        &["--region"],
+        Store,
+        "START,END of the data region to analyze (default whole program)",
+    );
+}
+
+fn entropy_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    let (start, data) = region(&input, &o.range).unwrap_or_else(|e| {
+        eprintln!("--region {}: {}", o.range, e);
+        std::process::exit(2);
+    });
+    match &o.crib {
+        Some(crib) => crack_crib(start, data, crib),
+        None => show_entropy(start, data, o.window),
+    }
+    Ok(0)
+}
+
+fn serial_checks_command(o: Options) -> Result<i32, S8Error> {
+    show_serial_checks(&o.program())?;
+    Ok(0)
+}
+
+fn keygen_command(o: Options) -> Result<i32, S8Error> {
+    let language = o.keygen_language.as_deref().unwrap_or_default();
+    let ok = write_keygen(
+        &o.program(),
+        language,
+        o.output.clone(),
+        o.max_cycles.unwrap_or(1_000_000),
+    );
+    Ok(status(ok?))
+}
+
+fn layout_command(o: Options) -> Result<i32, S8Error> {
+    show_layout(&o.program(), o.input())?;
+    Ok(0)
+}
+
+fn unpack_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Run the program and disassemble code it wrote when it is first executed");
+    program_arguments(
+        ap,
+        &mut o.file,
+        &mut o.stdin,
+        &mut o.stdin_sources,
+        &mut o.stdin_pad,
+    );
+    ap.refer(&mut o.output).add_option(
+        &["-o", "--output"],
+        StoreOption,
+        "Save each stage as PREFIX-0xADDR.bin",
+    );
+    ap.refer(&mut o.max_cycles).add_option(
+        &["--max-cycles"],
+        StoreOption,
+        "Stop the program after this many cycles (default 1000000)",
+    );
+    ap.refer(&mut o.color).add_option(
+        &["--color"],
+        StoreTrue,
+        "Syntax highlight the disassembled stages",
+    );
+}
+
+fn unpack_command(o: Options) -> Result<i32, S8Error> {
+    let found = show_unpacked(
+        &o.program(),
+        o.input().unwrap_or_default(),
+        o.output.clone(),
+        o.max_cycles.unwrap_or(1_000_000),
+        o.color,
+    );
+    Ok(status(found?))
+}
+
+fn obfuscation_command(o: Options) -> Result<i32, S8Error> {
+    show_obfuscation(&o.program(), o.input(), o.max_cycles.unwrap_or(1_000_000))?;
+    Ok(0)
+}
+
+fn dominators_command(o: Options) -> Result<i32, S8Error> {
+    show_dominators(&o.program())?;
+    Ok(0)
+}
+
+fn loops_command(o: Options) -> Result<i32, S8Error> {
+    show_loops(&o.program(), o.input(), o.costs())?;
+    Ok(0)
+}
+
+fn signatures_command(o: Options) -> Result<i32, S8Error> {
+    let database = o.signature_db.as_deref().unwrap_or_default();
+    show_signatures(&load_signatures(&o.program(), database)?);
+    Ok(0)
+}
+
+fn liveness_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    show_liveness(&input, &o.names(&input)?)?;
+    Ok(0)
+}
+
+fn observed_prototypes(
+    o: &Options,
+    input: &[u8],
+    names: &BTreeMap<usize, String>,
+) -> Result<BTreeMap<usize, String>, S8Error> {
+    let corpus: Vec<Vec<u8>> = if o.stdin.is_empty() {
+        Vec::new()
+    } else {
+        load_corpus(&o.stdin, &o.input())
+            .into_iter()
+            .map(|(_, bytes)| bytes)
+            .collect()
+    };
+    infer_signatures(input, &corpus, names, o.max_cycles.unwrap_or(1_000_000))
+}
+
+fn infer_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    for (entry, prototype) in observed_prototypes(&o, &input, &o.names(&input)?)? {
+        println!("{:#06x} {}", entry, prototype);
+    }
+    Ok(0)
+}
+
+fn map_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description(
+        "Draw the address space as labelled segments (runs the program if input is given)",
+    );
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to map");
+    ap.refer(&mut o.stdin)
+        .add_argument("stdin", List, "Input data");
+    input_options(ap, &mut o.stdin_sources, &mut o.stdin_pad);
+    signature_option(ap, &mut o.signature_db);
+    ap.refer(&mut o.project_file).add_option(
+        &["--project"],
+        Store,
+        "Project file with the labels (default .s8project)",
+    );
+}
+
+fn map_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    let program = replay::program_hash(program_bytes(&input)?);
+    let mut labels = match project::Project::load(&o.project_file) {
+        Ok(project) => project.labels(program),
+        Err(e) => {
+            eprintln!("{}: {}", o.project_file, e);
+            BTreeMap::new()
+        }
+    };
+    labels.extend(o.names(&input)?);
+    show_map(&input, o.input(), &labels)?;
+    Ok(0)
+}
+
+fn replay_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Re-run a replay file and check the result is identical");
+    ap.refer(&mut o.replay_file).required().add_argument(
+        "replay",
+        StoreOption,
+        "Replay file to check",
+    );
+    ap.refer(&mut o.file).required().add_argument(
+        "file",
+        Store,
+        "Program the replay was recorded with",
+    );
+    ap.refer(&mut o.debug).add_option(
+        &["-d", "--debug"],
+        StoreTrue,
+        "Step through the replay in the debugger",
+    );
+    ap.refer(&mut o.color)
+        .add_option(&["--color"], StoreTrue, "Syntax highlight assembly output");
+}
+
+fn replay_command(o: Options) -> Result<i32, S8Error> {
+    let replay_file = o.replay_file.as_deref().unwrap_or_default();
+    Ok(status(replay_run(
+        replay_file,
+        &o.program(),
+        o.debug,
+        o.color,
+    )?))
+}
+
+fn stress_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description(
+        "Run the program on seeded random inputs and summarize halts, coverage and outputs",
+    );
+    ap.refer(&mut o.stress_spec).required().add_argument(
+        "spec",
+        StoreOption,
+        "Inputs to generate, seed=N:count=M:len=L",
+    );
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to run");
+    checkpoint_options(
+        ap,
+        &mut o.checkpoint_every,
+        &mut o.search_state,
+        &mut o.shard,
+    );
+    ap.refer(&mut o.max_cycles).add_option(
+        &["--max-cycles"],
+        StoreOption,
+        "Stop each run after this many cycles (default 1000000)",
+    );
+}
+
+fn stress_command(o: Options) -> Result<i32, S8Error> {
+    run_stress(
+        &o.program(),
+        o.stress_spec.as_deref().unwrap_or_default(),
+        o.max_cycles.unwrap_or(1_000_000),
+        o.checkpoint(&o.file),
+        o.part(),
+    )?;
+    Ok(0)
+}
+
+fn export_command(o: Options) -> Result<i32, S8Error> {
+    let directory = o.export_dir.as_deref().unwrap_or_default();
+    let ok = export_solution(
+        directory,
+        &o.program(),
+        o.input().unwrap_or_else(prompt_input),
+        o.seeds(),
+        o.costs(),
+        replay::Settings {
+            costs: o.cost_text(),
+            max_cycles: Some(o.max_cycles.unwrap_or(1_000_000)),
+            stubs: Vec::new(),
+        },
+    );
+    Ok(status(ok?))
+}
+
+fn call_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description(
+        "Run the function at ADDRESS until it returns and print the resulting registers and memory",
+    );
+    program_arguments(
+        ap,
+        &mut o.file,
+        &mut o.stdin,
+        &mut o.stdin_sources,
+        &mut o.stdin_pad,
+    );
+    ap.refer(&mut o.call_entry).required().add_option(
+        &["--fn"],
+        StoreOption,
+        "Address of the function to call",
+    );
+    call_options(ap, &mut o.call_regs, &mut o.call_mem);
+    ap.refer(&mut o.max_cycles).add_option(
+        &["--max-cycles"],
+        StoreOption,
+        "Stop the function after this many cycles (default 1000000)",
+    );
+}
+
+fn call_options<'a>(
+    ap: &mut ArgumentParser<'a>,
+    call_regs: &'a mut String,
+    call_mem: &'a mut Vec<String>,
+) {
+    ap.refer(call_regs).add_option(
+        &["--regs"],
+        Store,
+        "Registers to set before the call, e.g. r2=0x41,r3=0x10",
+    );
+    ap.refer(call_mem).add_option(
+        &["--mem"],
+        Collect,
+        "Memory to set before the call, e.g. 0x200=@args.bin, 0x200=hex:4142 or 0x200=7 (repeatable)",
+    );
+}
+
+fn call_command(o: Options) -> Result<i32, S8Error> {
+    let ok = run_call(
+        &o.program(),
+        &o.input().unwrap_or_default(),
+        o.call_entry.as_deref().unwrap_or_default(),
+        &o.call_regs,
+        &o.call_mem,
+        o.max_cycles.unwrap_or(1_000_000),
+    );
+    Ok(status(ok?))
+}
+
+fn lockstep_command(o: Options) -> Result<i32, S8Error> {
+    let ok = run_lockstep(
+        &o.program(),
+        &o.input().unwrap_or_else(prompt_input),
+        &o.lockstep_specs,
+        o.max_cycles.unwrap_or(1_000_000),
+    );
+    Ok(status(ok?))
+}
+
+fn bench_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Measure emulation speed on the given input");
+    program_arguments(
+        ap,
+        &mut o.file,
+        &mut o.stdin,
+        &mut o.stdin_sources,
+        &mut o.stdin_pad,
+    );
+    ap.refer(&mut o.iterations)
+        .add_option(&["--iterations"], Store, "Number of runs (default 10)");
+    ap.refer(&mut o.cost_file).add_option(
+        &["--costs"],
+        StoreOption,
+        "TOML file with the cycle cost of each mnemonic",
+    );
+}
+
+fn bench_command(o: Options) -> Result<i32, S8Error> {
+    let stdin = o.input().unwrap_or_else(prompt_input);
+    run_bench(&o.program(), &stdin, o.iterations, o.costs())?;
+    Ok(0)
+}
+
+fn branch_coverage_command(o: Options) -> Result<i32, S8Error> {
+    let corpus = load_corpus(&o.stdin, &o.input());
+    let limit = o.max_cycles.unwrap_or(1_000_000);
+    show_branch_coverage(&o.program(), &corpus, o.costs(), limit)?;
+    Ok(0)
+}
+
+fn coverage_out_command(o: Options) -> Result<i32, S8Error> {
+    let corpus = load_corpus(&o.stdin, &o.input());
+    let limit = o.max_cycles.unwrap_or(1_000_000);
+    let path = o.coverage_out.as_deref().unwrap_or_default();
+    write_coverage(&o.program(), &corpus, o.costs(), limit, path)?;
+    Ok(0)
+}
+
+fn function_cycles_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    let stdin = o.input().unwrap_or_else(prompt_input);
+    show_function_cycles(&input, stdin, o.costs(), o.max_cycles, &o.names(&input)?)?;
+    Ok(0)
+}
+
+fn timeline_command(o: Options) -> Result<i32, S8Error> {
+    let format = o.timeline_format.as_deref().unwrap_or_default();
+    if format != "text" && format != "html" {
+        eprintln!(
+            "--timeline: unknown format {}, expected text or html",
+            format
+        );
+        return Ok(2);
+    }
+    let markers: Vec<(usize, String)> = o
+        .marker_specs
+        .iter()
+        .flat_map(|spec| {
+            trace::parse_markers(spec).unwrap_or_else(|e| {
+                eprintln!("--marker {}: {}", spec, e);
+                std::process::exit(2);
+            })
+        })
+        .collect();
+    let input = o.program();
+    let stdin = o.input().unwrap_or_else(prompt_input);
+    let names = o.names(&input)?;
+    let memory = parser::memory_image(program_bytes(&input)?);
+    let functions = functions::find_functions(&memory);
+    let mut computer = State::new(memory, stdin).with_costs(o.costs());
+    let limit = o.max_cycles.unwrap_or(usize::MAX);
+    let timeline = timeline::record(&mut computer, &functions, &names, &markers, limit, 64);
+    o.emit(if format == "html" {
+        timeline.html(&o.file)
+    } else {
+        timeline.text()
+    });
+    Ok(0)
+}
+
+fn input_timeline_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    let stdin = o.input().unwrap_or_else(prompt_input);
+    show_input_timeline(&input, stdin, o.costs(), o.max_cycles, &o.names(&input)?)?;
+    Ok(0)
+}
+
+fn parallel_command(o: Options) -> Result<i32, S8Error> {
+    run_parallel(&o.program(), &o.stdin)?;
+    Ok(0)
+}
+
+fn events_command(o: Options) -> Result<i32, S8Error> {
+    let stdin = o.input().unwrap_or_else(prompt_input);
+    stream_events(&o.program(), stdin, o.debug)?;
+    Ok(0)
+}
+
+fn report_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Write a Markdown report of the program (with coverage if input is given)");
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("file", Store, "Program to describe");
+    ap.refer(&mut o.stdin)
+        .add_argument("stdin", List, "Input data");
+    input_options(ap, &mut o.stdin_sources, &mut o.stdin_pad);
+    signature_option(ap, &mut o.signature_db);
+    ap.refer(&mut o.output)
+        .add_option(&["-o", "--output"], StoreOption, "Output file");
+}
+
+fn report_command(o: Options) -> Result<i32, S8Error> {
+    let input = o.program();
+    let names = o.names(&input)?;
+    o.emit(report::report(
+        &o.file,
+        program_bytes(&input)?,
+        o.input(),
+        &names,
+    ));
+    Ok(0)
+}
+
+fn table_command(o: Options) -> Result<i32, S8Error> {
+    let separator = if o.csv { "," } else { "\t" };
+    export_table(&o.program(), o.base(), separator)?;
+    Ok(0)
+}
+
+fn import_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description(
+        "Extract the program and sample input from a challenge handout (zip, base64 or JSON) into a project directory",
+    );
+    ap.refer(&mut o.file)
+        .required()
+        .add_argument("handout", Store, "Challenge handout");
+    ap.refer(&mut o.import_dir).add_option(
+        &["--dir"],
+        StoreOption,
+        "Directory to extract into, defaults to the handout name",
+    );
+}
+
+fn import_command(o: Options) -> Result<i32, S8Error> {
+    Ok(status(import_challenge(&o.file, o.import_dir.clone())?))
+}
+
+fn conformance_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description("Run the instruction test vectors (*.s8t) in a directory");
+    ap.refer(&mut o.conformance_dir).required().add_argument(
+        "directory",
+        StoreOption,
+        "Directory with the test vectors",
+    );
+}
+
+fn conformance_command(o: Options) -> Result<i32, S8Error> {
+    Ok(status(run_conformance(
+        o.conformance_dir.as_deref().unwrap_or_default(),
+    )))
+}
+
+fn selftest_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description(
+        "Round-trip random words and programs through the decoder, encoder and assembler",
+    );
+    ap.refer(&mut o.selftest_spec).add_argument(
+        "spec",
+        StoreOption,
+        "Seed and number of rounds, seed=N:count=M",
+    );
+}
+
+fn selftest_command(o: Options) -> Result<i32, S8Error> {
+    Ok(status(run_selftest(
+        o.selftest_spec.as_deref().unwrap_or_default(),
+    )))
+}
+
+#[cfg(feature = "fuzz")]
+fn fuzz_command(o: Options) -> Result<i32, S8Error> {
+    if o.shard.is_some() {
+        eprintln!("--shard: fuzzing follows one random sequence and can't be split");
+        return Ok(2);
+    }
+    let corpus: Vec<String> = o.files().into_iter().filter(|f| !f.is_empty()).collect();
+    let spec = o.fuzz_spec.as_deref().unwrap_or_default();
+    Ok(status(run_fuzz(spec, &corpus, o.checkpoint("fuzz"))))
+}
+
+/// Without a command every option is accepted, and the first mode flag
+/// given (in the order of `flat_command`) picks what to do.
+fn flat_arguments<'a>(ap: &mut ArgumentParser<'a>, o: &'a mut Options) {
+    ap.set_description(
+        "Disassemble, run and debug SLEDE8 programs. The first argument may be a command: disasm, run, debug, asm, info, cfg, calls, solve, merge, fmt, lint, lsp, verify, opt, minimize, entropy, map, unpack, replay, stress, call, bench, coverage-diff, report, import, conformance or selftest (see COMMAND --help)",
+    );
+    ap.refer(&mut o.file)
+        .add_argument("file", Store, "Filename to load");
+    ap.refer(&mut o.stdin).add_argument(
+        "stdin",
+        List,
+        "Input data (or further modules with --link)",
+    );
+    input_options(ap, &mut o.stdin_sources, &mut o.stdin_pad);
+    listing_options(ap, &mut o.clean, &mut o.recursive, &mut o.raw, &mut o.base);
+    ap.refer(&mut o.disass).add_option(
+        &["-D", "--disassemble"],
+        StoreTrue,
+        "Do disassembly instead of running",
+    );
+    ap.refer(&mut o.debug)
+        .add_option(&["-d", "--debug"], StoreTrue, "Run in debug mode");
+    ap.refer(&mut o.break_at_entry).add_option(
+        &["--break-at-entry"],
+        StoreTrue,
+        "Start in the debugger stopped at the first instruction",
+    );
+    ap.refer(&mut o.break_at).add_option(
+        &["--break-at"],
+        Store,
+        "Comma separated breakpoints; start in the debugger and run to the first one",
+    );
+    ap.refer(&mut o.project_file).add_option(
+        &["--project"],
+        Store,
+        "Project file the debugger session is saved to (default .s8project)",
+    );
+    hook_options(ap, &mut o.on_write_stdout, &mut o.on_fault, &mut o.ftrace);
+    inspect_options(
+        ap,
+        &mut o.grep_output,
+        &mut o.grep_memory,
+        &mut o.scan_memory,
+        &mut o.dump_regions,
+    );
+    trace_options(
+        ap,
+        &mut o.trace_file,
+        &mut o.trace_filter,
+        &mut o.record_file,
+    );
+    ap.refer(&mut o.lockstep_specs).add_option(
+        &["--lockstep"],
+        Collect,
+        "Run the program under two configurations (default or costs=FILE:max-cycles=N, given twice) and report the first divergence",
+    );
+    ap.refer(&mut o.info).add_option(
+        &["--info"],
+        StoreTrue,
+        "Print the program hash, size, amount of code, functions and I/O instructions",
+    );
+    ap.refer(&mut o.import).add_option(
+        &["--import"],
+        StoreTrue,
+        "Extract the program and sample input from a challenge handout (zip, base64 or JSON) into a project directory",
+    );
+    ap.refer(&mut o.import_dir).add_option(
+        &["--import-dir"],
+        StoreOption,
+        "Directory for --import, defaults to the handout name",
+    );
+    ap.refer(&mut o.export_dir).add_option(
+        &["--export-solution"],
+        StoreOption,
+        "Run the program on STDIN and write the input as raw, hex and base64, the output and a replay manifest into this directory",
+    );
+    ap.refer(&mut o.call_entry).add_option(
+        &["--call"],
+        StoreOption,
+        "Run the function at ADDRESS until it returns and print the resulting registers and memory",
+    );
+    call_options(ap, &mut o.call_regs, &mut o.call_mem);
+    machine_options(
+        ap,
+        &mut o.cost_file,
+        &mut o.max_cycles,
+        &mut o.stub_specs,
+        &mut o.marker_specs,
+    );
+    ap.refer(&mut o.load_trace).add_option(
+        &["--load-trace"],
+        StoreOption,
+        "Start in the debugger with a trace file to seek in",
+    );
+    ap.refer(&mut o.fmt)
+        .add_option(&["--fmt"], StoreTrue, "Format s8asm source");
+    ap.refer(&mut o.lint)
+        .add_option(&["--lint"], StoreTrue, "Check s8asm source for mistakes");
+    ap.refer(&mut o.verify).add_option(
+        &["--verify"],
+        StoreTrue,
+        "Statically check jump targets and reachability",
+    );
+    ap.refer(&mut o.callconv).add_option(
+        &["--callconv"],
+        StoreTrue,
+        "Check TUR/RETUR balance and r0/r1 clobbering (also runs the program if stdin is given)",
+    );
+    ap.refer(&mut o.clobbers).add_option(
+        &["--clobbers"],
+        Store,
+        "Comma separated functions documented to clobber r0/r1",
+    );
+    ap.refer(&mut o.cfg_dot).add_option(
+        &["--cfg"],
+        StoreTrue,
+        "Print the control flow graph as Graphviz DOT (pipe into dot -Tpng)",
+    );
+    ap.refer(&mut o.call_graph).add_option(
+        &["--call-graph"],
+        StoreTrue,
+        "Print the call graph as a tree with unreachable functions and recursion cycles",
+    );
+    ap.refer(&mut o.call_graph_dot).add_option(
+        &["--call-graph-dot"],
+        StoreTrue,
+        "Print the call graph as Graphviz DOT (pipe into dot -Tpng)",
+    );
+    ap.refer(&mut o.dominators).add_option(
+        &["--dominators"],
+        StoreTrue,
+        "Print the immediate dominator of every basic block",
+    );
+    ap.refer(&mut o.liveness).add_option(
+        &["--liveness"],
+        StoreTrue,
+        "Print the input, output, clobbered and dead registers of every function",
+    );
+    ap.refer(&mut o.infer).add_option(
+        &["--infer-signatures"],
+        StoreTrue,
+        "Infer the argument and result registers of every function from liveness and runs on STDIN; with -d, annotate the listing",
+    );
+    ap.refer(&mut o.loops).add_option(
+        &["--loops"],
+        StoreTrue,
+        "Find natural loops (and count trips if stdin is given)",
+    );
+    ap.refer(&mut o.opt).add_option(
+        &["--opt"],
+        StoreTrue,
+        "Run the peephole optimizer (on --link output, or on a program with -o)",
+    );
+    ap.refer(&mut o.minimize).add_option(
+        &["--minimize"],
+        StoreTrue,
+        "NOP out every instruction not needed for the same output on stdin (needs -o)",
+    );
+    ap.refer(&mut o.signature_db).add_option(
+        &["--signatures"],
+        StoreOption,
+        "Hash functions and name those found in this signature database",
+    );
+    ap.refer(&mut o.crib).add_option(
+        &["--crib"],
+        StoreOption,
+        "Recover XOR/ADD keys from known plaintext in the program data",
+    );
+    region_options(ap, &mut o.range);
+    ap.refer(&mut o.entropy).add_option(
+        &["--entropy"],
+        StoreTrue,
+        "Plot entropy per window and a byte histogram of the program",
+    );
+    ap.refer(&mut o.window).add_option(
+        &["--window"],
+        Store,
+        "Window size in bytes for --entropy (default 64)",
+    );
+    ap.refer(&mut o.serial_checks).add_option(
+        &["--serial-checks"],
+        StoreTrue,
+        "Find loops that read, transform and compare input against a table",
+    );
+    ap.refer(&mut o.keygen_language).add_option(
+        &["--keygen"],
+        StoreOption,
+        "Write a keygen for the first serial check that reads input (python or rust, to -o or stdout)",
+    );
+    ap.refer(&mut o.probes).add_option(
+        &["--instrument"],
+        Store,
+        "Insert SKRIVs of registers at addresses, e.g. 0x10=r2+r3,0x24=r5 (needs -o)",
+    );
+    ap.refer(&mut o.layout).add_option(
+        &["--layout"],
+        StoreTrue,
+        "Map code, data, runtime-written and free memory (runs the program if stdin is given)",
+    );
+    ap.refer(&mut o.map).add_option(
+        &["--map"],
+        StoreTrue,
+        "Draw the address space as segments named by project labels and --signatures (runs the program if stdin is given)",
+    );
+    ap.refer(&mut o.unpack).add_option(
+        &["--unpack"],
+        StoreTrue,
+        "Run the program and disassemble code it wrote when it is first executed (-o PREFIX saves each stage)",
+    );
+    ap.refer(&mut o.obfuscation).add_option(
+        &["--obfuscation"],
+        StoreTrue,
+        "Report anti-debug and obfuscation tricks (runs the program if stdin is given)",
+    );
+    ap.refer(&mut o.replay_file).add_option(
+        &["--replay"],
+        StoreOption,
+        "Re-run a replay file and check the result is identical (works with -d)",
+    );
+    ap.refer(&mut o.verify_trace_file).add_option(
+        &["--verify-trace"],
+        StoreOption,
+        "Run the program and check every step matches a recorded trace",
+    );
+    ap.refer(&mut o.stress_spec).add_option(
+        &["--stress"],
+        StoreOption,
+        "Run the program on seeded random inputs and summarize halts, coverage and outputs (seed=N:count=M:len=L)",
+    );
+    ap.refer(&mut o.reach_stopp).add_option(
+        &["--reach-stopp"],
+        StoreOption,
+        "Search for an input that makes the program halt at the STOPP at this address",
+    );
+    reach_option(ap, &mut o.reach_with);
+    checkpoint_options(
+        ap,
+        &mut o.checkpoint_every,
+        &mut o.search_state,
+        &mut o.shard,
+    );
+    ap.refer(&mut o.merge).add_option(
+        &["--merge"],
+        StoreOption,
+        "Merge the search state or coverage files given as arguments into this file",
+    );
+    ap.refer(&mut o.events).add_option(
+        &["--events"],
+        StoreTrue,
+        "Stream execution as JSON lines, controlled by pause/resume/step/stop on stdin",
+    );
+    ap.refer(&mut o.parallel).add_option(
+        &["--parallel"],
+        StoreTrue,
+        "Run the program on every given input file in parallel",
+    );
+    ap.refer(&mut o.bench).add_option(
+        &["--bench"],
+        StoreTrue,
+        "Measure emulation speed on the given input",
+    );
+    ap.refer(&mut o.function_cycles).add_option(
+        &["--function-cycles"],
+        StoreTrue,
+        "Run the program and print inclusive/exclusive cycles per function",
+    );
+    ap.refer(&mut o.input_timeline).add_option(
+        &["--input-timeline"],
+        StoreTrue,
+        "Run the program and report which cycles and function processed each input byte",
+    );
+    ap.refer(&mut o.timeline_format).add_option(
+        &["--timeline"],
+        StoreOption,
+        "Run the program and draw when each function ran, I/O happened and memory was written (text or html, to -o or stdout)",
+    );
+    ap.refer(&mut o.branch_coverage).add_option(
+        &["--branch-coverage"],
+        StoreTrue,
+        "Run the program on every input file and report BHOPPs never flipped and edges never executed",
+    );
+    ap.refer(&mut o.coverage_out).add_option(
+        &["--coverage-out"],
+        StoreOption,
+        "Run the program on every input file and write how often each instruction executed",
+    );
+    ap.refer(&mut o.coverage_merge).add_option(
+        &["--coverage-merge"],
+        StoreOption,
+        "Merge the coverage files given as arguments into this file",
+    );
+    ap.refer(&mut o.coverage_diff).add_option(
+        &["--coverage-diff"],
+        StoreTrue,
+        "Print the instructions reached by only one of two coverage sets (a.s8cov,b.s8cov c.s8cov)",
+    );
+    ap.refer(&mut o.iterations).add_option(
+        &["--iterations"],
+        Store,
+        "Number of runs for --bench (default 10)",
+    );
+    ap.refer(&mut o.csv)
+        .add_option(&["--csv"], StoreTrue, "Export the disassembly as CSV");
+    ap.refer(&mut o.tsv).add_option(
+        &["--tsv"],
+        StoreTrue,
+        "Export the disassembly as tab separated values",
+    );
+    ap.refer(&mut o.markdown).add_option(
+        &["--report"],
+        StoreTrue,
+        "Write a Markdown report of the program (with coverage if stdin is given)",
+    );
+    ap.refer(&mut o.io_device).add_option(
+        &["--io"],
+        StoreOption,
+        "Connect LES/SKRIV to tty, tty:raw, or serve the program on tcp:ADDRESS or unix:PATH",
+    );
+    ap.refer(&mut o.output)
+        .add_option(&["-o", "--output"], StoreOption, "Output file");
+    ap.refer(&mut o.color)
+        .add_option(&["--color"], StoreTrue, "Syntax highlight assembly output");
+    ap.refer(&mut o.conformance_dir).add_option(
+        &["--conformance"],
+        StoreOption,
+        "Run the instruction test vectors (*.s8t) in a directory",
+    );
+    ap.refer(&mut o.selftest_spec).add_option(
+        &["--selftest"],
+        StoreOption,
+        "Round-trip random words and programs through the decoder, encoder and assembler (seed=N:count=M)",
+    );
+    #[cfg(feature = "fuzz")]
+    ap.refer(&mut o.fuzz_spec).add_option(
+        &["--fuzz"],
+        StoreOption,
+        "Fuzz decode, disassemble, assemble or execute with mutated corpus files (TARGET:seed=N:count=M)",
+    );
+    ap.refer(&mut o.lsp).add_option(
+        &["--lsp"],
+        StoreTrue,
+        "Run an s8asm language server on stdin/stdout",
+    );
+    ap.refer(&mut o.link_output).add_option(
+        &["--link", "--asm"],
+        StoreOption,
+        "Assemble the given modules and link them into this file",
+    );
+    ap.refer(&mut o.in_place).add_option(
+        &["-i", "--in-place"],
+        StoreTrue,
+        "Rewrite the source file instead of printing",
+    );
+}
+
+fn flat_command(o: Options) -> Result<i32, S8Error> {
+    if o.lsp {
+        return lsp_command(o);
+    }
+    #[cfg(feature = "fuzz")]
+    if o.fuzz_spec.is_some() {
+        return fuzz_command(o);
+    }
+    if o.selftest_spec.is_some() {
+        return selftest_command(o);
+    }
+    if o.conformance_dir.is_some() {
+        return conformance_command(o);
+    }
+    if o.file.is_empty() {
+        eprintln!("No input file given");
+        return Ok(2);
+    }
+    let named = o.signature_db.is_some()
+        && !(o.disass
+            || o.markdown
+            || o.function_cycles
+            || o.input_timeline
+            || o.map
+            || o.liveness
+            || o.infer
+            || o.cfg_dot
+            || o.call_graph
+            || o.call_graph_dot);
+    let command: fn(Options) -> Result<i32, S8Error> = if o.import {
+        import_command
+    } else if o.merge.is_some() {
+        merge_command
+    } else if o.coverage_merge.is_some() {
+        coverage_merge_command
+    } else if o.coverage_diff {
+        coverage_diff_command
+    } else if o.fmt {
+        fmt_command
+    } else if o.lint {
+        lint_command
+    } else if o.link_output.is_some() {
+        asm_command
+    } else if o.raw {
+        disasm_command
+    } else if o.info {
+        info_command
+    } else if o.verify {
+        verify_command
+    } else if o.callconv {
+        callconv_command
+    } else if o.opt {
+        opt_command
+    } else if !o.probes.is_empty() {
+        instrument_command
+    } else if o.minimize {
+        minimize_command
+    } else if o.crib.is_some() || o.entropy {
+        entropy_command
+    } else if o.serial_checks {
+        serial_checks_command
+    } else if o.keygen_language.is_some() {
+        keygen_command
+    } else if o.layout {
+        layout_command
+    } else if o.unpack {
+        unpack_command
+    } else if o.obfuscation {
+        obfuscation_command
+    } else if o.dominators {
+        dominators_command
+    } else if o.loops {
+        loops_command
+    } else if named {
+        signatures_command
+    } else if o.liveness {
+        liveness_command
+    } else if o.infer && !o.disass {
+        infer_command
+    } else if o.map {
+        map_command
+    } else if o.replay_file.is_some() {
+        replay_command
+    } else if o.verify_trace_file.is_some() {
+        verify_trace_command
+    } else if o.stress_spec.is_some() {
+        stress_command
+    } else if o.reach_stopp.is_some() {
+        solve_command
+    } else if o.export_dir.is_some() {
+        export_command
+    } else if o.call_entry.is_some() {
+        call_command
+    } else if !o.lockstep_specs.is_empty() {
+        lockstep_command
+    } else if o.bench {
+        bench_command
+    } else if o.branch_coverage {
+        branch_coverage_command
+    } else if o.coverage_out.is_some() {
+        coverage_out_command
+    } else if o.function_cycles {
+        function_cycles_command
+    } else if o.timeline_format.is_some() {
+        timeline_command
+    } else if o.input_timeline {
+        input_timeline_command
+    } else if o.parallel {
+        parallel_command
+    } else if o.io_device.is_some() {
+        io_command
+    } else if o.events {
+        events_command
+    } else if o.call_graph || o.call_graph_dot {
+        calls_command
+    } else if o.cfg_dot {
+        cfg_command
+    } else if o.markdown {
+        report_command
+    } else if o.csv || o.tsv {
+        table_command
+    } else if o.disass {
+        disasm_command
+    } else {
+        run_command
+    };
+    command(o)
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+    let command = args.get(1).and_then(|name| {
+        COMMANDS
+            .iter()
+            .find(|command| command.name == name.as_str())
+    });
+    if let Some(command) = command {
+        args.remove(1);
+        args[0] = format!("{} {}", args[0], command.name);
+    }
+    let mut options = Options::new();
+    {
+        let mut ap = ArgumentParser::new();
+        match command {
+            Some(command) => (command.arguments)(&mut ap, &mut options),
+            None => flat_arguments(&mut ap, &mut options),
+        }
+        if let Err(code) = ap.parse(args, &mut std::io::stdout(), &mut std::io::stderr()) {
+            std::process::exit(code);
+        }
+    }
+    let file = options.file.clone();
+    let result = match command {
+        Some(command) => (command.run)(options),
+        None => flat_command(options),
+    };
+    match result {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("{}: {}", file, e);
+            std::process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_addresses_are_errors() {
+        assert_eq!(parse_addresses("0x10, 32"), Ok(vec![0x10, 32]));
+        assert!(parse_addresses("0x10,zz").is_err());
+        assert!(parse_probes("0x2=rX").is_err());
+        assert!(parse_probes("0x2").is_err());
+        let input = [parser::MAGIC, &[0; 8][..]].concat();
+        assert_eq!(
+            region(&input, "2,6").map(|(start, data)| (start, data.len())),
+            Ok((2, 4))
+        );
+        assert!(region(&input, "6,2").is_err());
+        assert!(region(&input, "0,10").is_err());
+    }
+
+    #[test]
+    fn headerless_programs_are_errors() {
+        let input = [0x01, 0x00];
+        assert_eq!(program_bytes(&input), Err(S8Error::NotSlede8));
+        assert!(matches!(program_lines(&input, 0), Err(S8Error::NotSlede8)));
+        assert_eq!(verify_program(&input), Err(S8Error::NotSlede8));
+        assert!(region(&input, "").is_err());
+    }
+
+    #[test]
+    fn optimize_refuses_a_changed_program() {
+        let source = "NOPE\nSETT r0, 0x0c\nSETT r1, 0\nLAST r2\nSKRIV r2\nSTOPP\n.DATA 0x41";
+        let fragment =
+            assembler::assemble_fragment(source).unwrap_or_else(|_| panic!("bad test source"));
+        let module = linker::Module {
+            name: String::from("test"),
+            fragment,
+        };
+        let program = linker::link(&[module]).unwrap_or_else(|e| panic!("{}", e.join("\n")));
+        assert!(optimize(&program, None).is_ok());
+        assert!(optimize(&program, Some(Vec::new())).is_err());
+    }
 }
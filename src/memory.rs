@@ -0,0 +1,135 @@
+use crate::parser::MEMORY_SIZE;
+use std::fmt;
+use std::ops::Index;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub const PAGE_SIZE: usize = 256;
+const PAGES: usize = MEMORY_SIZE / PAGE_SIZE;
+
+static SEGMENTS: AtomicU64 = AtomicU64::new(0);
+
+fn segment() -> u64 {
+    SEGMENTS.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Clone)]
+pub struct Memory {
+    pages: [Arc<[u8; PAGE_SIZE]>; PAGES],
+    dirty: [u64; MEMORY_SIZE / 64],
+    segment: u64,
+}
+
+impl Memory {
+    pub fn new(image: [u8; MEMORY_SIZE]) -> Memory {
+        let zero = Arc::new([0; PAGE_SIZE]);
+        let mut pages = [(); PAGES].map(|_| Arc::clone(&zero));
+        for (page, chunk) in pages.iter_mut().zip(image.chunks_exact(PAGE_SIZE)) {
+            if chunk.iter().any(|&b| b != 0) {
+                Arc::make_mut(page).copy_from_slice(chunk);
+            }
+        }
+        Memory {
+            pages,
+            dirty: [0; MEMORY_SIZE / 64],
+            segment: segment(),
+        }
+    }
+
+    fn write(&mut self, address: usize, value: u8) -> bool {
+        let page = &mut self.pages[address / PAGE_SIZE];
+        let changed = page[address % PAGE_SIZE] != value;
+        if changed {
+            Arc::make_mut(page)[address % PAGE_SIZE] = value;
+        }
+        changed
+    }
+
+    pub fn set(&mut self, address: usize, value: u8) {
+        if self.write(address, value) {
+            self.dirty[address / 64] |= 1 << (address % 64);
+        }
+    }
+
+    pub fn dirty(&self) -> Vec<usize> {
+        (0..MEMORY_SIZE)
+            .filter(|a| self.dirty[a / 64] & (1 << (a % 64)) != 0)
+            .collect()
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty = [0; MEMORY_SIZE / 64];
+        self.segment = segment();
+    }
+
+    // Bytes outside the dirty set still hold what they held when the segment
+    // began, so within one segment only bytes dirty on either side can differ.
+    pub fn restore(&mut self, snapshot: &Memory) {
+        if self.segment == snapshot.segment {
+            let dirty = self.dirty;
+            for (word, (&mine, &saved)) in dirty.iter().zip(&snapshot.dirty).enumerate() {
+                let mut touched = mine | saved;
+                while touched != 0 {
+                    let address = word * 64 + touched.trailing_zeros() as usize;
+                    self.write(address, snapshot[address]);
+                    touched &= touched - 1;
+                }
+            }
+        } else {
+            for (page, saved) in self.pages.iter_mut().zip(&snapshot.pages) {
+                if Arc::ptr_eq(page, saved) {
+                    continue;
+                }
+                match Arc::get_mut(page) {
+                    Some(owned) => owned.copy_from_slice(&saved[..]),
+                    None => *page = Arc::clone(saved),
+                }
+            }
+            self.segment = snapshot.segment;
+        }
+        self.dirty = snapshot.dirty;
+    }
+
+    pub fn word(&self, address: usize) -> u16 {
+        u16::from_le_bytes([self[address], self[address + 1]])
+    }
+}
+
+impl Index<usize> for Memory {
+    type Output = u8;
+    fn index(&self, address: usize) -> &u8 {
+        &self.pages[address / PAGE_SIZE][address % PAGE_SIZE]
+    }
+}
+
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Memory({} pages)", PAGES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_copies_back_dirty_bytes() {
+        let mut image = [0; MEMORY_SIZE];
+        image[0x10] = 0xaa;
+        let mut memory = Memory::new(image);
+        memory.set(0x20, 1);
+        let snapshot = memory.clone();
+        memory.set(0x10, 0xbb);
+        memory.set(0x300, 2);
+        memory.set(0x301, 0);
+        assert_eq!(memory.dirty(), vec![0x10, 0x20, 0x300]);
+        memory.restore(&snapshot);
+        assert_eq!((memory[0x10], memory[0x20], memory[0x300]), (0xaa, 1, 0));
+        assert_eq!(memory.dirty(), vec![0x20]);
+        memory.clear_dirty();
+        memory.set(0x20, 3);
+        memory.restore(&snapshot);
+        assert_eq!(memory[0x20], 1);
+        assert_eq!(memory.dirty(), vec![0x20]);
+    }
+}
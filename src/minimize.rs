@@ -0,0 +1,68 @@
+use crate::emulator::{HaltReason, State};
+use crate::parser::{encode_instruction, memory_image, Instruction};
+
+#[derive(PartialEq)]
+struct Outcome {
+    output: Vec<u8>,
+    halt: Option<HaltReason>,
+}
+
+fn run(program: &[u8], stdin: &[u8], max_steps: usize) -> (Outcome, usize) {
+    let mut state = State::new(memory_image(program), stdin.to_vec());
+    state.run(max_steps);
+    let outcome = Outcome {
+        output: state.stdout().to_vec(),
+        halt: state.halted(),
+    };
+    (outcome, state.cycles())
+}
+
+fn nop_out(program: &[u8], words: &[usize]) -> Vec<u8> {
+    let nope = encode_instruction(&Instruction::Nope).to_le_bytes();
+    let mut candidate = program.to_vec();
+    for &pc in words {
+        candidate[pc..pc + 2].copy_from_slice(&nope);
+    }
+    candidate
+}
+
+pub fn live_words(program: &[u8]) -> usize {
+    let nope = encode_instruction(&Instruction::Nope).to_le_bytes();
+    program.chunks_exact(2).filter(|w| *w != nope).count()
+}
+
+pub fn minimize(program: &[u8], stdin: &[u8]) -> Vec<u8> {
+    let (reference, cycles) = run(program, stdin, 1_000_000);
+    let max_steps = cycles * 10 + 1000;
+    let nope = encode_instruction(&Instruction::Nope).to_le_bytes();
+
+    let mut current = program.to_vec();
+    let mut words: Vec<usize> = (0..program.len() - program.len() % 2)
+        .step_by(2)
+        .filter(|&pc| program[pc..pc + 2] != nope)
+        .collect();
+
+    let mut chunk = words.len().div_ceil(2).max(1);
+    while !words.is_empty() {
+        let mut i = 0;
+        let mut progress = false;
+        while i < words.len() {
+            let end = (i + chunk).min(words.len());
+            let candidate = nop_out(&current, &words[i..end]);
+            if run(&candidate, stdin, max_steps).0 == reference {
+                current = candidate;
+                words.drain(i..end);
+                progress = true;
+            } else {
+                i = end;
+            }
+        }
+        if chunk == 1 && !progress {
+            break;
+        }
+        if !progress {
+            chunk = chunk.div_ceil(2);
+        }
+    }
+    current
+}
@@ -0,0 +1,153 @@
+use crate::emulator::State;
+use crate::parser::{decode_at, reachable, ALEOp, Instruction, MEMORY_SIZE};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub struct Finding {
+    pub address: usize,
+    pub description: String,
+    pub suggestion: &'static str,
+}
+
+fn jumps(memory: &[u8], code: &BTreeSet<usize>, findings: &mut Vec<Finding>) {
+    for &pc in code {
+        let target = match decode_at(memory, pc) {
+            Instruction::Hopp(a) | Instruction::BHopp(a) | Instruction::Tur(a) => usize::from(a),
+            _ => continue,
+        };
+        if !target.is_multiple_of(2) {
+            findings.push(Finding {
+                address: pc,
+                description: format!(
+                    "{} jumps into the middle of the instruction at {:#06x}",
+                    decode_at(memory, pc),
+                    target - 1
+                ),
+                suggestion: "the -D listing shows the decoding from the target marked with ~",
+            });
+        }
+    }
+}
+
+fn overlaps(memory: &[u8], code: &BTreeSet<usize>, findings: &mut Vec<Finding>) {
+    for &pc in code {
+        if code.contains(&(pc + 1)) {
+            findings.push(Finding {
+                address: pc,
+                description: format!(
+                    "overlapping decodings: {} at {:#06x} and {} at {:#06x}",
+                    decode_at(memory, pc),
+                    pc,
+                    decode_at(memory, pc + 1),
+                    pc + 1
+                ),
+                suggestion: "a listing shows only one of them, follow the one the jumps use",
+            });
+        }
+    }
+}
+
+fn code_pointers(memory: &[u8], code: &BTreeSet<usize>, findings: &mut Vec<Finding>) {
+    for &pc in code {
+        if let Instruction::Finn(a) = decode_at(memory, pc) {
+            let a = usize::from(a);
+            if code.contains(&a) || a > 0 && code.contains(&(a - 1)) {
+                findings.push(Finding {
+                    address: pc,
+                    description: format!("points r0:r1 at the code at {:#06x}", a),
+                    suggestion: "check for LAGR through the pointer, the program may patch itself",
+                });
+            }
+        }
+    }
+}
+
+fn counters(memory: &[u8], code: &BTreeSet<usize>, findings: &mut Vec<Finding>) {
+    let mut increments: BTreeMap<(usize, usize), BTreeSet<usize>> = BTreeMap::new();
+    let mut compares: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for &pc in code {
+        match decode_at(memory, pc) {
+            Instruction::ALE(ALEOp::Pluss, ra, rb) if ra != rb => {
+                increments
+                    .entry((usize::from(ra), usize::from(rb)))
+                    .or_default()
+                    .insert(pc);
+            }
+            Instruction::Sammenligne(_, ra, rb) => {
+                compares.entry(usize::from(ra)).or_default().push(pc);
+                compares.entry(usize::from(rb)).or_default().push(pc);
+            }
+            _ => (),
+        }
+    }
+    for ((ra, rb), sites) in increments {
+        if sites.len() < 3 {
+            continue;
+        }
+        for &pc in compares.get(&ra).into_iter().flatten() {
+            findings.push(Finding {
+                address: pc,
+                description: format!(
+                    "{} compares r{}, which {} places add r{} to like a cycle counter",
+                    decode_at(memory, pc),
+                    ra,
+                    sites.len(),
+                    rb
+                ),
+                suggestion:
+                    "patch the comparison or set the counter in the debugger before it runs",
+            });
+        }
+    }
+}
+
+fn self_writes(
+    memory: [u8; MEMORY_SIZE],
+    stdin: Vec<u8>,
+    max_cycles: usize,
+    findings: &mut Vec<Finding>,
+) {
+    let mut state = State::new(memory, stdin);
+    let mut written: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut reported = BTreeSet::new();
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        let pc = state.pc();
+        for address in [pc, pc + 1] {
+            if let Some(&writer) = written.get(&address) {
+                if reported.insert(address) {
+                    findings.push(Finding {
+                        address: writer,
+                        description: format!(
+                            "LAGR writes {:#06x}, which is executed at {:#06x} afterwards",
+                            address, pc
+                        ),
+                        suggestion: "break at the written code and disassemble memory there once it is patched",
+                    });
+                }
+            }
+        }
+        if let Instruction::Lagr(_) = state.next_instruction() {
+            written.insert(state.pointer(), pc);
+        }
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+    }
+}
+
+pub fn analyze(
+    memory: [u8; MEMORY_SIZE],
+    stdin: Option<Vec<u8>>,
+    max_cycles: usize,
+) -> Vec<Finding> {
+    let code = reachable(&memory);
+    let mut findings = Vec::new();
+    jumps(&memory, &code, &mut findings);
+    overlaps(&memory, &code, &mut findings);
+    code_pointers(&memory, &code, &mut findings);
+    counters(&memory, &code, &mut findings);
+    if let Some(stdin) = stdin {
+        self_writes(memory, stdin, max_cycles, &mut findings);
+    }
+    findings.sort_by_key(|f| f.address);
+    findings
+}
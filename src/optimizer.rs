@@ -0,0 +1,166 @@
+use crate::parser::{decode_at, encode_instruction, memory_image, reachable, Instruction, Operand};
+use std::collections::BTreeSet;
+
+const MAX_LADDER: usize = 16;
+
+#[derive(Default)]
+pub struct Savings {
+    pub nopes: usize,
+    pub setts: usize,
+    pub jumps: usize,
+    pub bytes: usize,
+}
+
+fn target(instruction: &Instruction) -> Option<Operand> {
+    match instruction {
+        Instruction::Hopp(a)
+        | Instruction::BHopp(a)
+        | Instruction::Tur(a)
+        | Instruction::Finn(a) => Some(*a),
+        _ => None,
+    }
+}
+
+fn retarget(instruction: &Instruction, address: Operand) -> Instruction {
+    match instruction {
+        Instruction::Hopp(_) => Instruction::Hopp(address),
+        Instruction::BHopp(_) => Instruction::BHopp(address),
+        Instruction::Tur(_) => Instruction::Tur(address),
+        Instruction::Finn(_) => Instruction::Finn(address),
+        _ => panic!("instruction has no address operand"),
+    }
+}
+
+fn write(image: &mut [u8], pc: usize, instruction: &Instruction) {
+    image[pc..pc + 2].copy_from_slice(&encode_instruction(instruction).to_le_bytes());
+}
+
+fn redundant_sett(memory: &[u8], code: &BTreeSet<usize>, pc: usize) -> bool {
+    let register = match decode_at(memory, pc) {
+        Instruction::Sett(ra, rb) if ra == rb => return true,
+        Instruction::Sett(ra, _) | Instruction::SettImmediate(ra, _) => ra,
+        _ => return false,
+    };
+    if !code.contains(&(pc + 2)) {
+        return false;
+    }
+    let next = decode_at(memory, pc + 2);
+    next.writes().any(|w| w == register) && !next.reads().any(|r| r == register)
+}
+
+pub fn optimize(program: &[u8]) -> (Vec<u8>, Savings) {
+    let mut memory = memory_image(program);
+    let code: BTreeSet<usize> = reachable(&memory)
+        .into_iter()
+        .filter(|pc| pc + 1 < program.len())
+        .collect();
+    let mut savings = Savings::default();
+
+    for &pc in &code {
+        let instruction = decode_at(&memory, pc);
+        let original = match instruction {
+            Instruction::Hopp(a) | Instruction::BHopp(a) | Instruction::Tur(a) => a,
+            _ => continue,
+        };
+        let mut address = original;
+        for _ in 0..MAX_LADDER {
+            if !code.contains(&usize::from(address)) {
+                break;
+            }
+            match decode_at(&memory, usize::from(address)) {
+                Instruction::Hopp(next) if next != address => address = next,
+                _ => break,
+            }
+        }
+        if address != original {
+            write(&mut memory, pc, &retarget(&instruction, address));
+            savings.jumps += 1;
+        }
+    }
+
+    let mut removed = BTreeSet::new();
+    for &pc in &code {
+        match decode_at(&memory, pc) {
+            Instruction::Nope => {
+                removed.insert(pc);
+                savings.nopes += 1;
+            }
+            Instruction::Hopp(a) if usize::from(a) == pc + 2 => {
+                removed.insert(pc);
+                savings.jumps += 1;
+            }
+            _ => {
+                if redundant_sett(&memory, &code, pc) {
+                    removed.insert(pc);
+                    savings.setts += 1;
+                }
+            }
+        }
+    }
+
+    let relocate = |address: Operand| {
+        let shift = removed.range(..usize::from(address)).count() * 2;
+        Operand::Addr(address.value() - shift as u16)
+    };
+    let mut image = Vec::with_capacity(program.len());
+    let mut pc = 0;
+    while pc < program.len() {
+        if removed.contains(&pc) {
+            pc += 2;
+            continue;
+        }
+        if code.contains(&pc) {
+            let instruction = decode_at(&memory, pc);
+            match target(&instruction) {
+                Some(a) if relocate(a) != a => {
+                    let moved = retarget(&instruction, relocate(a));
+                    image.extend_from_slice(&encode_instruction(&moved).to_le_bytes());
+                }
+                _ => image.extend_from_slice(&memory[pc..pc + 2]),
+            }
+            pc += 2;
+        } else {
+            image.push(memory[pc]);
+            pc += 1;
+        }
+    }
+    savings.bytes = program.len() - image.len();
+    (image, savings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble_fragment;
+    use crate::linker::{link, Module};
+
+    fn image(source: &str) -> Vec<u8> {
+        let fragment = assemble_fragment(source).unwrap_or_else(|_| panic!("bad test source"));
+        let module = Module {
+            name: String::from("test"),
+            fragment,
+        };
+        link(&[module]).unwrap_or_else(|e| panic!("{}", e.join("\n")))
+    }
+
+    #[test]
+    fn shortens_jump_ladders() {
+        let (optimized, savings) = optimize(&image("HOPP a\na: HOPP b\nb: STOPP"));
+        let memory = memory_image(&optimized);
+        assert!(savings.jumps > 0);
+        match decode_at(&memory, 0) {
+            Instruction::Hopp(target) => assert!(matches!(
+                decode_at(&memory, usize::from(target)),
+                Instruction::Stopp
+            )),
+            _ => panic!("the first instruction is no longer a jump"),
+        }
+    }
+
+    #[test]
+    fn jump_to_the_end_of_memory() {
+        let (_, savings) = optimize(&image("NOPE\nHOPP 0xfff\nSTOPP"));
+        assert_eq!(savings.nopes, 1);
+        assert_eq!(savings.jumps, 0);
+    }
+}
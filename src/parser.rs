@@ -1,5 +1,11 @@
+use crate::error::S8Error;
 use byteorder::{ByteOrder, LittleEndian};
+use std::collections::BTreeSet;
 use std::fmt;
+use std::io::{self, BufReader, ErrorKind, Read};
+
+pub const MAGIC: &[u8] = b".SLEDE8";
+pub const MEMORY_SIZE: usize = 4096;
 
 pub struct Line {
     pub pos: usize,
@@ -7,6 +13,7 @@ pub struct Line {
     pub instruction: Instruction,
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum ALEOp {
     Og,
     Eller,
@@ -17,9 +24,9 @@ pub enum ALEOp {
     Minus,
 }
 
-impl fmt::Display for ALEOp {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let cmd = match self {
+impl ALEOp {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
             ALEOp::Og => "OG",
             ALEOp::Eller => "ELLER",
             ALEOp::XEller => "XELLER",
@@ -27,11 +34,17 @@ impl fmt::Display for ALEOp {
             ALEOp::Hskift => "HSKIFT",
             ALEOp::Pluss => "PLUSS",
             ALEOp::Minus => "MINUS",
-        };
-        write!(f, "{}", cmd)
+        }
     }
 }
 
+impl fmt::Display for ALEOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum CMPOp {
     Lik,
     Ulik,
@@ -41,150 +54,519 @@ pub enum CMPOp {
     Sel,
 }
 
-impl fmt::Display for CMPOp {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let cmd = match self {
+impl CMPOp {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
             CMPOp::Lik => "LIK",
             CMPOp::Ulik => "ULIK",
             CMPOp::Me => "ME",
             CMPOp::Mel => "MEL",
             CMPOp::Se => "SE",
             CMPOp::Sel => "SEL",
-        };
-        write!(f, "{}", cmd)
+        }
+    }
+}
+
+impl fmt::Display for CMPOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())
     }
 }
 
+pub const ALE_OPS: [ALEOp; 7] = [
+    ALEOp::Og,
+    ALEOp::Eller,
+    ALEOp::XEller,
+    ALEOp::Vskift,
+    ALEOp::Hskift,
+    ALEOp::Pluss,
+    ALEOp::Minus,
+];
+
+pub const CMP_OPS: [CMPOp; 6] = [
+    CMPOp::Lik,
+    CMPOp::Ulik,
+    CMPOp::Me,
+    CMPOp::Mel,
+    CMPOp::Se,
+    CMPOp::Sel,
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OperandKind {
+    Register,
+    Immediate,
+    Address,
+}
+
+pub struct OpClass {
+    pub operands: &'static [OperandKind],
+    pub is_branch: bool,
+    pub modifies_flag: bool,
+    decode: fn(u16) -> Option<Instruction>,
+}
+
+use OperandKind::{Address, Immediate, Register};
+
+const fn class(
+    operands: &'static [OperandKind],
+    is_branch: bool,
+    modifies_flag: bool,
+    decode: fn(u16) -> Option<Instruction>,
+) -> OpClass {
+    OpClass {
+        operands,
+        is_branch,
+        modifies_flag,
+        decode,
+    }
+}
+
+fn operation(op: u16) -> usize {
+    ((op >> 4) & 0xf) as usize
+}
+
+fn arg1(op: u16) -> Operand {
+    Operand::Reg(((op >> 8) & 0xf) as u8)
+}
+
+fn arg2(op: u16) -> Operand {
+    Operand::Reg(((op >> 12) & 0xf) as u8)
+}
+
+fn address(op: u16) -> Operand {
+    Operand::Addr(op >> 4)
+}
+
+pub static OPCLASSES: [OpClass; 16] = [
+    class(&[], false, false, |_| Some(Instruction::Stopp)),
+    class(&[Register, Immediate], false, false, |op| {
+        Some(Instruction::SettImmediate(
+            Operand::Reg(operation(op) as u8),
+            Operand::Imm((op >> 8) as u8),
+        ))
+    }),
+    class(&[Register, Register], false, false, |op| {
+        Some(Instruction::Sett(
+            Operand::Reg(operation(op) as u8),
+            arg1(op),
+        ))
+    }),
+    class(&[Address], false, false, |op| {
+        Some(Instruction::Finn(address(op)))
+    }),
+    class(&[Register], false, false, |op| match operation(op) {
+        0 => Some(Instruction::Last(arg1(op))),
+        1 => Some(Instruction::Lagr(arg1(op))),
+        _ => None,
+    }),
+    class(&[Register, Register], false, false, |op| {
+        let aleop = ALE_OPS.get(operation(op))?;
+        Some(Instruction::ALE(*aleop, arg1(op), arg2(op)))
+    }),
+    class(&[Register], false, false, |op| match operation(op) {
+        0 => Some(Instruction::Les(arg1(op))),
+        1 => Some(Instruction::Skriv(arg1(op))),
+        _ => None,
+    }),
+    class(&[Register, Register], false, true, |op| {
+        let cmpop = CMP_OPS.get(operation(op))?;
+        Some(Instruction::Sammenligne(*cmpop, arg1(op), arg2(op)))
+    }),
+    class(&[Address], true, false, |op| {
+        Some(Instruction::Hopp(address(op)))
+    }),
+    class(&[Address], true, false, |op| {
+        Some(Instruction::BHopp(address(op)))
+    }),
+    class(&[Address], true, false, |op| {
+        Some(Instruction::Tur(address(op)))
+    }),
+    class(&[], true, false, |_| Some(Instruction::Retur)),
+    class(&[], false, false, |_| Some(Instruction::Nope)),
+    class(&[], false, false, |_| None),
+    class(&[], false, false, |_| None),
+    class(&[], false, false, |_| None),
+];
+
+static DATA_CLASS: OpClass = class(&[], false, false, |_| None);
+
+/// A decoded SLEDE8 instruction; opcodes that decode to nothing become `Data`.
+#[allow(clippy::upper_case_acronyms)]
 pub enum Instruction {
-    SettImmediate(usize, u8),
-    Sett(usize, usize),
+    SettImmediate(Operand, Operand),
+    Sett(Operand, Operand),
     Nope,
     Stopp,
-    ALE(ALEOp, usize, usize),
-    Sammenligne(CMPOp, usize, usize),
-    Hopp(u16),
-    BHopp(u16),
-    Les(usize),
-    Skriv(usize),
-    Finn(u16),
-    Last(usize),
-    Lagr(usize),
-    Tur(u16),
+    ALE(ALEOp, Operand, Operand),
+    Sammenligne(CMPOp, Operand, Operand),
+    Hopp(Operand),
+    BHopp(Operand),
+    Les(Operand),
+    Skriv(Operand),
+    Finn(Operand),
+    Last(Operand),
+    Lagr(Operand),
+    Tur(Operand),
     Retur,
     Data(u16),
 }
 
-impl fmt::Display for Instruction {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    Reg(u8),
+    Imm(u8),
+    Addr(u16),
+}
+
+impl Operand {
+    /// The register number, immediate value or address.
+    pub fn value(self) -> u16 {
+        match self {
+            Operand::Reg(v) | Operand::Imm(v) => u16::from(v),
+            Operand::Addr(a) => a,
+        }
+    }
+}
+
+impl fmt::LowerHex for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.value(), f)
+    }
+}
+
+impl From<Operand> for usize {
+    fn from(operand: Operand) -> usize {
+        usize::from(operand.value())
+    }
+}
+
+impl fmt::Display for Operand {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Instruction::Sett(o1, o2) => write!(f, "SETT\tr{}, r{}", o1, o2),
-            Instruction::SettImmediate(o1, o2) => write!(f, "SETT\tr{}, {}", o1, o2),
-            Instruction::Nope => write!(f, "NOPE"),
-            Instruction::Stopp => write!(f, "STOPP"),
-            Instruction::ALE(i, o1, o2) => write!(f, "{}\tr{}, r{}", i, o1, o2),
-            Instruction::Sammenligne(i, o1, o2) => write!(f, "{}\tr{}, r{}", i, o1, o2),
-            Instruction::Hopp(o1) => write!(f, "HOPP\t{:#06x}", o1),
-            Instruction::BHopp(o1) => write!(f, "BHOPP\t{:#06x}", o1),
-            Instruction::Les(o1) => write!(f, "LES\tr{}", o1),
-            Instruction::Skriv(o1) => write!(f, "SKRIV\tr{}", o1),
-            Instruction::Finn(o1) => write!(f, "FINN\t{:#06x}", o1),
-            Instruction::Last(o1) => write!(f, "LAST\tr{}", o1),
-            Instruction::Lagr(o1) => write!(f, "LAGR\tr{}", o1),
-            Instruction::Tur(o1) => write!(f, "TUR\t{:#06x}", o1),
-            Instruction::Retur => write!(f, "RETUR"),
-            Instruction::Data(d) => write!(f, ".DATA\t{:#x},{:#x}", (d >> 8), d & 0xff),
+            Operand::Reg(r) => write!(f, "r{}", r),
+            Operand::Imm(v) => write!(f, "{}", v),
+            Operand::Addr(a) => write!(f, "{:#06x}", a),
         }
     }
 }
 
-pub fn decode_instruction(op: u16) -> Instruction {
-    let opclass = op & 0xf;
-    let operation = ((op >> 4) & 0xf) as usize;
-    let value = (op >> 8) as u8;
-    let address = (op >> 4) as u16;
-    let arg1 = ((op >> 8) & 0xf) as usize;
-    let arg2 = ((op >> 12) & 0xf) as usize;
-
-    let data = Instruction::Data(op);
-
-    match opclass {
-        0x0 => Instruction::Stopp,
-        0x1 => Instruction::SettImmediate(operation, value),
-        0x2 => Instruction::Sett(operation, arg1),
-        0x3 => Instruction::Finn(address),
-        0x4 => {
-            if operation == 0 {
-                Instruction::Last(arg1)
-            } else if operation == 1 {
-                Instruction::Lagr(arg1)
-            } else {
-                data
-            }
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Instruction::Data(d) = self {
+            return write!(f, ".DATA\t{:#x},{:#x}", d & 0xff, d >> 8);
         }
-        0x5 => {
-            if let Some(aleop) = match operation {
-                0 => Some(ALEOp::Og),
-                1 => Some(ALEOp::Eller),
-                2 => Some(ALEOp::XEller),
-                3 => Some(ALEOp::Vskift),
-                4 => Some(ALEOp::Hskift),
-                5 => Some(ALEOp::Pluss),
-                6 => Some(ALEOp::Minus),
-                _ => None,
-            } {
-                Instruction::ALE(aleop, arg1, arg2)
-            } else {
-                data
-            }
+        f.write_str(self.mnemonic())?;
+        for (i, operand) in self.operands().enumerate() {
+            f.write_str(if i == 0 { "\t" } else { ", " })?;
+            write!(f, "{}", operand)?;
+        }
+        Ok(())
+    }
+}
+
+impl Instruction {
+    pub fn class(&self) -> &'static OpClass {
+        match self {
+            Instruction::Data(_) => &DATA_CLASS,
+            instruction => &OPCLASSES[usize::from(encode_instruction(instruction) & 0xf)],
+        }
+    }
+
+    pub fn operand_kinds(&self) -> &'static [OperandKind] {
+        self.class().operands
+    }
+
+    pub fn is_branch(&self) -> bool {
+        self.class().is_branch
+    }
+
+    pub fn modifies_flag(&self) -> bool {
+        self.class().modifies_flag
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Sett(..) | Instruction::SettImmediate(..) => "SETT",
+            Instruction::Nope => "NOPE",
+            Instruction::Stopp => "STOPP",
+            Instruction::ALE(op, ..) => op.mnemonic(),
+            Instruction::Sammenligne(op, ..) => op.mnemonic(),
+            Instruction::Hopp(_) => "HOPP",
+            Instruction::BHopp(_) => "BHOPP",
+            Instruction::Les(_) => "LES",
+            Instruction::Skriv(_) => "SKRIV",
+            Instruction::Finn(_) => "FINN",
+            Instruction::Last(_) => "LAST",
+            Instruction::Lagr(_) => "LAGR",
+            Instruction::Tur(_) => "TUR",
+            Instruction::Retur => "RETUR",
+            Instruction::Data(_) => ".DATA",
         }
-        0x6 => match operation {
-            0 => Instruction::Les(arg1),
-            1 => Instruction::Skriv(arg1),
-            _ => data,
-        },
-        0x7 => {
-            if let Some(cmpop) = match operation {
-                0 => Some(CMPOp::Lik),
-                1 => Some(CMPOp::Ulik),
-                2 => Some(CMPOp::Me),
-                3 => Some(CMPOp::Mel),
-                4 => Some(CMPOp::Se),
-                5 => Some(CMPOp::Sel),
-                _ => None,
-            } {
-                Instruction::Sammenligne(cmpop, arg1, arg2)
-            } else {
-                data
+    }
+
+    pub fn operands(&self) -> impl Iterator<Item = Operand> {
+        let operands = match *self {
+            Instruction::SettImmediate(a, b)
+            | Instruction::Sett(a, b)
+            | Instruction::ALE(_, a, b)
+            | Instruction::Sammenligne(_, a, b) => [Some(a), Some(b)],
+            Instruction::Les(a)
+            | Instruction::Skriv(a)
+            | Instruction::Last(a)
+            | Instruction::Lagr(a)
+            | Instruction::Hopp(a)
+            | Instruction::BHopp(a)
+            | Instruction::Tur(a)
+            | Instruction::Finn(a) => [Some(a), None],
+            Instruction::Nope | Instruction::Stopp | Instruction::Retur | Instruction::Data(_) => {
+                [None, None]
             }
+        };
+        IntoIterator::into_iter(operands).flatten()
+    }
+
+    pub fn targets(&self) -> Option<u16> {
+        match self {
+            Instruction::Hopp(a) | Instruction::BHopp(a) | Instruction::Tur(a) => Some(a.value()),
+            _ => None,
+        }
+    }
+
+    pub fn successors(&self, pos: usize) -> Vec<usize> {
+        let targets = self.targets().into_iter().map(usize::from);
+        match self {
+            Instruction::Stopp | Instruction::Retur | Instruction::Hopp(_) => targets.collect(),
+            _ => std::iter::once(pos + 2).chain(targets).collect(),
         }
-        0x8 => Instruction::Hopp(address),
-        0x9 => Instruction::BHopp(address),
-        0xa => Instruction::Tur(address),
-        0xb => Instruction::Retur,
-        0xc => Instruction::Nope,
-        _ => data,
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = Operand> {
+        let writes = match *self {
+            Instruction::SettImmediate(r, _)
+            | Instruction::Sett(r, _)
+            | Instruction::ALE(_, r, _)
+            | Instruction::Les(r)
+            | Instruction::Last(r) => [Some(r), None],
+            Instruction::Finn(_) => [Some(Operand::Reg(0)), Some(Operand::Reg(1))],
+            _ => [None, None],
+        };
+        IntoIterator::into_iter(writes).flatten()
+    }
+
+    pub fn reads(&self) -> impl Iterator<Item = Operand> {
+        let reads = match *self {
+            Instruction::Sett(_, r) | Instruction::Skriv(r) => [Some(r), None, None],
+            Instruction::ALE(_, ra, rb) | Instruction::Sammenligne(_, ra, rb) => {
+                [Some(ra), Some(rb), None]
+            }
+            Instruction::Last(_) => [Some(Operand::Reg(0)), Some(Operand::Reg(1)), None],
+            Instruction::Lagr(r) => [Some(r), Some(Operand::Reg(0)), Some(Operand::Reg(1))],
+            _ => [None, None, None],
+        };
+        IntoIterator::into_iter(reads).flatten()
     }
 }
 
-pub fn disassemble(input: &[u8]) -> Vec<Line> {
-    if input[0..7] != vec![0x2e, 0x53, 0x4c, 0x45, 0x44, 0x45, 0x38] {
-        panic!("Not valid SLEDE8 file (missing magic bytes)");
+pub fn decode_at(memory: &[u8], address: usize) -> Instruction {
+    decode_instruction(LittleEndian::read_u16(&memory[address..address + 2]))
+}
+
+/// Decodes one little-endian opcode word.
+pub fn decode_instruction(op: u16) -> Instruction {
+    (OPCLASSES[usize::from(op & 0xf)].decode)(op).unwrap_or(Instruction::Data(op))
+}
+
+pub fn program(input: &[u8]) -> Result<&[u8], S8Error> {
+    if !input.starts_with(MAGIC) {
+        return Err(S8Error::NotSlede8);
     }
+    Ok(&input[MAGIC.len()..input.len().min(MAGIC.len() + MEMORY_SIZE)])
+}
+
+pub fn memory_image(program: &[u8]) -> [u8; MEMORY_SIZE] {
+    let mut memory = [0; MEMORY_SIZE];
+    memory[..program.len()].copy_from_slice(program);
+    memory
+}
 
-    let mut program: &[u8] = &input[7..input.len()];
-    if program.len() % 2 != 0 {
-        program = &input[7..input.len() - 1];
+/// Lists the instructions of a `.s8` file (including its `.SLEDE8` header), numbering them from `base`.
+pub fn disassemble(input: &[u8], base: usize) -> Result<Vec<Line>, S8Error> {
+    let mut program = program(input)?;
+    if !program.len().is_multiple_of(2) {
+        program = &program[..program.len() - 1];
     }
-    program
+    Ok(program
         .chunks(2)
         .enumerate()
         .map(|(i, chunk)| {
             let combined = LittleEndian::read_u16(chunk);
             Line {
-                pos: i * 2,
+                pos: base + i * 2,
                 op: combined,
                 instruction: decode_instruction(combined),
             }
         })
-        .collect()
+        .collect())
+}
+
+/// Finds every address executed when starting at 0 and following both sides of each branch, jump and call.
+pub fn reachable(memory: &[u8]) -> BTreeSet<usize> {
+    let mut seen = BTreeSet::new();
+    let mut queue = vec![0];
+    while let Some(pc) = queue.pop() {
+        if pc + 1 >= memory.len() || !seen.insert(pc) {
+            continue;
+        }
+        queue.extend(decode_at(memory, pc).successors(pc));
+    }
+    seen
+}
+
+/// Lists the instructions reachable from address 0, showing every word that is never executed as `.DATA`.
+pub fn disassemble_recursive(input: &[u8], base: usize) -> Result<Vec<Line>, S8Error> {
+    let code = reachable(&memory_image(program(input)?));
+    let mut lines = disassemble_overlapping(input, base, &code)?;
+    for line in &mut lines {
+        if !code.contains(&(line.pos - base)) {
+            line.instruction = Instruction::Data(line.op);
+        }
+    }
+    Ok(lines)
+}
+
+fn index_of<T: PartialEq>(table: &[T], op: &T) -> u16 {
+    table.iter().position(|o| o == op).unwrap() as u16
+}
+
+fn read_word<R: Read>(reader: &mut R) -> io::Result<Option<u16>> {
+    let mut word = [0; 2];
+    loop {
+        match reader.read(&mut word[..1]) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    reader
+        .read_exact(&mut word[1..])
+        .map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => {
+                io::Error::new(e.kind(), "stream ends in the middle of a word")
+            }
+            _ => e,
+        })?;
+    Ok(Some(LittleEndian::read_u16(&word)))
+}
+
+pub fn disassemble_overlapping(
+    input: &[u8],
+    base: usize,
+    code: &BTreeSet<usize>,
+) -> Result<Vec<Line>, S8Error> {
+    let program = program(input)?;
+    let mut lines = disassemble(input, base)?;
+    lines.extend(
+        code.iter()
+            .filter(|&&pc| !pc.is_multiple_of(2) && pc + 1 < program.len())
+            .map(|&pc| {
+                let op = LittleEndian::read_u16(&program[pc..pc + 2]);
+                Line {
+                    pos: base + pc,
+                    op,
+                    instruction: decode_instruction(op),
+                }
+            }),
+    );
+    lines.sort_by_key(|l| l.pos);
+    Ok(lines)
+}
+
+pub fn disassemble_stream<R: Read>(
+    reader: R,
+    base: usize,
+) -> impl Iterator<Item = io::Result<Line>> {
+    let mut reader = BufReader::new(reader);
+    let mut pos = base;
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        match read_word(&mut reader) {
+            Ok(Some(op)) => {
+                let line = Line {
+                    pos,
+                    op,
+                    instruction: decode_instruction(op),
+                };
+                pos += 2;
+                Some(Ok(line))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                failed = true;
+                Some(Err(e))
+            }
+        }
+    })
+}
+
+pub fn encode_instruction(instruction: &Instruction) -> u16 {
+    let reg = |r: Operand| r.value() & 0xf;
+    let addr = |a: Operand| (a.value() & 0xfff) << 4;
+    match instruction {
+        Instruction::Stopp => 0x0,
+        Instruction::SettImmediate(ra, val) => 0x1 | reg(*ra) << 4 | val.value() << 8,
+        Instruction::Sett(ra, rb) => 0x2 | reg(*ra) << 4 | reg(*rb) << 8,
+        Instruction::Finn(a) => 0x3 | addr(*a),
+        Instruction::Last(r) => 0x4 | reg(*r) << 8,
+        Instruction::Lagr(r) => 0x4 | 1 << 4 | reg(*r) << 8,
+        Instruction::ALE(op, ra, rb) => {
+            0x5 | index_of(&ALE_OPS, op) << 4 | reg(*ra) << 8 | reg(*rb) << 12
+        }
+        Instruction::Les(r) => 0x6 | reg(*r) << 8,
+        Instruction::Skriv(r) => 0x6 | 1 << 4 | reg(*r) << 8,
+        Instruction::Sammenligne(op, ra, rb) => {
+            0x7 | index_of(&CMP_OPS, op) << 4 | reg(*ra) << 8 | reg(*rb) << 12
+        }
+        Instruction::Hopp(a) => 0x8 | addr(*a),
+        Instruction::BHopp(a) => 0x9 | addr(*a),
+        Instruction::Tur(a) => 0xa | addr(*a),
+        Instruction::Retur => 0xb,
+        Instruction::Nope => 0xc,
+        Instruction::Data(d) => *d,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_word_round_trips() {
+        for word in 0..=u16::MAX {
+            let instruction = decode_instruction(word);
+            assert_eq!(
+                instruction.operands().count(),
+                instruction.operand_kinds().len()
+            );
+            let op = encode_instruction(&instruction);
+            assert_eq!(encode_instruction(&decode_instruction(op)), op);
+        }
+    }
+
+    #[test]
+    fn memory_operands() {
+        let lagr = Instruction::Lagr(Operand::Reg(5));
+        let reads: Vec<Operand> = lagr.reads().collect();
+        assert_eq!(reads, [Operand::Reg(5), Operand::Reg(0), Operand::Reg(1)]);
+        assert_eq!(lagr.writes().count(), 0);
+        let finn = Instruction::Finn(Operand::Addr(0x123));
+        let writes: Vec<Operand> = finn.writes().collect();
+        assert_eq!(writes, [Operand::Reg(0), Operand::Reg(1)]);
+        assert_eq!(finn.targets(), None);
+        assert_eq!(finn.to_string(), "FINN\t0x0123");
+    }
 }
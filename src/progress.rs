@@ -0,0 +1,103 @@
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const REDRAW: Duration = Duration::from_millis(250);
+const WIDTH: usize = 24;
+
+pub struct Progress {
+    total: usize,
+    resumed: usize,
+    done: AtomicUsize,
+    started: Instant,
+    drawn: Mutex<Instant>,
+    score: AtomicUsize,
+    best: Mutex<Option<(usize, String)>>,
+    enabled: bool,
+}
+
+fn clock(seconds: f64) -> String {
+    if !seconds.is_finite() || seconds > 359_999.0 {
+        return String::from("?");
+    }
+    let seconds = seconds as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+impl Progress {
+    pub fn new(total: usize, resumed: usize) -> Progress {
+        Progress {
+            total,
+            resumed,
+            done: AtomicUsize::new(resumed),
+            started: Instant::now(),
+            drawn: Mutex::new(Instant::now()),
+            score: AtomicUsize::new(0),
+            best: Mutex::new(None),
+            enabled: io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn step(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        self.draw(done);
+    }
+
+    pub fn offer(&self, score: usize, describe: impl FnOnce() -> String) {
+        if score < self.score.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut best = self.best.lock().expect("progress lock");
+        if best.as_ref().is_none_or(|(s, _)| score > *s) {
+            *best = Some((score, describe()));
+            self.score.store(score + 1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn best(&self) -> Option<(usize, String)> {
+        self.best.lock().expect("progress lock").clone()
+    }
+
+    fn draw(&self, done: usize) {
+        if !self.enabled {
+            return;
+        }
+        let mut drawn = match self.drawn.try_lock() {
+            Ok(drawn) => drawn,
+            Err(_) => return,
+        };
+        if drawn.elapsed() < REDRAW {
+            return;
+        }
+        *drawn = Instant::now();
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = (done - self.resumed) as f64 / elapsed;
+        let filled = WIDTH * done.min(self.total) / self.total.max(1);
+        let best = match self.best() {
+            Some((_, best)) => format!("  best {}", best),
+            None => String::new(),
+        };
+        eprint!(
+            "\r[{}{}] {}/{}  {:.0}/s  ETA {}{}\x1b[K",
+            "#".repeat(filled),
+            ".".repeat(WIDTH - filled),
+            done,
+            self.total,
+            rate,
+            clock(self.total.saturating_sub(done) as f64 / rate),
+            best
+        );
+    }
+
+    pub fn finish(&self) {
+        if self.enabled {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use crate::source::{parse_number, parse_register};
+use crate::types::{self, Typed};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, ErrorKind};
+
+pub const DEFAULT_PATH: &str = ".s8project";
+
+#[derive(Default)]
+pub struct Project {
+    programs: BTreeMap<u64, Vec<(String, String)>>,
+}
+
+#[derive(Clone)]
+pub struct Region {
+    pub start: usize,
+    pub end: Option<usize>,
+    pub name: String,
+}
+
+#[derive(Clone)]
+pub struct Variable {
+    pub start: usize,
+    pub end: usize,
+    pub register: usize,
+    pub name: String,
+}
+
+#[derive(Default)]
+pub struct Session {
+    pub breakpoints: Vec<usize>,
+    pub watchpoints: Vec<String>,
+    pub checkpoint: Option<String>,
+    pub aliases: BTreeMap<usize, String>,
+    pub regions: Vec<Region>,
+    pub variables: Vec<Variable>,
+    pub types: Vec<Typed>,
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "{:#05x}-{:#05x}={}", self.start, end, self.name),
+            None => write!(f, "{:#05x}={}", self.start, self.name),
+        }
+    }
+}
+
+impl fmt::Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:#05x}-{:#05x} r{}={}",
+            self.start, self.end, self.register, self.name
+        )
+    }
+}
+
+pub fn parse_range(text: &str) -> Option<(usize, Option<usize>)> {
+    let (start, end) = match text.split_once('-') {
+        Some((start, end)) => (start, Some(end)),
+        None => (text, None),
+    };
+    let start = parse_number(start.trim()).filter(|&a| a < 0x1000)? as usize;
+    match end.map(|end| parse_number(end.trim()).filter(|&a| a < 0x1000)) {
+        Some(Some(end)) if end as usize >= start => Some((start, Some(end as usize))),
+        Some(_) => None,
+        None => Some((start, None)),
+    }
+}
+
+pub fn name_at(regions: &[Region], address: usize) -> Option<(&Region, usize)> {
+    regions
+        .iter()
+        .filter(|r| r.start <= address && r.end.is_none_or(|end| address <= end))
+        .max_by_key(|r| r.start)
+        .map(|r| (r, address - r.start))
+}
+
+pub fn names_at(
+    aliases: &BTreeMap<usize, String>,
+    variables: &[Variable],
+    pc: usize,
+) -> BTreeMap<usize, String> {
+    let mut names = aliases.clone();
+    for variable in variables.iter().filter(|v| v.start <= pc && pc <= v.end) {
+        names.insert(variable.register, variable.name.clone());
+    }
+    names
+}
+
+impl fmt::Display for Project {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (program, entries) in &self.programs {
+            writeln!(f, "[program {:016x}]", program)?;
+            for (key, value) in entries {
+                writeln!(f, "{} {}", key, value)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Project {
+    pub fn load(path: &str) -> Result<Project, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Project::default()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let mut project = Project::default();
+        let mut current = None;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix("[program ") {
+                let hash = header
+                    .strip_suffix(']')
+                    .and_then(|h| u64::from_str_radix(h, 16).ok())
+                    .ok_or(format!("line {}: bad program header", i + 1))?;
+                project.programs.entry(hash).or_default();
+                current = Some(hash);
+                continue;
+            }
+            let program = current.ok_or(format!("line {}: entry outside a program", i + 1))?;
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            project
+                .programs
+                .entry(program)
+                .or_default()
+                .push((key.to_string(), value.trim().to_string()));
+        }
+        Ok(project)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn get(&self, program: u64, key: &str) -> Vec<&str> {
+        self.programs
+            .get(&program)
+            .into_iter()
+            .flatten()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    pub fn set(&mut self, program: u64, key: &str, values: Vec<String>) {
+        let entries = self.programs.entry(program).or_default();
+        entries.retain(|(k, _)| k != key);
+        entries.extend(values.into_iter().map(|v| (key.to_string(), v)));
+    }
+
+    pub fn aliases(&self, program: u64) -> BTreeMap<usize, String> {
+        self.get(program, "alias")
+            .into_iter()
+            .flat_map(|line| line.split(','))
+            .filter_map(|alias| {
+                let (register, name) = alias.split_once('=')?;
+                let register = parse_register(register).filter(|&r| r < 16)?;
+                Some((register, name.trim().to_string()))
+            })
+            .collect()
+    }
+
+    pub fn regions(&self, program: u64) -> Vec<Region> {
+        self.get(program, "label")
+            .into_iter()
+            .flat_map(|line| line.split(','))
+            .filter_map(|label| {
+                let (range, name) = label.split_once('=')?;
+                let (start, end) = parse_range(range)?;
+                Some(Region {
+                    start,
+                    end,
+                    name: name.trim().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn labels(&self, program: u64) -> BTreeMap<usize, String> {
+        self.regions(program)
+            .into_iter()
+            .map(|r| (r.start, r.name))
+            .collect()
+    }
+
+    pub fn types(&self, program: u64) -> Vec<Typed> {
+        let mut typed: Vec<Typed> = self
+            .get(program, "type")
+            .into_iter()
+            .flat_map(|line| line.split(", "))
+            .filter_map(|entry| {
+                let (address, data) = entry.split_once('=')?;
+                Some(Typed {
+                    start: parse_number(address.trim()).filter(|&a| a < 0x1000)? as usize,
+                    data: types::parse(data).ok()?,
+                })
+            })
+            .collect();
+        typed.sort_by_key(|t| t.start);
+        typed
+    }
+
+    pub fn variables(&self, program: u64) -> Vec<Variable> {
+        let mut variables = Vec::new();
+        for line in self.get(program, "var") {
+            let (range, names) = line.split_once(' ').unwrap_or((line, ""));
+            let (start, end) = match parse_range(range) {
+                Some((start, Some(end))) => (start, end),
+                _ => continue,
+            };
+            for name in names.split(',') {
+                if let Some((register, name)) = name.split_once('=') {
+                    if let Some(register) = parse_register(register.trim()).filter(|&r| r < 16) {
+                        variables.push(Variable {
+                            start,
+                            end,
+                            register,
+                            name: name.trim().to_string(),
+                        });
+                    }
+                }
+            }
+        }
+        variables
+    }
+
+    pub fn session(&self, program: u64) -> Session {
+        Session {
+            breakpoints: self
+                .get(program, "break")
+                .into_iter()
+                .filter_map(|a| parse_number(a).map(|a| a as usize))
+                .collect(),
+            watchpoints: self
+                .get(program, "watch")
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            checkpoint: self
+                .get(program, "checkpoint")
+                .first()
+                .map(|c| c.to_string()),
+            aliases: self.aliases(program),
+            regions: self.regions(program),
+            variables: self.variables(program),
+            types: self.types(program),
+        }
+    }
+
+    pub fn store_session(&mut self, program: u64, session: &Session) {
+        let breakpoints = session
+            .breakpoints
+            .iter()
+            .map(|a| format!("{:#06x}", a))
+            .collect();
+        self.set(program, "break", breakpoints);
+        self.set(program, "watch", session.watchpoints.clone());
+        self.set(
+            program,
+            "checkpoint",
+            session.checkpoint.iter().cloned().collect(),
+        );
+        self.set(
+            program,
+            "label",
+            session.regions.iter().map(Region::to_string).collect(),
+        );
+        self.set(
+            program,
+            "var",
+            session.variables.iter().map(Variable::to_string).collect(),
+        );
+    }
+}
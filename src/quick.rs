@@ -0,0 +1,134 @@
+use crate::emulator::{HaltReason, RunResult, State};
+use crate::error::S8Error;
+use crate::parser::{self, MEMORY_SIZE};
+use std::cell::RefCell;
+use std::time::Instant;
+
+/// A program ready to be run many times by [`quick_run`].
+pub struct Program {
+    initial: State,
+}
+
+impl Program {
+    /// Loads a `.s8` file, including its `.SLEDE8` header.
+    pub fn new(input: &[u8]) -> Result<Program, S8Error> {
+        Ok(Program::from_image(parser::memory_image(parser::program(
+            input,
+        )?)))
+    }
+
+    pub fn from_image(memory: [u8; MEMORY_SIZE]) -> Program {
+        Program {
+            initial: State::new(memory, Vec::new()),
+        }
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+}
+
+/// Runs `program` on `input` for at most `limit` cycles.
+///
+/// Each thread keeps one machine and resets it in place between calls, so after the first call on a
+/// thread the only allocation is the returned output.
+pub fn quick_run(program: &Program, input: &[u8], limit: usize) -> RunResult {
+    STATE.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let started = Instant::now();
+        let state = cell.get_or_insert_with(|| program.initial.clone());
+        state.reset(&program.initial, input);
+        state.run(limit);
+        RunResult {
+            halt: state.halted().unwrap_or(HaltReason::TickLimit(limit)),
+            cycles: state.cycles(),
+            wall_time: started.elapsed(),
+            input_read: state.input_read(),
+            output: state.stdout().to_vec(),
+            coverage: None,
+            state: None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{encode_instruction, Instruction, Operand};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    struct Counting;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    fn image(instructions: &[Instruction]) -> [u8; MEMORY_SIZE] {
+        let program: Vec<u8> = instructions
+            .iter()
+            .flat_map(|i| encode_instruction(i).to_le_bytes())
+            .collect();
+        parser::memory_image(&program)
+    }
+
+    #[test]
+    fn alternating_programs_match_fresh_runs_without_allocating() {
+        let echo = image(&[
+            Instruction::Finn(Operand::Addr(0x300)),
+            Instruction::Les(Operand::Reg(2)),
+            Instruction::Lagr(Operand::Reg(2)),
+            Instruction::Skriv(Operand::Reg(2)),
+            Instruction::Hopp(Operand::Addr(2)),
+        ]);
+        let store = image(&[
+            Instruction::Finn(Operand::Addr(0x700)),
+            Instruction::Les(Operand::Reg(3)),
+            Instruction::Lagr(Operand::Reg(3)),
+            Instruction::Tur(Operand::Addr(10)),
+            Instruction::Stopp,
+            Instruction::Retur,
+        ]);
+        let programs = [
+            (Program::from_image(echo), echo),
+            (Program::from_image(store), store),
+        ];
+        let inputs: [&[u8]; 3] = [b"abc", b"xyz", b"q"];
+        for round in 0..4 {
+            for (program, memory) in &programs {
+                for input in inputs {
+                    let before = ALLOCATIONS.with(Cell::get);
+                    let result = quick_run(program, input, 100);
+                    let allocations = ALLOCATIONS.with(Cell::get) - before;
+                    let fresh = State::new(*memory, input.to_vec()).run_result(100);
+                    assert_eq!(
+                        (
+                            result.halt,
+                            result.cycles,
+                            result.input_read,
+                            &result.output
+                        ),
+                        (fresh.halt, fresh.cycles, fresh.input_read, &fresh.output)
+                    );
+                    if round > 0 {
+                        assert_eq!(allocations, usize::from(!result.output.is_empty()));
+                    }
+                }
+            }
+        }
+    }
+}
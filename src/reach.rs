@@ -0,0 +1,423 @@
+use crate::batch::{self, LANES};
+use crate::checkpoint::{visit, Checkpoint};
+use crate::emulator::{HaltReason, State};
+use crate::parser::{Instruction, MEMORY_SIZE};
+use crate::progress::Progress;
+use crate::rng::Rng;
+use crate::source::parse_number;
+use crate::symex::{self, Limits};
+use rayon::prelude::*;
+use std::ops::Range;
+
+const BACKENDS: [&str; 5] = ["brute", "batch", "guided", "random", "symex"];
+
+pub struct Options {
+    pub backend: String,
+    pub seed: u64,
+    pub count: usize,
+    pub length: usize,
+    pub printable: bool,
+}
+
+pub fn parse(spec: &str, default_seed: u64) -> Result<Options, String> {
+    let mut parts = spec.split(':');
+    let backend = parts.next().unwrap_or_default().to_string();
+    if !BACKENDS.contains(&backend.as_str()) {
+        return Err(format!(
+            "unknown backend {}, expected one of {}",
+            backend,
+            BACKENDS.join(", ")
+        ));
+    }
+    let mut options = Options {
+        length: if backend == "brute" || backend == "batch" {
+            4
+        } else {
+            32
+        },
+        backend,
+        seed: default_seed,
+        count: 1_000_000,
+        printable: true,
+    };
+    for part in parts.filter(|p| !p.is_empty()) {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        let number = parse_number(value).ok_or(format!("bad value in {}", part))?;
+        match key {
+            "seed" => options.seed = u64::from(number),
+            "count" => options.count = number as usize,
+            "len" => options.length = number as usize,
+            "printable" => options.printable = number != 0,
+            _ => return Err(format!("unknown reach option {}", key)),
+        }
+    }
+    Ok(options)
+}
+
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub count: usize,
+}
+
+pub struct Found {
+    pub input: Vec<u8>,
+    pub path: Vec<Edge>,
+}
+
+pub struct Search {
+    pub found: Option<Found>,
+    pub tried: usize,
+    pub coverage: Vec<bool>,
+}
+
+fn alphabet(options: &Options) -> Vec<u8> {
+    if options.printable {
+        (0x20..0x7f).collect()
+    } else {
+        (0..=0xff).collect()
+    }
+}
+
+fn run(
+    memory: [u8; MEMORY_SIZE],
+    input: Vec<u8>,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+) -> (Option<usize>, usize) {
+    finish(&mut State::new(memory, input), max_cycles, checkpoint)
+}
+
+fn until_read(
+    state: &mut State,
+    position: usize,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+) -> bool {
+    let mut visited = [0; MEMORY_SIZE / 64];
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        if state.input_read() == position && matches!(state.next_instruction(), Instruction::Les(_))
+        {
+            break;
+        }
+        visit(&mut visited, state.pc());
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+    }
+    checkpoint.cover(&visited);
+    state.halted().is_none() && state.cycles() < max_cycles
+}
+
+fn finish(state: &mut State, max_cycles: usize, checkpoint: &Checkpoint) -> (Option<usize>, usize) {
+    let mut last = state.pc();
+    let mut visited = [0; MEMORY_SIZE / 64];
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        last = state.pc();
+        visit(&mut visited, last);
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+    }
+    checkpoint.cover(&visited);
+    match state.halted() {
+        Some(HaltReason::Stopp) => (Some(last), state.cycles()),
+        _ => (None, state.cycles()),
+    }
+}
+
+fn path(memory: [u8; MEMORY_SIZE], input: &[u8], max_cycles: usize) -> Vec<Edge> {
+    let mut state = State::new(memory, input.to_vec());
+    let mut edges: Vec<Edge> = Vec::new();
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        let from = state.pc();
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+        let to = state.pc();
+        if state.halted().is_some() || to == from + 2 {
+            continue;
+        }
+        match edges.last_mut() {
+            Some(edge) if edge.from == from && edge.to == to => edge.count += 1,
+            _ => edges.push(Edge { from, to, count: 1 }),
+        }
+    }
+    edges
+}
+
+fn attempt(
+    memory: [u8; MEMORY_SIZE],
+    input: &[u8],
+    target: usize,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+    progress: &Progress,
+) -> bool {
+    let (stopp, cycles) = run(memory, input.to_vec(), max_cycles, checkpoint);
+    progress.step();
+    progress.offer(cycles, || describe(input, cycles));
+    stopp == Some(target)
+}
+
+fn describe(input: &[u8], cycles: usize) -> String {
+    format!("{} ({} cycles)", hex::encode(input), cycles)
+}
+
+fn nth_input(alphabet: &[u8], length: usize, mut n: usize) -> Vec<u8> {
+    let mut input = vec![0; length];
+    for b in input.iter_mut().rev() {
+        *b = alphabet[n % alphabet.len()];
+        n /= alphabet.len();
+    }
+    input
+}
+
+fn exhaustive_input(alphabet: &[u8], mut n: usize) -> Vec<u8> {
+    let mut length = 0;
+    loop {
+        let total = alphabet.len().saturating_pow(length as u32);
+        if n < total {
+            return nth_input(alphabet, length, n);
+        }
+        n -= total;
+        length += 1;
+    }
+}
+
+fn exhaustive_total(alphabet: &[u8], options: &Options) -> usize {
+    (0..=options.length).fold(0usize, |total, length| {
+        total.saturating_add(alphabet.len().saturating_pow(length as u32))
+    })
+}
+
+fn chunked(
+    total: usize,
+    options: &Options,
+    checkpoint: &Checkpoint,
+    progress: &Progress,
+    input: impl Fn(usize) -> Vec<u8>,
+    find: impl Fn(Range<usize>) -> Option<usize>,
+) -> (Option<usize>, usize) {
+    let found = checkpoint.saved.value("found");
+    if let Some(n) = found.and_then(|f| f.split(' ').next()?.parse().ok()) {
+        return (Some(n), checkpoint.explored());
+    }
+    for gap in checkpoint.unexplored(total) {
+        let mut start = gap.start;
+        while start < gap.end {
+            let end = start.saturating_add(checkpoint.every()).min(gap.end);
+            if let Some(n) = find(start..end) {
+                checkpoint.explore(start..n + 1);
+                let found = format!("{} {}", n, hex::encode(input(n)));
+                checkpoint.save(
+                    progress,
+                    &[("seed", options.seed.to_string()), ("found", found)],
+                );
+                return (Some(n), checkpoint.explored());
+            }
+            checkpoint.explore(start..end);
+            checkpoint.save(progress, &[("seed", options.seed.to_string())]);
+            start = end;
+        }
+    }
+    (None, checkpoint.explored())
+}
+
+fn brute(
+    memory: [u8; MEMORY_SIZE],
+    target: usize,
+    options: &Options,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+) -> (Option<Vec<u8>>, usize) {
+    let alphabet = alphabet(options);
+    let total = exhaustive_total(&alphabet, options);
+    let progress = checkpoint.progress(total);
+    let input = |n| exhaustive_input(&alphabet, n);
+    let (found, tried) = chunked(total, options, checkpoint, &progress, input, |range| {
+        range
+            .into_par_iter()
+            .find_first(|&n| attempt(memory, &input(n), target, max_cycles, checkpoint, &progress))
+    });
+    progress.finish();
+    (found.map(input), tried)
+}
+
+fn lockstep(
+    memory: [u8; MEMORY_SIZE],
+    target: usize,
+    options: &Options,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+) -> (Option<Vec<u8>>, usize) {
+    let alphabet = alphabet(options);
+    let total = exhaustive_total(&alphabet, options);
+    let progress = checkpoint.progress(total);
+    let input = |n| exhaustive_input(&alphabet, n);
+    let (found, tried) = chunked(total, options, checkpoint, &progress, input, |range| {
+        (0..range.len().div_ceil(LANES))
+            .into_par_iter()
+            .find_map_first(|chunk| {
+                let start = range.start + chunk * LANES;
+                let inputs: Vec<Vec<u8>> =
+                    (start..range.end.min(start + LANES)).map(input).collect();
+                let mut visited = [0; MEMORY_SIZE / 64];
+                let outcomes = batch::run(&memory, &inputs, max_cycles, &mut visited);
+                checkpoint.cover(&visited);
+                outcomes
+                    .iter()
+                    .zip(&inputs)
+                    .position(|(o, input)| {
+                        progress.step();
+                        progress.offer(o.cycles, || describe(input, o.cycles));
+                        o.halt == HaltReason::Stopp && o.pc == target
+                    })
+                    .map(|i| start + i)
+            })
+    });
+    progress.finish();
+    (found.map(input), tried)
+}
+
+fn random(
+    memory: [u8; MEMORY_SIZE],
+    target: usize,
+    options: &Options,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+) -> (Option<Vec<u8>>, usize) {
+    let alphabet = alphabet(options);
+    let input = |i: usize| {
+        let mut rng = Rng::new(options.seed.wrapping_add(i as u64));
+        let length = rng.next_u64() as usize % (options.length + 1);
+        (0..length)
+            .map(|_| alphabet[rng.next_u64() as usize % alphabet.len()])
+            .collect::<Vec<u8>>()
+    };
+    let progress = checkpoint.progress(options.count);
+    let (found, tried) = chunked(
+        options.count,
+        options,
+        checkpoint,
+        &progress,
+        input,
+        |range| {
+            range.into_par_iter().find_first(|&i| {
+                attempt(memory, &input(i), target, max_cycles, checkpoint, &progress)
+            })
+        },
+    );
+    progress.finish();
+    (found.map(input), tried)
+}
+
+fn guided(
+    memory: [u8; MEMORY_SIZE],
+    target: usize,
+    options: &Options,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+) -> (Option<Vec<u8>>, usize) {
+    let alphabet = alphabet(options);
+    let cost = |length: usize| length * alphabet.len() + 1;
+    let progress = checkpoint.progress((0..=options.length).map(cost).sum());
+    let mut tried = 0;
+    for length in 0..=options.length {
+        if checkpoint.unexplored(tried + cost(length)).is_empty() {
+            tried += cost(length);
+            continue;
+        }
+        let mut input = vec![alphabet[0]; length];
+        for position in 0..length {
+            let mut prefix = State::new(memory, input.clone());
+            if !until_read(&mut prefix, position, max_cycles, checkpoint) {
+                let skipped = (length - position) * alphabet.len();
+                (0..skipped).for_each(|_| progress.step());
+                tried += skipped;
+                break;
+            }
+            let scores: Vec<(Option<usize>, usize)> = alphabet
+                .par_iter()
+                .map_init(
+                    || prefix.clone(),
+                    |state, &b| {
+                        let mut candidate = input.clone();
+                        candidate[position] = b;
+                        state.reset(&prefix, &candidate);
+                        let (stopp, cycles) = finish(state, max_cycles, checkpoint);
+                        progress.step();
+                        progress.offer(cycles, || describe(&candidate, cycles));
+                        (stopp, cycles)
+                    },
+                )
+                .collect();
+            tried += scores.len();
+            if let Some(i) = scores.iter().position(|s| s.0 == Some(target)) {
+                input[position] = alphabet[i];
+                progress.finish();
+                return (Some(input), tried);
+            }
+            let best = (0..scores.len())
+                .max_by_key(|&i| (scores[i].1, std::cmp::Reverse(i)))
+                .unwrap_or(0);
+            input[position] = alphabet[best];
+        }
+        tried += 1;
+        if attempt(memory, &input, target, max_cycles, checkpoint, &progress) {
+            progress.finish();
+            return (Some(input), tried);
+        }
+        checkpoint.explore(0..tried);
+        checkpoint.save(&progress, &[("seed", options.seed.to_string())]);
+    }
+    progress.finish();
+    (None, tried)
+}
+
+fn symbolic(
+    memory: [u8; MEMORY_SIZE],
+    target: usize,
+    options: &Options,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+) -> (Option<Vec<u8>>, usize) {
+    let progress = checkpoint.progress(options.count);
+    let limits = Limits {
+        length: options.length,
+        steps: options.count,
+        cycles: max_cycles,
+    };
+    let outcome = symex::search(&memory, target, &alphabet(options), &limits, &progress);
+    checkpoint.cover(&outcome.visited);
+    progress.finish();
+    let input = outcome
+        .input
+        .filter(|input| run(memory, input.clone(), max_cycles, checkpoint).0 == Some(target));
+    (input, outcome.paths)
+}
+
+pub fn reach(
+    memory: [u8; MEMORY_SIZE],
+    target: usize,
+    options: &Options,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+) -> Search {
+    let (input, tried) = match options.backend.as_str() {
+        "brute" => brute(memory, target, options, max_cycles, checkpoint),
+        "batch" => lockstep(memory, target, options, max_cycles, checkpoint),
+        "guided" => guided(memory, target, options, max_cycles, checkpoint),
+        "symex" => symbolic(memory, target, options, max_cycles, checkpoint),
+        _ => random(memory, target, options, max_cycles, checkpoint),
+    };
+    checkpoint.finish();
+    Search {
+        found: input.map(|input| Found {
+            path: path(memory, &input, max_cycles),
+            input,
+        }),
+        tried,
+        coverage: checkpoint.coverage(),
+    }
+}
@@ -0,0 +1,124 @@
+use crate::signatures::fnv1a;
+use std::fmt;
+
+const HEADER: &str = "s8replay 1";
+
+#[derive(Default)]
+pub struct Settings {
+    pub costs: Option<String>,
+    pub max_cycles: Option<usize>,
+    pub stubs: Vec<String>,
+}
+
+pub struct Replay {
+    pub version: String,
+    pub program: u64,
+    pub input: Vec<u8>,
+    pub seeds: Vec<u64>,
+    pub settings: Settings,
+    pub cycles: usize,
+    pub output: Vec<u8>,
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seeds: Vec<String> = self.seeds.iter().map(|s| s.to_string()).collect();
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "version {}", self.version)?;
+        writeln!(f, "program {:016x}", self.program)?;
+        writeln!(f, "input {}", hex::encode(&self.input))?;
+        writeln!(f, "seeds {}", seeds.join(","))?;
+        if let Some(costs) = &self.settings.costs {
+            writeln!(f, "costs {}", hex::encode(costs))?;
+        }
+        if let Some(max_cycles) = self.settings.max_cycles {
+            writeln!(f, "max-cycles {}", max_cycles)?;
+        }
+        for stub in &self.settings.stubs {
+            writeln!(f, "stub {}", stub)?;
+        }
+        writeln!(f, "cycles {}", self.cycles)?;
+        writeln!(f, "output {}", hex::encode(&self.output))
+    }
+}
+
+pub fn program_hash(program: &[u8]) -> u64 {
+    fnv1a(program)
+}
+
+pub fn parse(text: &str) -> Result<Replay, String> {
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(String::from("not a replay file"));
+    }
+    let mut replay = Replay {
+        version: String::new(),
+        program: 0,
+        input: Vec::new(),
+        seeds: Vec::new(),
+        settings: Settings::default(),
+        cycles: 0,
+        output: Vec::new(),
+    };
+    for line in lines {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let bad = || format!("could not parse {} in replay file", key);
+        match key {
+            "version" => replay.version = value.to_string(),
+            "program" => replay.program = u64::from_str_radix(value, 16).map_err(|_| bad())?,
+            "input" => replay.input = hex::decode(value).map_err(|_| bad())?,
+            "seeds" => {
+                replay.seeds = value
+                    .split(',')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.parse().map_err(|_| bad()))
+                    .collect::<Result<_, _>>()?
+            }
+            "costs" => {
+                let text = hex::decode(value).map_err(|_| bad())?;
+                replay.settings.costs = Some(String::from_utf8(text).map_err(|_| bad())?);
+            }
+            "max-cycles" => replay.settings.max_cycles = Some(value.parse().map_err(|_| bad())?),
+            "stub" => replay.settings.stubs.push(value.to_string()),
+            "cycles" => replay.cycles = value.parse().map_err(|_| bad())?,
+            "output" => replay.output = hex::decode(value).map_err(|_| bad())?,
+            _ => return Err(format!("unknown key {} in replay file", key)),
+        }
+    }
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_survive_a_round_trip() {
+        let replay = Replay {
+            version: String::from("1.0.0"),
+            program: 0x1234,
+            input: b"k?!".to_vec(),
+            seeds: vec![7],
+            settings: Settings {
+                costs: Some(String::from("LES = 10\n")),
+                max_cycles: Some(500),
+                stubs: vec![String::from("0x240=return:r2=0")],
+            },
+            cycles: 14,
+            output: vec![0x21],
+        };
+        let parsed = parse(&replay.to_string()).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(parsed.settings.costs.as_deref(), Some("LES = 10\n"));
+        assert_eq!(parsed.settings.max_cycles, Some(500));
+        assert_eq!(parsed.settings.stubs, replay.settings.stubs);
+        assert_eq!(parsed.to_string(), replay.to_string());
+    }
+
+    #[test]
+    fn old_files_have_no_settings() {
+        let parsed =
+            parse("s8replay 1\nversion 1\nprogram 00\ninput \nseeds \ncycles 3\noutput \n")
+                .unwrap_or_else(|e| panic!("{}", e));
+        assert!(parsed.settings.costs.is_none() && parsed.settings.stubs.is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+use crate::cfg::{Cfg, EdgeKind};
+use crate::emulator::{State, StepEvent};
+use crate::functions::{find_functions, function_body};
+use crate::parser::{decode_at, memory_image, reachable, Instruction};
+use crate::replay::program_hash;
+use crate::verify::verify;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+const MIN_STRING: usize = 4;
+
+pub fn strings(program: &[u8]) -> Vec<(usize, String)> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    for (i, &b) in program.iter().chain(std::iter::once(&0)).enumerate() {
+        if b.is_ascii_graphic() || b == b' ' {
+            continue;
+        }
+        if i - start >= MIN_STRING {
+            found.push((
+                start,
+                String::from_utf8_lossy(&program[start..i]).into_owned(),
+            ));
+        }
+        start = i + 1;
+    }
+    found
+}
+
+pub fn xrefs(memory: &[u8], code: &BTreeSet<usize>) -> BTreeMap<u16, Vec<usize>> {
+    let mut xrefs: BTreeMap<u16, Vec<usize>> = BTreeMap::new();
+    for &pc in code {
+        match decode_at(memory, pc) {
+            Instruction::Finn(a) => xrefs.entry(a.value()).or_default().push(pc),
+            instruction => {
+                if let Some(target) = instruction.targets() {
+                    xrefs.entry(target).or_default().push(pc);
+                }
+            }
+        }
+    }
+    xrefs
+}
+
+fn mermaid(cfg: &Cfg, out: &mut String) {
+    let _ = writeln!(out, "```mermaid\ngraph TD");
+    for block in cfg.blocks.values() {
+        let _ = writeln!(
+            out,
+            "    b{:x}[\"{:#06x}-{:#06x}\"]",
+            block.start,
+            block.start,
+            block.end - 2
+        );
+        for (to, kind) in &block.successors {
+            let arrow = match kind {
+                EdgeKind::Fallthrough => "-->",
+                EdgeKind::Taken => "-->|taken|",
+                EdgeKind::Call => "-.->|call|",
+            };
+            let _ = writeln!(out, "    b{:x} {} b{:x}", block.start, arrow, to);
+        }
+    }
+    let _ = writeln!(out, "```");
+}
+
+pub fn report(
+    name: &str,
+    program: &[u8],
+    stdin: Option<Vec<u8>>,
+    names: &BTreeMap<usize, String>,
+) -> String {
+    let memory = memory_image(program);
+    let code: BTreeSet<usize> = reachable(&memory)
+        .into_iter()
+        .filter(|pc| pc + 1 < program.len())
+        .collect();
+    let functions = find_functions(&memory);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# {}\n", name);
+    let _ = writeln!(out, "## Program\n");
+    let _ = writeln!(out, "* size: {} bytes", program.len());
+    let _ = writeln!(out, "* hash: `{:016x}`", program_hash(program));
+    let _ = writeln!(out, "* reachable instructions: {}", code.len());
+    let _ = writeln!(out, "* functions: {}", functions.len());
+    let findings = verify(program);
+    if !findings.is_empty() {
+        let _ = writeln!(out, "\n### Verification\n");
+        for finding in &findings {
+            let level = if finding.error { "error" } else { "note" };
+            let _ = writeln!(
+                out,
+                "* `{:#06x}` {}: {}",
+                finding.address, level, finding.message
+            );
+        }
+    }
+
+    let _ = writeln!(out, "\n## Functions\n");
+    let _ = writeln!(out, "| entry | name | instructions |");
+    let _ = writeln!(out, "|---|---|---|");
+    for &entry in &functions {
+        let _ = writeln!(
+            out,
+            "| `{:#06x}` | {} | {} |",
+            entry,
+            names.get(&entry).map(String::as_str).unwrap_or(""),
+            function_body(&memory, entry).len()
+        );
+    }
+
+    let _ = writeln!(out, "\n## Control flow\n");
+    mermaid(&Cfg::build(&memory), &mut out);
+
+    let _ = writeln!(out, "\n## Strings\n");
+    for (address, text) in strings(program) {
+        let _ = writeln!(out, "* `{:#06x}` `{}`", address, text.replace('`', "'"));
+    }
+
+    let _ = writeln!(out, "\n## Cross references\n");
+    for (target, sources) in xrefs(&memory, &code) {
+        let sources: Vec<String> = sources.iter().map(|s| format!("`{:#06x}`", s)).collect();
+        let _ = writeln!(out, "* `{:#06x}` from {}", target, sources.join(", "));
+    }
+
+    if let Some(stdin) = stdin {
+        let mut state = State::new(memory, stdin);
+        let mut executed = BTreeSet::new();
+        for step in state.iter().take(1_000_000) {
+            match step {
+                Ok(StepEvent::Step { pc, .. }) => {
+                    executed.insert(pc);
+                }
+                _ => break,
+            }
+        }
+        let _ = writeln!(out, "\n## Coverage\n");
+        let _ = writeln!(
+            out,
+            "* {} of {} reachable instructions executed ({} cycles)",
+            executed.intersection(&code).count(),
+            code.len(),
+            state.cycles()
+        );
+        if !state.stdout().is_empty() {
+            let _ = writeln!(out, "* output: `{}`", hex::encode(state.stdout()));
+        }
+        for &entry in &functions {
+            let body = function_body(&memory, entry);
+            let _ = writeln!(
+                out,
+                "* function `{:#06x}`: {}/{}",
+                entry,
+                executed.intersection(&body).count(),
+                body.len()
+            );
+        }
+    }
+    out
+}
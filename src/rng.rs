@@ -0,0 +1,45 @@
+use crate::source::parse_number;
+
+const DEFAULT_LENGTH: usize = 256;
+
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    #[cfg(feature = "fuzz")]
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn bytes(&mut self, length: usize) -> Vec<u8> {
+        (0..length).map(|_| self.next_u64() as u8).collect()
+    }
+}
+
+pub fn parse(spec: &str) -> Result<(u64, usize), String> {
+    let mut seed = None;
+    let mut length = DEFAULT_LENGTH;
+    for part in spec.split(':').filter(|p| !p.is_empty()) {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        let number = parse_number(value).ok_or(format!("bad value in {}", part))?;
+        match key {
+            "seed" => seed = Some(u64::from(number)),
+            "len" => length = number as usize,
+            _ => return Err(format!("unknown rng option {}", key)),
+        }
+    }
+    Ok((seed.ok_or("rng needs a seed=N")?, length))
+}
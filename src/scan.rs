@@ -0,0 +1,51 @@
+use crate::emulator::State;
+use crate::parser::MEMORY_SIZE;
+use regex::bytes::Regex;
+use std::collections::HashSet;
+
+pub struct Match {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+pub fn find(pattern: &Regex, haystack: &[u8]) -> Vec<Match> {
+    pattern
+        .find_iter(haystack)
+        .filter(|m| !m.as_bytes().is_empty())
+        .map(|m| Match {
+            offset: m.start(),
+            bytes: m.as_bytes().to_vec(),
+        })
+        .collect()
+}
+
+const WINDOW: usize = 256;
+
+pub struct MemoryScanner {
+    pattern: Regex,
+    reported: HashSet<(usize, Vec<u8>)>,
+}
+
+impl MemoryScanner {
+    pub fn new(pattern: Regex) -> MemoryScanner {
+        MemoryScanner {
+            pattern,
+            reported: HashSet::new(),
+        }
+    }
+
+    pub fn written(&mut self, state: &State, address: usize) -> Vec<Match> {
+        let start = address.saturating_sub(WINDOW);
+        let end = (address + WINDOW).min(MEMORY_SIZE);
+        let window: Vec<u8> = (start..end).map(|a| state.memory_at(a)).collect();
+        find(&self.pattern, &window)
+            .into_iter()
+            .map(|m| Match {
+                offset: start + m.offset,
+                bytes: m.bytes,
+            })
+            .filter(|m| m.offset <= address && address < m.offset + m.bytes.len())
+            .filter(|m| self.reported.insert((m.offset, m.bytes.clone())))
+            .collect()
+    }
+}
@@ -0,0 +1,94 @@
+use crate::assembler::assemble_fragment;
+use crate::parser::{decode_instruction, encode_instruction};
+use crate::rng::Rng;
+use crate::source::parse_number;
+
+const MAX_PROGRAM_LENGTH: usize = 16;
+
+pub struct Options {
+    pub seed: u64,
+    pub count: usize,
+}
+
+pub fn parse(spec: &str, default_seed: u64) -> Result<Options, String> {
+    let mut options = Options {
+        seed: default_seed,
+        count: 10_000,
+    };
+    for part in spec.split(':').filter(|p| !p.is_empty()) {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        let number = parse_number(value).ok_or(format!("bad value in {}", part))?;
+        match key {
+            "seed" => options.seed = u64::from(number),
+            "count" => options.count = number as usize,
+            _ => return Err(format!("unknown selftest option {}", key)),
+        }
+    }
+    Ok(options)
+}
+
+fn listing(words: &[u16]) -> String {
+    words
+        .iter()
+        .map(|&w| format!("{}\n", decode_instruction(w)))
+        .collect()
+}
+
+fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    assemble_fragment(source).map(|f| f.code).map_err(|errors| {
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        messages.join("; ")
+    })
+}
+
+pub fn words(rng: &mut Rng, count: usize) -> Vec<String> {
+    let mut failures = Vec::new();
+    for _ in 0..count {
+        let word = rng.next_u64() as u16;
+        let first = encode_instruction(&decode_instruction(word));
+        let second = encode_instruction(&decode_instruction(first));
+        if first != second {
+            failures.push(format!(
+                "{:#06x} decodes to {} ({:#06x}), which decodes to {} ({:#06x})",
+                word,
+                decode_instruction(word),
+                first,
+                decode_instruction(first),
+                second
+            ));
+        }
+    }
+    failures
+}
+
+pub fn programs(rng: &mut Rng, count: usize) -> Vec<String> {
+    let mut failures = Vec::new();
+    for _ in 0..count {
+        let length = 1 + rng.next_u64() as usize % MAX_PROGRAM_LENGTH;
+        let words: Vec<u16> = (0..length).map(|_| rng.next_u64() as u16).collect();
+        let source = listing(&words);
+        let result = assemble(&source).and_then(|first| {
+            let disassembled: Vec<u16> = first
+                .chunks(2)
+                .map(|c| u16::from_le_bytes([c[0], c.get(1).copied().unwrap_or(0)]))
+                .collect();
+            let relisted = listing(&disassembled);
+            let second = assemble(&relisted)?;
+            if first == second {
+                Ok(())
+            } else {
+                Err(format!(
+                    "reassembling the disassembly changed the bytes\n{}\nassembled to {}\n{}\nassembled to {}",
+                    source.trim_end(),
+                    hex::encode(&first),
+                    relisted.trim_end(),
+                    hex::encode(&second)
+                ))
+            }
+        });
+        if let Err(e) = result {
+            failures.push(e);
+        }
+    }
+    failures
+}
@@ -0,0 +1,83 @@
+use crate::cfg::{Cfg, Loop};
+use crate::parser::{decode_at, Instruction};
+use std::collections::BTreeSet;
+
+pub struct SerialCheck {
+    pub header: usize,
+    pub reads_input: bool,
+    pub tables: BTreeSet<u16>,
+    pub transforms: Vec<usize>,
+    pub compare: usize,
+    pub branch: usize,
+    pub target: usize,
+    pub exits_on_branch: bool,
+}
+
+fn instructions(cfg: &Cfg, memory: &[u8], blocks: &BTreeSet<usize>) -> Vec<(usize, Instruction)> {
+    blocks
+        .iter()
+        .flat_map(|b| (*b..cfg.blocks[b].end).step_by(2))
+        .map(|pc| (pc, decode_at(memory, pc)))
+        .collect()
+}
+
+fn detect(cfg: &Cfg, memory: &[u8], l: &Loop) -> Option<SerialCheck> {
+    let body = instructions(cfg, memory, &l.body);
+    let reads_input = body.iter().any(|(_, i)| matches!(i, Instruction::Les(_)));
+    let loads = body.iter().any(|(_, i)| matches!(i, Instruction::Last(_)));
+    if !reads_input && !loads {
+        return None;
+    }
+
+    let (branch, target) = body.iter().find_map(|(pc, i)| match i {
+        Instruction::BHopp(a) => Some((*pc, usize::from(*a))),
+        _ => None,
+    })?;
+    let compare = body
+        .iter()
+        .rev()
+        .filter(|(pc, _)| *pc < branch)
+        .find(|(_, i)| i.modifies_flag())?
+        .0;
+
+    let predecessors = cfg.predecessors();
+    let entry: BTreeSet<usize> = predecessors[&l.header]
+        .iter()
+        .filter(|p| !l.body.contains(p))
+        .copied()
+        .collect();
+    let tables = body
+        .iter()
+        .chain(instructions(cfg, memory, &entry).iter())
+        .filter_map(|(_, i)| match i {
+            Instruction::Finn(a) => Some(a.value()),
+            _ => None,
+        })
+        .collect();
+    let transforms = body
+        .iter()
+        .filter(|(pc, i)| {
+            *pc < compare && matches!(i, Instruction::ALE(_, ra, _) if ra.value() > 1)
+        })
+        .map(|(pc, _)| *pc)
+        .collect();
+
+    Some(SerialCheck {
+        header: l.header,
+        reads_input,
+        tables,
+        transforms,
+        compare,
+        branch,
+        target,
+        exits_on_branch: !l.body.contains(&target),
+    })
+}
+
+pub fn find_serial_checks(memory: &[u8]) -> Vec<SerialCheck> {
+    let cfg = Cfg::build(memory);
+    cfg.loops(memory)
+        .iter()
+        .filter_map(|l| detect(&cfg, memory, l))
+        .collect()
+}
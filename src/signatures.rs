@@ -0,0 +1,76 @@
+use crate::functions::{find_functions, function_body};
+use crate::parser::{decode_at, Instruction, OperandKind};
+use std::collections::{BTreeMap, HashMap};
+
+pub struct Signature {
+    pub entry: usize,
+    pub hash: u64,
+    pub name: Option<String>,
+}
+
+fn normalize(instruction: &Instruction, entry: usize) -> String {
+    let relative = |a: u16| usize::from(a) as isize - entry as isize;
+    match instruction {
+        Instruction::Hopp(a) => format!("HOPP {}", relative(a.value())),
+        Instruction::BHopp(a) => format!("BHOPP {}", relative(a.value())),
+        instruction => instruction.operand_kinds().iter().fold(
+            instruction.mnemonic().to_string(),
+            |shape, kind| {
+                shape
+                    + match kind {
+                        OperandKind::Register => " r",
+                        OperandKind::Immediate => " #",
+                        OperandKind::Address => " @",
+                    }
+            },
+        ),
+    }
+}
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn hash_function(memory: &[u8], entry: usize) -> u64 {
+    let shape = function_body(memory, entry)
+        .into_iter()
+        .map(|pc| normalize(&decode_at(memory, pc), entry))
+        .collect::<Vec<String>>()
+        .join(";");
+    fnv1a(shape.as_bytes())
+}
+
+pub fn parse_database(text: &str) -> HashMap<u64, String> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let (hash, name) = line.split_once(char::is_whitespace)?;
+            let hash = u64::from_str_radix(hash, 16).ok()?;
+            Some((hash, name.trim().to_string()))
+        })
+        .collect()
+}
+
+pub fn match_functions(memory: &[u8], database: &HashMap<u64, String>) -> Vec<Signature> {
+    find_functions(memory)
+        .into_iter()
+        .map(|entry| {
+            let hash = hash_function(memory, entry);
+            Signature {
+                entry,
+                hash,
+                name: database.get(&hash).cloned(),
+            }
+        })
+        .collect()
+}
+
+pub fn names(signatures: &[Signature]) -> BTreeMap<usize, String> {
+    signatures
+        .iter()
+        .filter_map(|s| s.name.clone().map(|name| (s.entry, name)))
+        .collect()
+}
@@ -0,0 +1,88 @@
+use crate::emulator::State;
+use std::fmt::Write;
+
+pub struct Node {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub state: State,
+}
+
+#[derive(Default)]
+pub struct SnapshotTree {
+    pub nodes: Vec<Node>,
+    pub current: Option<usize>,
+}
+
+impl SnapshotTree {
+    fn find(&self, name: &str) -> Result<usize, String> {
+        self.nodes
+            .iter()
+            .position(|n| n.name == name)
+            .ok_or_else(|| format!("no snapshot named {}", name))
+    }
+
+    pub fn save(&mut self, name: &str, state: &State) -> Result<(), String> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(String::from("expected snap NAME"));
+        }
+        if self.find(name).is_ok() {
+            return Err(format!("a snapshot named {} exists", name));
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: self.current,
+            state: state.clone(),
+        });
+        self.current = Some(self.nodes.len() - 1);
+        Ok(())
+    }
+
+    pub fn goto(&mut self, name: &str) -> Result<&State, String> {
+        let i = self.find(name)?;
+        self.current = Some(i);
+        Ok(&self.nodes[i].state)
+    }
+
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<(), String> {
+        if self.find(new).is_ok() {
+            return Err(format!("a snapshot named {} exists", new));
+        }
+        let i = self.find(old)?;
+        self.nodes[i].name = new.to_string();
+        Ok(())
+    }
+
+    fn draw(&self, parent: Option<usize>, prefix: &str, text: &mut String) {
+        let children: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| self.nodes[i].parent == parent)
+            .collect();
+        for (n, &i) in children.iter().enumerate() {
+            let last = n + 1 == children.len();
+            let (branch, indent) = match (parent, last) {
+                (None, _) => ("", ""),
+                (Some(_), false) => ("├─ ", "│  "),
+                (Some(_), true) => ("└─ ", "   "),
+            };
+            let node = &self.nodes[i];
+            let _ = writeln!(
+                text,
+                "{}{}{} (pc {:#06x}, cycle {}, {} input bytes read, {} output bytes){}",
+                prefix,
+                branch,
+                node.name,
+                node.state.pc(),
+                node.state.cycles(),
+                node.state.input_read(),
+                node.state.stdout().len(),
+                if self.current == Some(i) { " *" } else { "" }
+            );
+            self.draw(Some(i), &format!("{}{}", prefix, indent), text);
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.draw(None, "", &mut text);
+        text
+    }
+}
@@ -0,0 +1,120 @@
+pub const MNEMONICS: [(&str, &str); 26] = [
+    (
+        "SETT",
+        "SETT rA, rB|imm: copy a register or an 8 bit immediate into rA",
+    ),
+    (
+        "FINN",
+        "FINN addr: point r1:r0 at addr (r0 low byte, r1 high nibble)",
+    ),
+    ("LAST", "LAST rA: load the byte at r1:r0 into rA"),
+    ("LAGR", "LAGR rA: store rA at r1:r0"),
+    ("OG", "OG rA, rB: rA = rA & rB"),
+    ("ELLER", "ELLER rA, rB: rA = rA | rB"),
+    ("XELLER", "XELLER rA, rB: rA = rA ^ rB"),
+    ("VSKIFT", "VSKIFT rA, rB: rA = rA << rB"),
+    ("HSKIFT", "HSKIFT rA, rB: rA = rA >> rB"),
+    ("PLUSS", "PLUSS rA, rB: rA = rA + rB (mod 256)"),
+    ("MINUS", "MINUS rA, rB: rA = rA - rB (mod 256)"),
+    ("LES", "LES rA: read the next input byte into rA"),
+    ("SKRIV", "SKRIV rA: write rA to the output"),
+    ("LIK", "LIK rA, rB: flagg = rA == rB"),
+    ("ULIK", "ULIK rA, rB: flagg = rA != rB"),
+    ("ME", "ME rA, rB: flagg = rA < rB"),
+    ("MEL", "MEL rA, rB: flagg = rA <= rB"),
+    ("SE", "SE rA, rB: flagg = rA > rB"),
+    ("SEL", "SEL rA, rB: flagg = rA >= rB"),
+    ("HOPP", "HOPP addr: jump to addr"),
+    ("BHOPP", "BHOPP addr: jump to addr if flagg is set"),
+    ("TUR", "TUR addr: call the subroutine at addr"),
+    ("RETUR", "RETUR: return from a subroutine"),
+    ("STOPP", "STOPP: halt the machine"),
+    ("NOPE", "NOPE: do nothing"),
+    (".DATA", ".DATA byte, ...: raw bytes"),
+];
+
+pub fn mnemonic_doc(mnemonic: &str) -> Option<&'static str> {
+    let mnemonic = mnemonic.to_uppercase();
+    MNEMONICS
+        .iter()
+        .find(|(name, _)| *name == mnemonic)
+        .map(|(_, doc)| *doc)
+}
+
+pub struct SourceLine {
+    pub label: Option<String>,
+    pub mnemonic: Option<String>,
+    pub operands: Vec<String>,
+    pub comment: Option<String>,
+}
+
+impl SourceLine {
+    pub fn is_blank(&self) -> bool {
+        self.label.is_none() && self.mnemonic.is_none() && self.comment.is_none()
+    }
+}
+
+pub fn parse_line(line: &str) -> SourceLine {
+    let (code, comment) = match line.find(';') {
+        Some(i) => (&line[..i], Some(line[i + 1..].trim().to_string())),
+        None => (line, None),
+    };
+
+    let mut code = code.trim();
+    let mut label = None;
+    if let Some(i) = code.find(':') {
+        let name = code[..i].trim();
+        if !name.is_empty() && !name.contains(char::is_whitespace) {
+            label = Some(name.to_string());
+            code = code[i + 1..].trim();
+        }
+    }
+
+    let (mnemonic, operands) = if code.is_empty() {
+        (None, Vec::new())
+    } else {
+        let (m, rest) = match code.find(char::is_whitespace) {
+            Some(i) => (&code[..i], code[i..].trim()),
+            None => (code, ""),
+        };
+        let operands = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',').map(|o| o.trim().to_string()).collect()
+        };
+        (Some(m.to_uppercase()), operands)
+    };
+
+    SourceLine {
+        label,
+        mnemonic,
+        operands,
+        comment,
+    }
+}
+
+pub fn parse(source: &str) -> Vec<SourceLine> {
+    source.lines().map(parse_line).collect()
+}
+
+pub fn parse_number(token: &str) -> Option<u32> {
+    let token = token.trim();
+    if let Some(hex) = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = token.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok()
+    } else {
+        token.parse().ok()
+    }
+}
+
+pub fn parse_register(token: &str) -> Option<usize> {
+    let token = token.trim();
+    let number = token
+        .strip_prefix('r')
+        .or_else(|| token.strip_prefix('R'))?;
+    number.parse().ok()
+}
@@ -0,0 +1,200 @@
+use crate::checkpoint::{visit, Checkpoint, Saved};
+use crate::emulator::{HaltReason, State};
+use crate::error::S8Error;
+use crate::parser::MEMORY_SIZE;
+use crate::rng::Rng;
+use crate::source::parse_number;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+pub struct Options {
+    pub seed: u64,
+    pub count: usize,
+    pub length: usize,
+}
+
+pub fn parse(spec: &str, default_seed: u64) -> Result<Options, String> {
+    let mut options = Options {
+        seed: default_seed,
+        count: 1000,
+        length: 64,
+    };
+    for part in spec.split(':').filter(|p| !p.is_empty()) {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        let number = parse_number(value).ok_or(format!("bad value in {}", part))?;
+        match key {
+            "seed" => options.seed = u64::from(number),
+            "count" => options.count = number as usize,
+            "len" => options.length = number as usize,
+            _ => return Err(format!("unknown stress option {}", key)),
+        }
+    }
+    Ok(options)
+}
+
+const KINDS: [&str; 8] = [
+    "STOPP",
+    "pc outside memory",
+    "input exhausted",
+    "empty return stack",
+    "not a program",
+    "cycle limit",
+    "interrupted",
+    "running",
+];
+
+fn kind(reason: Option<HaltReason>) -> &'static str {
+    match reason {
+        Some(HaltReason::Stopp) => "STOPP",
+        Some(HaltReason::PcOutOfRange(_)) | Some(HaltReason::Fault(S8Error::PcOutOfRange(_))) => {
+            "pc outside memory"
+        }
+        Some(HaltReason::Fault(S8Error::InputExhausted(_))) => "input exhausted",
+        Some(HaltReason::Fault(S8Error::EmptyReturnStack(_))) => "empty return stack",
+        Some(HaltReason::Fault(S8Error::NotSlede8)) => "not a program",
+        Some(HaltReason::TickLimit(_)) => "cycle limit",
+        Some(HaltReason::UserInterrupt) => "interrupted",
+        None => "running",
+    }
+}
+
+pub struct Output {
+    pub stdout: Vec<u8>,
+    pub count: usize,
+    pub first: usize,
+}
+
+pub struct Outcome {
+    pub halts: BTreeMap<&'static str, (usize, usize)>,
+    pub coverage: Vec<bool>,
+    pub outputs: Vec<Output>,
+}
+
+pub fn input(options: &Options, i: usize) -> Vec<u8> {
+    let mut rng = Rng::new(options.seed.wrapping_add(i as u64));
+    let length = rng.next_u64() as usize % (options.length + 1);
+    rng.bytes(length)
+}
+
+fn restore(saved: &Saved) -> Option<Outcome> {
+    let mut outcome = Outcome {
+        halts: BTreeMap::new(),
+        coverage: Vec::new(),
+        outputs: Vec::new(),
+    };
+    for halt in saved.values("halt") {
+        let mut fields = halt.splitn(3, ' ');
+        let count = fields.next()?.parse().ok()?;
+        let first = fields.next()?.parse().ok()?;
+        let name = fields.next()?;
+        let reason = KINDS.iter().find(|&&k| k == name)?;
+        outcome.halts.insert(*reason, (count, first));
+    }
+    for output in saved.values("output") {
+        let mut fields = output.splitn(3, ' ');
+        outcome.outputs.push(Output {
+            count: fields.next()?.parse().ok()?,
+            first: fields.next()?.parse().ok()?,
+            stdout: hex::decode(fields.next().unwrap_or_default()).ok()?,
+        });
+    }
+    Some(outcome)
+}
+
+fn values(options: &Options, outcome: &Outcome) -> Vec<(&'static str, String)> {
+    let mut values = vec![("seed", options.seed.to_string())];
+    for (reason, (count, first)) in &outcome.halts {
+        values.push(("halt", format!("{} {} {}", count, first, reason)));
+    }
+    for output in &outcome.outputs {
+        values.push((
+            "output",
+            format!(
+                "{} {} {}",
+                output.count,
+                output.first,
+                hex::encode(&output.stdout)
+            ),
+        ));
+    }
+    values
+}
+
+pub fn stress(
+    memory: [u8; MEMORY_SIZE],
+    options: &Options,
+    max_cycles: usize,
+    checkpoint: &Checkpoint,
+) -> Outcome {
+    let mut outcome = restore(&checkpoint.saved).unwrap_or_else(|| {
+        eprintln!("Could not read the stress results in the checkpoint, starting over");
+        restore(&Saved::default()).expect("an empty checkpoint restores")
+    });
+    let progress = checkpoint.progress(options.count);
+    let mut outputs: HashMap<Vec<u8>, usize> = outcome
+        .outputs
+        .iter()
+        .enumerate()
+        .map(|(index, output)| (output.stdout.clone(), index))
+        .collect();
+    for gap in checkpoint.unexplored(options.count) {
+        let mut start = gap.start;
+        while start < gap.end {
+            let end = start.saturating_add(checkpoint.every()).min(gap.end);
+            let runs: Vec<(&'static str, Vec<u8>)> = (start..end)
+                .into_par_iter()
+                .map(|i| {
+                    let mut state = State::new(memory, input(options, i));
+                    let mut visited = [0; MEMORY_SIZE / 64];
+                    while state.halted().is_none() && state.cycles() < max_cycles {
+                        visit(&mut visited, state.pc());
+                        if !matches!(state.iter().next(), Some(Ok(_))) {
+                            break;
+                        }
+                    }
+                    if state.halted().is_none() {
+                        state.halt(HaltReason::TickLimit(max_cycles));
+                    }
+                    checkpoint.cover(&visited);
+                    let covered = visited.iter().map(|w| w.count_ones() as usize).sum();
+                    progress.step();
+                    progress.offer(covered, || {
+                        format!("input #{} covers {} instructions", i, covered)
+                    });
+                    (kind(state.halted()), state.stdout().to_vec())
+                })
+                .collect();
+
+            for (i, (reason, stdout)) in (start..end).zip(runs) {
+                let halt = outcome.halts.entry(reason).or_insert((0, i));
+                halt.0 += 1;
+                halt.1 = halt.1.min(i);
+                match outputs.get(&stdout) {
+                    Some(&index) => {
+                        let output = &mut outcome.outputs[index];
+                        output.count += 1;
+                        output.first = output.first.min(i);
+                    }
+                    None => {
+                        outputs.insert(stdout.clone(), outcome.outputs.len());
+                        outcome.outputs.push(Output {
+                            stdout,
+                            count: 1,
+                            first: i,
+                        });
+                    }
+                }
+            }
+            checkpoint.explore(start..end);
+            checkpoint.save(&progress, &values(options, &outcome));
+            start = end;
+        }
+    }
+    outcome.coverage = checkpoint.coverage();
+    progress.finish();
+    checkpoint.finish();
+    outcome
+        .outputs
+        .sort_by(|a, b| b.count.cmp(&a.count).then(a.first.cmp(&b.first)));
+    outcome
+}
@@ -0,0 +1,37 @@
+use crate::call;
+use crate::source::parse_number;
+
+pub enum Effect {
+    Return(Vec<(usize, u8)>),
+    Script(String),
+}
+
+pub struct Stub {
+    pub address: usize,
+    pub effect: Effect,
+}
+
+pub fn parse(spec: &str) -> Result<Stub, String> {
+    let (address, effect) = spec.split_once('=').ok_or_else(|| {
+        format!(
+            "expected ADDRESS=return[:rN=V,...] or ADDRESS=script:COMMANDS, got {}",
+            spec
+        )
+    })?;
+    let address = parse_number(address)
+        .filter(|&a| a < 0x1000)
+        .ok_or_else(|| format!("{} is not a code address", address))? as usize;
+    let effect = if effect == "return" {
+        Effect::Return(Vec::new())
+    } else if let Some(registers) = effect.strip_prefix("return:") {
+        Effect::Return(call::parse_registers(registers)?)
+    } else if let Some(commands) = effect.strip_prefix("script:") {
+        Effect::Script(commands.to_string())
+    } else {
+        return Err(format!(
+            "unknown stub {}, expected return or script",
+            effect
+        ));
+    };
+    Ok(Stub { address, effect })
+}
@@ -0,0 +1,500 @@
+use crate::checkpoint::{visit, Visited};
+use crate::parser::{decode_instruction, ALEOp, CMPOp, Instruction, MEMORY_SIZE};
+use crate::progress::Progress;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+const SOLVER_BUDGET: usize = 1 << 20;
+const MAX_NODES: usize = 1 << 12;
+
+#[derive(Clone)]
+enum Value {
+    Byte(u8),
+    Input(usize),
+    Ale(ALEOp, Rc<Value>, Rc<Value>, usize),
+}
+
+fn ale(op: ALEOp, a: u8, b: u8) -> u8 {
+    match op {
+        ALEOp::Pluss => a.wrapping_add(b),
+        ALEOp::Minus => a.wrapping_sub(b),
+        ALEOp::Eller => a | b,
+        ALEOp::XEller => a ^ b,
+        ALEOp::Og => a & b,
+        ALEOp::Vskift => a.checked_shl(u32::from(b)).unwrap_or(0),
+        ALEOp::Hskift => a.checked_shr(u32::from(b)).unwrap_or(0),
+    }
+}
+
+fn holds(op: CMPOp, a: u8, b: u8) -> bool {
+    match op {
+        CMPOp::Lik => a == b,
+        CMPOp::Me => a < b,
+        CMPOp::Mel => a <= b,
+        CMPOp::Se => a > b,
+        CMPOp::Sel => a >= b,
+        CMPOp::Ulik => a != b,
+    }
+}
+
+impl Value {
+    fn byte(&self) -> Option<u8> {
+        match self {
+            Value::Byte(byte) => Some(*byte),
+            _ => None,
+        }
+    }
+
+    fn nodes(&self) -> usize {
+        match self {
+            Value::Ale(.., nodes) => *nodes,
+            _ => 1,
+        }
+    }
+
+    fn last_input(&self) -> Option<usize> {
+        match self {
+            Value::Byte(_) => None,
+            Value::Input(i) => Some(*i),
+            Value::Ale(_, a, b, _) => a.last_input().max(b.last_input()),
+        }
+    }
+
+    fn eval(&self, input: &[u8]) -> u8 {
+        match self {
+            Value::Byte(byte) => *byte,
+            Value::Input(i) => input[*i],
+            Value::Ale(op, a, b, _) => ale(*op, a.eval(input), b.eval(input)),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Constraint {
+    op: CMPOp,
+    a: Value,
+    b: Value,
+    holds: bool,
+}
+
+impl Constraint {
+    fn check(&self, input: &[u8]) -> bool {
+        holds(self.op, self.a.eval(input), self.b.eval(input)) == self.holds
+    }
+}
+
+// Assigns the input bytes in order and checks each constraint as soon as the last byte it reads
+// is set, trying the previous model's byte first.
+fn solve(
+    constraints: &[Constraint],
+    count: usize,
+    alphabet: &[u8],
+    hint: &[u8],
+) -> Option<Vec<u8>> {
+    let mut by_last: Vec<Vec<&Constraint>> = vec![Vec::new(); count];
+    for constraint in constraints {
+        match constraint.a.last_input().max(constraint.b.last_input()) {
+            Some(last) => by_last[last].push(constraint),
+            None if !constraint.check(&[]) => return None,
+            None => (),
+        }
+    }
+    let order: Vec<Vec<u8>> = hint
+        .iter()
+        .map(|&h| {
+            std::iter::once(h)
+                .chain(alphabet.iter().copied().filter(|&b| b != h))
+                .collect()
+        })
+        .collect();
+    let mut input = vec![0; count];
+    let mut choice = vec![0; count];
+    let mut budget = SOLVER_BUDGET;
+    let mut i = 0;
+    while i < count {
+        if choice[i] == order[i].len() {
+            choice[i] = 0;
+            i = i.checked_sub(1)?;
+            choice[i] += 1;
+            continue;
+        }
+        input[i] = order[i][choice[i]];
+        budget = budget.checked_sub(by_last[i].len().max(1))?;
+        if by_last[i].iter().all(|c| c.check(&input)) {
+            i += 1;
+        } else {
+            choice[i] += 1;
+        }
+    }
+    Some(input)
+}
+
+#[derive(Clone)]
+enum Flagg {
+    Known(bool),
+    Compare(CMPOp, Value, Value),
+}
+
+#[derive(Clone)]
+struct Path {
+    pc: usize,
+    cycles: usize,
+    registers: [Value; 16],
+    flagg: Flagg,
+    memory: BTreeMap<usize, Value>,
+    stack: Vec<usize>,
+    constraints: Vec<Constraint>,
+    model: Vec<u8>,
+}
+
+enum End {
+    Reached,
+    Halted,
+}
+
+pub struct Limits {
+    pub length: usize,
+    pub steps: usize,
+    pub cycles: usize,
+}
+
+pub struct Outcome {
+    pub input: Option<Vec<u8>>,
+    pub paths: usize,
+    pub visited: Visited,
+}
+
+impl Path {
+    fn concrete(&mut self, value: &Value) -> u8 {
+        let byte = value.eval(&self.model);
+        if value.byte().is_none() {
+            self.constraints.push(Constraint {
+                op: CMPOp::Lik,
+                a: value.clone(),
+                b: Value::Byte(byte),
+                holds: true,
+            });
+        }
+        byte
+    }
+
+    fn load(&self, memory: &[u8; MEMORY_SIZE], address: usize) -> Value {
+        self.memory
+            .get(&address)
+            .cloned()
+            .unwrap_or(Value::Byte(memory[address]))
+    }
+
+    // Fixes `value` to its byte in the model and forks a path that starts over at `pc` with
+    // every other byte it can take.
+    fn branch(&mut self, value: &Value, pc: usize, alphabet: &[u8], forks: &mut Vec<Path>) -> u8 {
+        let byte = value.eval(&self.model);
+        if value.byte().is_some() {
+            return byte;
+        }
+        let mut other = self.clone();
+        other.pc = pc;
+        other.cycles -= 1;
+        other.constraints.push(Constraint {
+            op: CMPOp::Ulik,
+            a: value.clone(),
+            b: Value::Byte(byte),
+            holds: true,
+        });
+        if let Some(model) = solve(&other.constraints, other.model.len(), alphabet, &self.model) {
+            other.model = model;
+            forks.push(other);
+        }
+        self.concrete(value)
+    }
+
+    fn pointer(&mut self, pc: usize, alphabet: &[u8], forks: &mut Vec<Path>) -> usize {
+        let (lo, hi) = (self.registers[0].clone(), self.registers[1].clone());
+        let lo = self.branch(&lo, pc, alphabet, forks);
+        let hi = self.branch(&hi, pc, alphabet, forks);
+        (usize::from(hi) << 8 | usize::from(lo)) & 0xfff
+    }
+
+    fn combine(&mut self, op: ALEOp, a: &Value, b: &Value) -> Value {
+        if let (Some(a), Some(b)) = (a.byte(), b.byte()) {
+            return Value::Byte(ale(op, a, b));
+        }
+        let nodes = 1 + a.nodes() + b.nodes();
+        let value = Value::Ale(op, Rc::new(a.clone()), Rc::new(b.clone()), nodes);
+        if nodes > MAX_NODES {
+            Value::Byte(self.concrete(&value))
+        } else {
+            value
+        }
+    }
+
+    fn step(
+        &mut self,
+        memory: &[u8; MEMORY_SIZE],
+        target: usize,
+        alphabet: &[u8],
+        limits: &Limits,
+        forks: &mut Vec<Path>,
+    ) -> Result<(), End> {
+        let pc = self.pc;
+        if self.cycles >= limits.cycles || pc + 1 >= MEMORY_SIZE {
+            return Err(End::Halted);
+        }
+        let (lo, hi) = (self.load(memory, pc), self.load(memory, pc + 1));
+        let word = u16::from_le_bytes([self.concrete(&lo), self.concrete(&hi)]);
+        self.pc += 2;
+        self.cycles += 1;
+        match decode_instruction(word) {
+            Instruction::Sett(ra, rb) => {
+                self.registers[usize::from(ra)] = self.registers[usize::from(rb)].clone()
+            }
+            Instruction::SettImmediate(ra, value) => {
+                self.registers[usize::from(ra)] = Value::Byte(value.value() as u8)
+            }
+            Instruction::ALE(op, ra, rb) => {
+                let (a, b) = (
+                    self.registers[usize::from(ra)].clone(),
+                    self.registers[usize::from(rb)].clone(),
+                );
+                self.registers[usize::from(ra)] = self.combine(op, &a, &b);
+            }
+            Instruction::Sammenligne(op, ra, rb) => {
+                let (a, b) = (
+                    &self.registers[usize::from(ra)],
+                    &self.registers[usize::from(rb)],
+                );
+                self.flagg = match (a.byte(), b.byte()) {
+                    (Some(a), Some(b)) => Flagg::Known(holds(op, a, b)),
+                    _ => Flagg::Compare(op, a.clone(), b.clone()),
+                };
+            }
+            Instruction::Stopp if pc == target => return Err(End::Reached),
+            Instruction::Stopp => return Err(End::Halted),
+            Instruction::Hopp(address) => self.pc = usize::from(address),
+            Instruction::BHopp(address) => {
+                let (op, a, b) = match &self.flagg {
+                    Flagg::Known(true) => {
+                        self.pc = usize::from(address);
+                        return Ok(());
+                    }
+                    Flagg::Known(false) => return Ok(()),
+                    Flagg::Compare(op, a, b) => (*op, a.clone(), b.clone()),
+                };
+                let taken = holds(op, a.eval(&self.model), b.eval(&self.model));
+                let mut other = self.clone();
+                let constraint = Constraint {
+                    op,
+                    a,
+                    b,
+                    holds: taken,
+                };
+                self.constraints.push(constraint.clone());
+                self.flagg = Flagg::Known(taken);
+                if taken {
+                    self.pc = usize::from(address);
+                } else {
+                    other.pc = usize::from(address);
+                }
+                other.constraints.push(Constraint {
+                    holds: !taken,
+                    ..constraint
+                });
+                other.flagg = Flagg::Known(!taken);
+                if let Some(model) =
+                    solve(&other.constraints, other.model.len(), alphabet, &self.model)
+                {
+                    other.model = model;
+                    forks.push(other);
+                }
+            }
+            Instruction::Les(r) => {
+                if self.model.len() >= limits.length {
+                    return Err(End::Halted);
+                }
+                self.registers[usize::from(r)] = Value::Input(self.model.len());
+                self.model.push(alphabet[0]);
+            }
+            Instruction::Tur(address) => {
+                self.stack.push(self.pc);
+                self.pc = usize::from(address);
+            }
+            Instruction::Retur => match self.stack.pop() {
+                Some(pc) => self.pc = pc,
+                None => return Err(End::Halted),
+            },
+            Instruction::Finn(address) => {
+                self.registers[0] = Value::Byte(address.value() as u8);
+                self.registers[1] = Value::Byte((address.value() >> 8) as u8);
+            }
+            Instruction::Last(r) => {
+                let address = self.pointer(pc, alphabet, forks);
+                self.registers[usize::from(r)] = self.load(memory, address);
+            }
+            Instruction::Lagr(r) => {
+                let address = self.pointer(pc, alphabet, forks);
+                let value = self.registers[usize::from(r)].clone();
+                self.memory.insert(address, value);
+            }
+            Instruction::Skriv(_) | Instruction::Nope | Instruction::Data(_) => (),
+        }
+        Ok(())
+    }
+}
+
+/// Explores the paths through the program with the input bytes as unknowns, forking at every
+/// BHOPP whose flag depends on them, until a path halts at the STOPP at `target`.
+pub fn search(
+    memory: &[u8; MEMORY_SIZE],
+    target: usize,
+    alphabet: &[u8],
+    limits: &Limits,
+    progress: &Progress,
+) -> Outcome {
+    let mut visited = [0; MEMORY_SIZE / 64];
+    let mut pending = vec![Path {
+        pc: 0,
+        cycles: 0,
+        registers: std::array::from_fn(|_| Value::Byte(0)),
+        flagg: Flagg::Known(false),
+        memory: BTreeMap::new(),
+        stack: Vec::new(),
+        constraints: Vec::new(),
+        model: Vec::new(),
+    }];
+    let mut paths = 0;
+    let mut steps = 0;
+    while let Some(mut path) = pending.pop() {
+        paths += 1;
+        let end = loop {
+            if steps >= limits.steps {
+                break None;
+            }
+            steps += 1;
+            progress.step();
+            visit(&mut visited, path.pc);
+            if let Err(end) = path.step(memory, target, alphabet, limits, &mut pending) {
+                break Some(end);
+            }
+        };
+        progress.offer(path.cycles, || hex::encode(&path.model));
+        match end {
+            Some(End::Reached) => {
+                return Outcome {
+                    input: Some(path.model),
+                    paths,
+                    visited,
+                }
+            }
+            Some(End::Halted) => (),
+            None => break,
+        }
+    }
+    Outcome {
+        input: None,
+        paths,
+        visited,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble_fragment;
+    use crate::linker::{link, Module};
+    use crate::parser::memory_image;
+
+    fn memory(source: &str) -> [u8; MEMORY_SIZE] {
+        let fragment = assemble_fragment(source).unwrap_or_else(|_| panic!("bad test source"));
+        let program = link(&[Module {
+            name: String::from("test"),
+            fragment,
+        }])
+        .unwrap_or_else(|e| panic!("{}", e.join("\n")));
+        memory_image(&program)
+    }
+
+    fn solve_for(source: &str, target: usize, length: usize) -> Option<Vec<u8>> {
+        let limits = Limits {
+            length,
+            steps: 100_000,
+            cycles: 1000,
+        };
+        let alphabet: Vec<u8> = (0x20..0x7f).collect();
+        search(
+            &memory(source),
+            target,
+            &alphabet,
+            &limits,
+            &Progress::new(0, 0),
+        )
+        .input
+    }
+
+    #[test]
+    fn transformed_bytes() {
+        let source = "
+            LES r2
+            SETT r3, 0x13
+            XELLER r2, r3
+            SETT r4, 0x72
+            ULIK r2, r4
+            BHOPP fail
+            LES r5
+            LES r6
+            PLUSS r5, r6
+            SETT r4, 0xc3
+            ULIK r5, r4
+            BHOPP fail
+            SETT r4, 2
+            HSKIFT r6, r4
+            SETT r4, 0x18
+            LIK r6, r4
+            BHOPP win
+        fail:
+            STOPP
+        win:
+            STOPP
+        ";
+        let input = solve_for(source, 0x24, 8).unwrap_or_default();
+        assert_eq!(input.len(), 3);
+        assert_eq!(input[0] ^ 0x13, 0x72);
+        assert_eq!(input[1].wrapping_add(input[2]), 0xc3);
+        assert_eq!(input[2] >> 2, 0x18);
+    }
+
+    #[test]
+    fn table_lookup_through_a_pointer() {
+        let source = "
+            LES r2
+            SETT r3, 3
+            OG r2, r3
+            FINN table
+            PLUSS r0, r2
+            LAST r3
+            SETT r4, 0x99
+            LIK r3, r4
+            BHOPP win
+            STOPP
+        win:
+            STOPP
+        table:
+            .DATA 1, 2, 0x99, 4
+        ";
+        assert_eq!(solve_for(source, 0x14, 4), Some(b"\"".to_vec()));
+        assert_eq!(solve_for(source, 0x12, 4), Some(b" ".to_vec()));
+    }
+
+    #[test]
+    fn unreachable_stopp() {
+        let source = "
+            LES r2
+            SETT r3, 0x80
+            ME r2, r3
+            BHOPP done
+            STOPP
+        done:
+            STOPP
+        ";
+        assert_eq!(solve_for(source, 0x08, 4), None);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::accounting::track;
+use crate::emulator::{HaltReason, State};
+use crate::parser::Instruction;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+const MAX_REGIONS: usize = 8;
+
+pub struct Row {
+    pub name: String,
+    pub columns: Vec<usize>,
+    pub total: String,
+    pub busy: bool,
+}
+
+pub struct Timeline {
+    pub cycles: usize,
+    pub per_column: usize,
+    pub rows: Vec<Row>,
+    pub halt: Option<HaltReason>,
+}
+
+fn bucket(events: &[(usize, usize)], columns: usize, per_column: usize) -> Vec<usize> {
+    let mut counts = vec![0; columns];
+    for &(cycle, count) in events {
+        counts[(cycle / per_column).min(columns - 1)] += count;
+    }
+    counts
+}
+
+fn regions(writes: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let addresses: BTreeSet<usize> = writes.iter().map(|&(_, a)| a).collect();
+    let mut regions: Vec<(usize, usize)> = Vec::new();
+    for address in addresses {
+        match regions.last_mut() {
+            Some(region) if region.1 == address => region.1 += 1,
+            _ => regions.push((address, address + 1)),
+        }
+    }
+    regions.sort_by_key(|r| std::cmp::Reverse(r.1 - r.0));
+    regions.truncate(MAX_REGIONS);
+    regions.sort();
+    regions
+}
+
+pub fn record(
+    state: &mut State,
+    functions: &BTreeSet<usize>,
+    names: &BTreeMap<usize, String>,
+    markers: &[(usize, String)],
+    max_cycles: usize,
+    columns: usize,
+) -> Timeline {
+    let mut active: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+    let mut input = Vec::new();
+    let mut output = Vec::new();
+    let mut writes = Vec::new();
+    let mut marks: BTreeMap<&str, Vec<(usize, usize)>> = BTreeMap::new();
+    let mut stack = vec![state.pc()];
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        for (_, name) in markers.iter().filter(|m| m.0 == state.pc()) {
+            marks.entry(name).or_default().push((state.cycles(), 1));
+        }
+        let instruction = state.next_instruction();
+        let before = state.cycles();
+        let pointer = state.pointer();
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+        let cost = state.cycles() - before;
+        active
+            .entry(stack[stack.len() - 1])
+            .or_default()
+            .push((before, cost));
+        match instruction {
+            Instruction::Les(_) => input.push((before, 1)),
+            Instruction::Skriv(_) => output.push((before, 1)),
+            Instruction::Lagr(_) => writes.push((before, pointer)),
+            _ => (),
+        }
+        track(&mut stack, &instruction, state.pc(), functions);
+    }
+
+    let cycles = state.cycles();
+    let columns = columns.min(cycles.max(1));
+    let per_column = cycles.div_ceil(columns).max(1);
+    let name = |entry: usize| match names.get(&entry) {
+        Some(name) => format!("{:#06x} {}", entry, name),
+        None => format!("{:#06x}", entry),
+    };
+    let mut rows: Vec<Row> = active
+        .iter()
+        .map(|(&entry, events)| Row {
+            name: name(entry),
+            columns: bucket(events, columns, per_column),
+            total: format!("{} cycles", events.iter().map(|e| e.1).sum::<usize>()),
+            busy: true,
+        })
+        .collect();
+    for (name, events) in marks {
+        rows.push(Row {
+            name: format!("marker {}", name),
+            columns: bucket(&events, columns, per_column),
+            total: format!("{} times", events.len()),
+            busy: false,
+        });
+    }
+    for (label, events) in [("input", &input), ("output", &output)] {
+        rows.push(Row {
+            name: label.to_string(),
+            columns: bucket(events, columns, per_column),
+            total: format!("{} bytes", events.len()),
+            busy: false,
+        });
+    }
+    for (start, end) in regions(&writes) {
+        let events: Vec<(usize, usize)> = writes
+            .iter()
+            .filter(|(_, a)| (start..end).contains(a))
+            .map(|&(cycle, _)| (cycle, 1))
+            .collect();
+        rows.push(Row {
+            name: format!("writes {:#06x}-{:#06x}", start, end - 1),
+            columns: bucket(&events, columns, per_column),
+            total: format!("{} stores", events.len()),
+            busy: false,
+        });
+    }
+    Timeline {
+        cycles,
+        per_column,
+        rows,
+        halt: state.halted(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Timeline {
+    fn ending(&self) -> String {
+        match self.halt {
+            Some(reason) => format!("{} cycles, {}", self.cycles, reason),
+            None => format!("{} cycles, unfinished", self.cycles),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let width = self.rows.iter().map(|r| r.name.len()).max().unwrap_or(0);
+        let mut text = format!(
+            "{:width$} cycles 0-{}, {} per column\n",
+            "",
+            self.cycles.saturating_sub(1),
+            self.per_column,
+            width = width
+        );
+        for row in &self.rows {
+            let bar: String = row
+                .columns
+                .iter()
+                .map(|&c| match c {
+                    0 => ' ',
+                    _ if !row.busy => '*',
+                    c if c * 2 < self.per_column => '-',
+                    _ => '#',
+                })
+                .collect();
+            let _ = writeln!(
+                text,
+                "{:width$} |{}| {}",
+                row.name,
+                bar,
+                row.total,
+                width = width
+            );
+        }
+        let _ = writeln!(text, "{}", self.ending());
+        text
+    }
+
+    pub fn html(&self, title: &str) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\nbody {{ font-family: monospace; }}\ntable {{ border-collapse: collapse; }}\ntd.c {{ width: 8px; height: 16px; padding: 0; }}\ntd.name {{ padding-right: 1em; white-space: nowrap; }}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n<p>cycles 0-{}, {} per column</p>\n<table>\n",
+            escape(title),
+            escape(title),
+            self.cycles.saturating_sub(1),
+            self.per_column
+        );
+        for row in &self.rows {
+            let max = row.columns.iter().copied().max().unwrap_or(0).max(1);
+            let _ = write!(html, "<tr><td class=\"name\">{}</td>", escape(&row.name));
+            for (i, &count) in row.columns.iter().enumerate() {
+                let opacity = if count == 0 {
+                    0.0
+                } else {
+                    0.2 + 0.8 * count as f64 / max as f64
+                };
+                let _ = write!(
+                    html,
+                    "<td class=\"c\" style=\"background: rgba(40, 90, 200, {:.2})\" title=\"cycles {}-{}: {}\"></td>",
+                    opacity,
+                    i * self.per_column,
+                    (i + 1) * self.per_column - 1,
+                    count
+                );
+            }
+            let _ = writeln!(html, "<td class=\"name\">{}</td></tr>", row.total);
+        }
+        let _ = write!(
+            html,
+            "</table>\n<p>{}</p>\n</body>\n</html>\n",
+            self.ending()
+        );
+        html
+    }
+}
@@ -0,0 +1,257 @@
+use crate::emulator::State;
+use crate::history::Step;
+use crate::source::parse_number;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+
+const MAGIC: &[u8; 8] = b"s8trace2";
+const MAGIC_V1: &[u8; 8] = b"s8trace1";
+const BLOCK_STEPS: usize = 4096;
+
+pub struct BlockIndex {
+    pub cycles: u64,
+    pub offset: u64,
+    pub steps: u32,
+}
+
+pub struct Marker {
+    pub cycles: u64,
+    pub name: String,
+}
+
+pub struct Block {
+    pub keyframe: String,
+    pub steps: Vec<Step>,
+}
+
+pub struct TraceWriter {
+    output: BufWriter<File>,
+    offset: u64,
+    keyframe: String,
+    block: Vec<u8>,
+    steps: u32,
+    cycles: u64,
+    index: Vec<BlockIndex>,
+    markers: Vec<Marker>,
+}
+
+fn write_step<W: Write>(mut w: W, step: &Step) -> io::Result<()> {
+    w.write_u64::<LittleEndian>(step.cycles as u64)?;
+    w.write_u16::<LittleEndian>(step.pc as u16)?;
+    w.write_u16::<LittleEndian>(step.op)?;
+    w.write_u16::<LittleEndian>(step.pointer as u16)?;
+    w.write_u8(u8::from(step.flagg))?;
+    w.write_all(&step.registers)
+}
+
+fn read_step<R: Read>(mut r: R) -> io::Result<Step> {
+    let cycles = r.read_u64::<LittleEndian>()? as usize;
+    let pc = usize::from(r.read_u16::<LittleEndian>()?);
+    let op = r.read_u16::<LittleEndian>()?;
+    let pointer = usize::from(r.read_u16::<LittleEndian>()?);
+    let flagg = r.read_u8()? != 0;
+    let mut registers = [0; 16];
+    r.read_exact(&mut registers)?;
+    Ok(Step {
+        pc,
+        cycles,
+        op,
+        pointer,
+        flagg,
+        registers,
+    })
+}
+
+impl TraceWriter {
+    pub fn create(path: &str, program: u64) -> io::Result<TraceWriter> {
+        let mut output = BufWriter::new(File::create(path)?);
+        output.write_all(MAGIC)?;
+        output.write_u64::<LittleEndian>(program)?;
+        Ok(TraceWriter {
+            output,
+            offset: 16,
+            keyframe: String::new(),
+            block: Vec::new(),
+            steps: 0,
+            cycles: 0,
+            index: Vec::new(),
+            markers: Vec::new(),
+        })
+    }
+
+    pub fn record(&mut self, state: &State) -> io::Result<()> {
+        if self.steps == 0 {
+            self.keyframe = state.checkpoint();
+            self.cycles = state.cycles() as u64;
+        }
+        write_step(&mut self.block, &Step::capture(state))?;
+        self.steps += 1;
+        if self.steps as usize == BLOCK_STEPS {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    pub fn mark(&mut self, state: &State, name: &str) {
+        self.markers.push(Marker {
+            cycles: state.cycles() as u64,
+            name: name.to_string(),
+        });
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        if self.steps == 0 {
+            return Ok(());
+        }
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_u32::<LittleEndian>(self.keyframe.len() as u32)?;
+        encoder.write_all(self.keyframe.as_bytes())?;
+        encoder.write_all(&self.block)?;
+        let compressed = encoder.finish()?;
+        self.output
+            .write_u32::<LittleEndian>(compressed.len() as u32)?;
+        self.output.write_all(&compressed)?;
+        self.index.push(BlockIndex {
+            cycles: self.cycles,
+            offset: self.offset,
+            steps: self.steps,
+        });
+        self.offset += 4 + compressed.len() as u64;
+        self.block.clear();
+        self.steps = 0;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.flush_block()?;
+        for block in &self.index {
+            self.output.write_u64::<LittleEndian>(block.cycles)?;
+            self.output.write_u64::<LittleEndian>(block.offset)?;
+            self.output.write_u32::<LittleEndian>(block.steps)?;
+        }
+        let mut markers = Vec::new();
+        for marker in &self.markers {
+            markers.write_u64::<LittleEndian>(marker.cycles)?;
+            markers.write_u16::<LittleEndian>(marker.name.len() as u16)?;
+            markers.write_all(marker.name.as_bytes())?;
+        }
+        self.output.write_all(&markers)?;
+        self.output
+            .write_u32::<LittleEndian>(markers.len() as u32)?;
+        self.output
+            .write_u32::<LittleEndian>(self.index.len() as u32)?;
+        self.output.flush()
+    }
+}
+
+pub struct Trace {
+    pub program: u64,
+    pub index: Vec<BlockIndex>,
+    pub markers: Vec<Marker>,
+    bytes: Vec<u8>,
+}
+
+impl Trace {
+    pub fn open(path: &str) -> Result<Trace, String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        let bad = || String::from("not a trace file");
+        if bytes.len() < 20 || &bytes[..8] != MAGIC && &bytes[..8] != MAGIC_V1 {
+            return Err(bad());
+        }
+        let program = (&bytes[8..16])
+            .read_u64::<LittleEndian>()
+            .map_err(|_| bad())?;
+        let count = (&bytes[bytes.len() - 4..])
+            .read_u32::<LittleEndian>()
+            .map_err(|_| bad())? as usize;
+        let mut end = bytes.len() - 4;
+        let mut markers = Vec::new();
+        if &bytes[..8] == MAGIC {
+            end = end
+                .checked_sub(4)
+                .filter(|&end| end >= 16)
+                .ok_or_else(bad)?;
+            let length = (&bytes[end..end + 4])
+                .read_u32::<LittleEndian>()
+                .map_err(|_| bad())? as usize;
+            end = end
+                .checked_sub(length)
+                .filter(|&end| end >= 16)
+                .ok_or_else(bad)?;
+            let mut data = &bytes[end..end + length];
+            while !data.is_empty() {
+                let cycles = data.read_u64::<LittleEndian>().map_err(|_| bad())?;
+                let mut name =
+                    vec![0; usize::from(data.read_u16::<LittleEndian>().map_err(|_| bad())?)];
+                data.read_exact(&mut name).map_err(|_| bad())?;
+                markers.push(Marker {
+                    cycles,
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                });
+            }
+        }
+        let start = end
+            .checked_sub(count * 20)
+            .filter(|&start| start >= 16)
+            .ok_or_else(bad)?;
+        let mut entries = &bytes[start..end];
+        let mut index = Vec::new();
+        for _ in 0..count {
+            index.push(BlockIndex {
+                cycles: entries.read_u64::<LittleEndian>().map_err(|_| bad())?,
+                offset: entries.read_u64::<LittleEndian>().map_err(|_| bad())?,
+                steps: entries.read_u32::<LittleEndian>().map_err(|_| bad())?,
+            });
+        }
+        Ok(Trace {
+            program,
+            index,
+            markers,
+            bytes,
+        })
+    }
+
+    pub fn block(&self, i: usize) -> Result<Block, String> {
+        let bad = |e: io::Error| format!("block {}: {}", i, e);
+        let entry = &self.index[i];
+        let mut data = self.bytes.get(entry.offset as usize..).unwrap_or(&[]);
+        let length = data.read_u32::<LittleEndian>().map_err(bad)? as usize;
+        let mut decoder = DeflateDecoder::new(data.get(..length).unwrap_or(&[]));
+        let mut keyframe = vec![0; decoder.read_u32::<LittleEndian>().map_err(bad)? as usize];
+        decoder.read_exact(&mut keyframe).map_err(bad)?;
+        let steps = (0..entry.steps)
+            .map(|_| read_step(&mut decoder))
+            .collect::<io::Result<_>>()
+            .map_err(bad)?;
+        Ok(Block {
+            keyframe: String::from_utf8_lossy(&keyframe).into_owned(),
+            steps,
+        })
+    }
+
+    pub fn block_at(&self, cycles: usize) -> Option<usize> {
+        let after = self.index.partition_point(|b| b.cycles <= cycles as u64);
+        after.checked_sub(1)
+    }
+}
+
+pub fn parse_markers(spec: &str) -> Result<Vec<(usize, String)>, String> {
+    spec.split(',')
+        .map(|marker| {
+            let (address, name) = marker
+                .split_once('=')
+                .ok_or_else(|| format!("expected ADDRESS=NAME, got {}", marker))?;
+            let address = parse_number(address)
+                .filter(|&a| a < 0x1000)
+                .ok_or_else(|| format!("{} is not a memory address", address))?;
+            if name.is_empty() || name.len() > 0xffff {
+                return Err(format!("bad marker name in {}", marker));
+            }
+            Ok((address as usize, name.to_string()))
+        })
+        .collect()
+}
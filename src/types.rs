@@ -0,0 +1,86 @@
+use crate::source::parse_number;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DataType {
+    U8(usize),
+    U16Le(usize),
+    String,
+}
+
+#[derive(Clone)]
+pub struct Typed {
+    pub start: usize,
+    pub data: DataType,
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataType::U8(n) => write!(f, "u8[{}]", n),
+            DataType::U16Le(n) => write!(f, "u16le[{}]", n),
+            DataType::String => f.write_str("string"),
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<DataType, String> {
+    let text = text.trim();
+    if text == "string" {
+        return Ok(DataType::String);
+    }
+    let (name, count) = text
+        .strip_suffix(']')
+        .and_then(|t| t.split_once('['))
+        .ok_or_else(|| format!("expected u8[N], u16le[N] or string, got {}", text))?;
+    let count = parse_number(count)
+        .filter(|&n| n > 0 && n <= 0x1000)
+        .ok_or_else(|| format!("bad element count in {}", text))? as usize;
+    match name {
+        "u8" => Ok(DataType::U8(count)),
+        "u16le" => Ok(DataType::U16Le(count)),
+        _ => Err(format!(
+            "unknown type {}, expected u8, u16le or string",
+            name
+        )),
+    }
+}
+
+impl Typed {
+    pub fn end(&self, memory: &[u8]) -> usize {
+        let end = match self.data {
+            DataType::U8(n) => self.start + n,
+            DataType::U16Le(n) => self.start + 2 * n,
+            DataType::String => memory
+                .iter()
+                .skip(self.start)
+                .position(|&b| b == 0)
+                .map_or(memory.len(), |i| self.start + i + 1),
+        };
+        end.min(memory.len())
+    }
+
+    pub fn render(&self, memory: &[u8]) -> String {
+        let bytes = &memory[self.start.min(memory.len())..self.end(memory)];
+        match self.data {
+            DataType::U8(_) => {
+                let values: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                format!("[{}]", values.join(" "))
+            }
+            DataType::U16Le(_) => {
+                let values: Vec<String> = bytes
+                    .chunks(2)
+                    .map(|w| match w {
+                        [lo, hi] => format!("{:#06x}", u16::from(*hi) << 8 | u16::from(*lo)),
+                        _ => format!("{:#04x}?", w[0]),
+                    })
+                    .collect();
+                format!("[{}]", values.join(", "))
+            }
+            DataType::String => {
+                let text = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+                format!("\"{}\"", text.escape_ascii())
+            }
+        }
+    }
+}
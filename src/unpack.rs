@@ -0,0 +1,59 @@
+use crate::emulator::State;
+use crate::parser::{Instruction, MEMORY_SIZE};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub struct Stage {
+    pub entry: usize,
+    pub start: usize,
+    pub bytes: Vec<u8>,
+    pub writers: BTreeSet<usize>,
+    pub cycles: usize,
+}
+
+pub fn unpack(memory: [u8; MEMORY_SIZE], stdin: Vec<u8>, max_cycles: usize) -> Vec<Stage> {
+    let mut state = State::new(memory, stdin);
+    let mut written: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut stages: Vec<Stage> = Vec::new();
+    while state.halted().is_none() && state.cycles() < max_cycles {
+        let pc = state.pc();
+        let fresh = written.contains_key(&pc) || written.contains_key(&(pc + 1));
+        if fresh
+            && !stages
+                .iter()
+                .any(|s| (s.start..s.start + s.bytes.len()).contains(&pc))
+        {
+            let first = if written.contains_key(&pc) {
+                pc
+            } else {
+                pc + 1
+            };
+            let mut start = first;
+            while start > 0 && written.contains_key(&(start - 1)) {
+                start -= 1;
+            }
+            let mut end = first;
+            while written.contains_key(&end) {
+                end += 1;
+            }
+            let mut start = start.min(pc);
+            if !(pc - start).is_multiple_of(2) {
+                start += 1;
+            }
+            let end = end.max(pc + 2).min(MEMORY_SIZE);
+            stages.push(Stage {
+                entry: pc,
+                start,
+                bytes: (start..end).map(|a| state.memory_at(a)).collect(),
+                writers: written.range(start..end).map(|(_, &w)| w).collect(),
+                cycles: state.cycles(),
+            });
+        }
+        if let Instruction::Lagr(_) = state.next_instruction() {
+            written.insert(state.pointer(), pc);
+        }
+        if !matches!(state.iter().next(), Some(Ok(_))) {
+            break;
+        }
+    }
+    stages
+}
@@ -0,0 +1,74 @@
+use crate::parser::{decode_at, memory_image, Instruction, MEMORY_SIZE};
+use std::collections::BTreeSet;
+
+pub struct Finding {
+    pub address: usize,
+    pub error: bool,
+    pub message: String,
+}
+
+pub fn verify(program: &[u8]) -> Vec<Finding> {
+    let memory = memory_image(program);
+    let mut findings = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut queue = vec![0];
+    let mut stopp = false;
+
+    while let Some(pc) = queue.pop() {
+        if pc + 1 >= MEMORY_SIZE || !seen.insert(pc) {
+            continue;
+        }
+        let instruction = decode_at(&memory, pc);
+        if let Some(target) = instruction.targets() {
+            let target = usize::from(target);
+            if target % 2 != 0 {
+                findings.push(Finding {
+                    address: pc,
+                    error: true,
+                    message: format!("jump target {:#06x} is odd", target),
+                });
+            }
+            if target >= program.len() {
+                findings.push(Finding {
+                    address: pc,
+                    error: true,
+                    message: format!(
+                        "jump target {:#06x} is outside the program ({:#06x} bytes)",
+                        target,
+                        program.len()
+                    ),
+                });
+            }
+        }
+        match instruction {
+            Instruction::Finn(target) if usize::from(target) >= program.len() => {
+                findings.push(Finding {
+                    address: pc,
+                    error: false,
+                    message: format!(
+                        "FINN {:#06x} points at memory not initialised by the program",
+                        target
+                    ),
+                })
+            }
+            Instruction::Data(op) => findings.push(Finding {
+                address: pc,
+                error: true,
+                message: format!("data word {:#06x} is reachable as code", op),
+            }),
+            Instruction::Stopp => stopp = true,
+            _ => (),
+        }
+        queue.extend(instruction.successors(pc));
+    }
+
+    if !stopp {
+        findings.push(Finding {
+            address: 0,
+            error: true,
+            message: String::from("no reachable STOPP"),
+        });
+    }
+    findings.sort_by_key(|f| f.address);
+    findings
+}
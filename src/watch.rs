@@ -0,0 +1,164 @@
+use crate::emulator::State;
+use crate::expr::Expr;
+use crate::source::parse_number;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+pub enum Target {
+    Memory(usize),
+    Pointer,
+}
+
+pub enum Condition {
+    Changed,
+    Equals(u16),
+    NotEquals(u16),
+    Range(u16, u16),
+}
+
+pub struct Watchpoint {
+    pub target: Target,
+    pub condition: Condition,
+    last: Option<u16>,
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.target {
+            Target::Memory(address) => write!(f, "[{:#06x}]", address)?,
+            Target::Pointer => write!(f, "ptr")?,
+        }
+        match self.condition {
+            Condition::Changed => Ok(()),
+            Condition::Equals(v) => write!(f, " == {:#04x}", v),
+            Condition::NotEquals(v) => write!(f, " != {:#04x}", v),
+            Condition::Range(lo, hi) => write!(f, " in {:#04x}..{:#04x}", lo, hi),
+        }
+    }
+}
+
+fn value(token: &str, max: u32) -> Result<u16, String> {
+    match parse_number(token) {
+        Some(value) if value <= max => Ok(value as u16),
+        _ => Err(format!("{} is not a value between 0 and {:#x}", token, max)),
+    }
+}
+
+impl Watchpoint {
+    pub fn parse(text: &str) -> Result<Watchpoint, String> {
+        let usage =
+            || String::from("expected [ADDRESS] or ptr, then nothing, == V, != V or in LO..HI");
+        let text = text.trim();
+        let (target, rest) = if let Some(rest) = text.strip_prefix("ptr") {
+            (Target::Pointer, rest)
+        } else {
+            let rest = text.strip_prefix('[').ok_or_else(usage)?;
+            let (address, rest) = rest.split_once(']').ok_or_else(usage)?;
+            match parse_number(address) {
+                Some(address) if address < 0x1000 => (Target::Memory(address as usize), rest),
+                _ => return Err(format!("{} is not a memory address", address)),
+            }
+        };
+        let max = match target {
+            Target::Memory(_) => 0xff,
+            Target::Pointer => 0xfff,
+        };
+        let mut tokens = rest.split_whitespace();
+        let condition = match (tokens.next(), tokens.next()) {
+            (None, _) => Condition::Changed,
+            (Some("=="), Some(v)) => Condition::Equals(value(v, max)?),
+            (Some("!="), Some(v)) => Condition::NotEquals(value(v, max)?),
+            (Some("in"), Some(range)) => {
+                let (lo, hi) = range.split_once("..").ok_or_else(usage)?;
+                Condition::Range(value(lo, max)?, value(hi, max)?)
+            }
+            _ => return Err(usage()),
+        };
+        if tokens.next().is_some() {
+            return Err(usage());
+        }
+        Ok(Watchpoint {
+            target,
+            condition,
+            last: None,
+        })
+    }
+
+    pub fn value(&self, state: &State) -> u16 {
+        match self.target {
+            Target::Memory(address) => u16::from(state.memory_at(address)),
+            Target::Pointer => state.pointer() as u16,
+        }
+    }
+
+    pub fn matches(&self, value: u16) -> bool {
+        match self.condition {
+            Condition::Changed => true,
+            Condition::Equals(v) => value == v,
+            Condition::NotEquals(v) => value != v,
+            Condition::Range(lo, hi) => lo <= value && value <= hi,
+        }
+    }
+
+    pub fn triggered(&mut self, value: u16) -> bool {
+        let changed = self.last.is_some_and(|last| last != value);
+        self.last = Some(value);
+        changed && self.matches(value)
+    }
+}
+
+pub struct Log {
+    text: String,
+    expr: Expr,
+    every: usize,
+    next: usize,
+    path: String,
+    output: BufWriter<File>,
+}
+
+impl fmt::Display for Log {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} every {} to {}", self.text, self.every, self.path)
+    }
+}
+
+impl Log {
+    pub fn parse(text: &str, aliases: &BTreeMap<usize, String>) -> Result<Log, String> {
+        let usage = || String::from("expected EXPRESSION every N [to FILE]");
+        let (text, rest) = text.rsplit_once(" every ").ok_or_else(usage)?;
+        let mut tokens = rest.split_whitespace();
+        let every = match tokens.next().and_then(parse_number) {
+            Some(every) if every > 0 => every as usize,
+            _ => return Err(usage()),
+        };
+        let path = match (tokens.next(), tokens.next(), tokens.next()) {
+            (None, _, _) => String::from("s8log.csv"),
+            (Some("to"), Some(path), None) => path.to_string(),
+            _ => return Err(usage()),
+        };
+        let expr = Expr::parse(text, aliases)?;
+        let mut output = BufWriter::new(File::create(&path).map_err(|e| e.to_string())?);
+        writeln!(output, "cycle,{}", text.trim()).map_err(|e| e.to_string())?;
+        Ok(Log {
+            text: text.trim().to_string(),
+            expr,
+            every,
+            next: 0,
+            path,
+            output,
+        })
+    }
+
+    pub fn sample(&mut self, state: &State) -> io::Result<()> {
+        if state.cycles() < self.next {
+            return Ok(());
+        }
+        self.next = (state.cycles() / self.every + 1) * self.every;
+        match self.expr.eval(state) {
+            Ok(value) => writeln!(self.output, "{},{}", state.cycles(), value),
+            Err(e) => writeln!(self.output, "{},{}", state.cycles(), e),
+        }
+    }
+}